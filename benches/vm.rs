@@ -0,0 +1,60 @@
+//!
+//! Times the interpreter on the programs in `benches/programs`.  Run
+//! `cargo bench`, optionally followed by part of a program's name
+//!
+extern crate gribc;
+
+use gribc::{ast, lex, runtime, RuntimeConfig};
+use std::env;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const PROGRAMS: &[(&str, &str)] = &[
+    ("calls", include_str!("programs/calls.grib")),
+    ("arithmetic", include_str!("programs/arithmetic.grib")),
+    ("mandelbrot", include_str!("programs/mandelbrot.grib")),
+];
+
+/// How many times each program runs.  The fastest run is the least disturbed by noise
+const RUNS: usize = 7;
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.
+}
+
+fn main() {
+    // `cargo bench` passes `--bench` before any filter
+    let filter = env::args().skip(1).find(|arg| !arg.starts_with('-'));
+
+    for (name, source) in PROGRAMS {
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !name.contains(filter.as_str()))
+        {
+            continue;
+        }
+
+        // Programs are parsed and analyzed the same way `gribc run` does, outside of the timing
+        let tokens = lex::lex(source).expect("Benchmarks lex");
+        let mut program = ast::ast(tokens, Path::new(name)).expect("Benchmarks parse");
+        ast::ref_check(&mut program).expect("Benchmarks pass analysis");
+
+        let mut times = (0..RUNS)
+            .map(|_| {
+                let start = Instant::now();
+                if let Err(e) = runtime::execute(&program, RuntimeConfig::default()) {
+                    panic!("{} failed: {}", name, e);
+                }
+                start.elapsed()
+            })
+            .collect::<Vec<_>>();
+        times.sort();
+
+        println!(
+            "{:<12} fastest {:>8.1}ms   median {:>8.1}ms",
+            name,
+            millis(times[0]),
+            millis(times[RUNS / 2])
+        );
+    }
+}
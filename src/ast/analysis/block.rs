@@ -2,8 +2,10 @@
 /// Walk functions related to statements and blocks
 ///
 use super::expression::walk_expression;
+use super::lint::{unreachable, BindingKind};
 use super::*;
 use ast::node::*;
+use location::Located;
 
 /// Add declarations to scope
 pub fn walk_decl(
@@ -15,6 +17,12 @@ pub fn walk_decl(
     for d in decl.declarations.iter_mut() {
         walk_expression(&mut d.value, scope, lams, cap)?;
 
+        let Located { data, start, end } = d.identifier.clone();
+        if scope.is_import(data) {
+            let (start, end) = (start.clone(), end.clone());
+            lams.report(scope, LintKind::ShadowedImport, Some(data), start, end);
+        }
+
         if !scope.insert_var(d.identifier.data, decl.mutable) {
             let start = d.identifier.start.clone();
             let end = d.identifier.end.clone();
@@ -24,8 +32,16 @@ pub fn walk_decl(
                 start,
                 end,
                 kind: WalkErrorType::InvalidRedefinition(name),
+                module: None,
             });
         }
+
+        let kind = if decl.mutable {
+            BindingKind::Variable
+        } else {
+            BindingKind::Im
+        };
+        lams.bind(scope, kind, data, start, end);
     }
 
     Ok(())
@@ -38,6 +54,8 @@ pub fn walk_ast(
     lams: &mut Lams,
     cap: &mut CaptureStack,
 ) -> WalkResult {
+    unreachable(nodes, scope, lams);
+
     for node in nodes.iter_mut() {
         match node {
             // Record the number of declarations in a loop or function up
@@ -53,9 +71,11 @@ pub fn walk_ast(
                             kind: WalkErrorType::InvalidReturn,
                             start: flow.start.clone(),
                             end: flow.end.clone(),
+                            module: None,
                         });
                     }
                 }
+                BreakType::Throw(expr) => walk_expression(expr, scope, lams, cap)?,
                 BreakType::Break | BreakType::Continue => {
                     if let Some(allocs) = scope.loop_alloc {
                         flow.allocations = allocs;
@@ -63,13 +83,18 @@ pub fn walk_ast(
                         let kind = match &flow.kind {
                             BreakType::Break => WalkErrorType::InvalidBreak,
                             BreakType::Continue => WalkErrorType::InvalidContinue,
-                            BreakType::Return(_) => panic!("Unreachable"),
+                            _ => panic!("Unreachable"),
                         };
 
                         let start = flow.start.clone();
                         let end = flow.end.clone();
 
-                        return Err(WalkError { kind, start, end });
+                        return Err(WalkError {
+                            kind,
+                            start,
+                            end,
+                            module: None,
+                        });
                     }
                 }
             },
@@ -82,6 +107,24 @@ pub fn walk_ast(
                 scope.sub_loop(|sub, block| walk_ast(block, sub, lams, cap), block)?;
             }
             Node::Declaration(declaration) => walk_decl(declaration, scope, lams, cap)?,
+            Node::Try { body, catch } => {
+                scope.sub_block(|scope, body| walk_ast(body, scope, lams, cap), body)?;
+
+                // The thrown value is declared at the start of the catch block
+                let Catch { param, block } = catch;
+                scope.sub(|scope| {
+                    if let Some(param) = param.as_ref() {
+                        scope.insert_var(param.name, true);
+                    }
+                    walk_ast(block, scope, lams, cap)?;
+                    scope.check_decls(block);
+
+                    if let Some(param) = param {
+                        param.captured = scope.take_captured(param.name);
+                    }
+                    Ok(())
+                })?;
+            }
             Node::LogicChain {
                 if_block,
                 elseifs,
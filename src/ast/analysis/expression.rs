@@ -24,7 +24,7 @@ pub fn walk_expression(
                 walk_expression(e, scope, lams, cap)?;
             }
         }
-        Expression::FunctionCall { function, args } => {
+        Expression::FunctionCall { function, args, .. } => {
             walk_expression(function, scope, lams, cap)?;
             for e in args {
                 walk_expression(e, scope, lams, cap)?;
@@ -43,12 +43,14 @@ pub fn walk_expression(
                                 kind: WalkErrorType::IdentifierNotFound(s),
                                 start: i.start.clone(),
                                 end: i.end.clone(),
+                                module: None,
                             });
                         } else if !scope.has_editable(s, cap) {
                             return Err(WalkError {
                                 kind: WalkErrorType::ImmutableModification(s),
                                 start: i.start.clone(),
                                 end: i.end.clone(),
+                                module: None,
                             });
                         }
                     }
@@ -88,8 +90,10 @@ pub fn walk_expression(
                                         start: ident.start,
                                         end: ident.end,
                                         kind: WalkErrorType::IdentifierNotFound(ident.data),
+                                        module: None,
                                     });
                                 }
+                                lams.read(scope, ident.data);
                                 if scope.in_second_pass() {
                                     let op = scope.runtime_value(ident.data);
                                     auto.get = op.map(AutoPropValue::Value);
@@ -110,12 +114,14 @@ pub fn walk_expression(
                                             start: ident.start.clone(),
                                             end: ident.end.clone(),
                                             kind: WalkErrorType::IdentifierNotFound(ident.data),
+                                            module: None,
                                         });
                                     } else if !scope.prop_check_mut(ident.data) {
                                         return Err(WalkError {
                                             start: ident.start.clone(),
                                             end: ident.end.clone(),
                                             kind: WalkErrorType::ImmutableModification(ident.data),
+                                            module: None,
                                         });
                                     }
                                 }
@@ -142,8 +148,10 @@ pub fn walk_expression(
                     start: identifier.start.clone(),
                     end: identifier.end.clone(),
                     kind: WalkErrorType::IdentifierNotFound(identifier.data),
+                    module: None,
                 });
             }
+            lams.read(scope, identifier.data);
             if scope.in_second_pass() {
                 if let Some(val) = scope.runtime_value(identifier.data) {
                     *expression = Expression::Value(val);
@@ -155,6 +163,7 @@ pub fn walk_expression(
                 kind: WalkErrorType::InvalidThis,
                 start: start.clone(),
                 end: end.clone(),
+                module: None,
             });
         }
         _ => {}
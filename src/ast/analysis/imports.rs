@@ -16,7 +16,10 @@ pub fn walk_procedure(
     cap: &mut CaptureStack,
 ) -> Result<(), WalkError> {
     scope.sub_fnc(
-        |scope, _, body| walk_ast(body, scope, lams, cap),
+        |scope, params, body| {
+            lams.bind_params(scope, params);
+            walk_ast(body, scope, lams, cap)
+        },
         &mut procedure.param_list,
         &mut procedure.body,
     )
@@ -27,6 +30,7 @@ pub fn walk_import(
     import: &Import,
     modules: &ModuleStore,
     strings: Strings,
+    host: &HostRegistry,
     scope: &mut Scope,
 ) -> WalkResult {
     match &import.kind {
@@ -37,7 +41,7 @@ pub fn walk_import(
                         scope.import_function(proc.identifier.data, *mod_ind, fn_ind);
                     }
                 }
-                Module::Native(_pkg) => {
+                Module::Native(_) | Module::Host(_) => {
                     panic!("Branch should be unreachable.  Native all imports are rewritten");
                 }
             };
@@ -56,6 +60,7 @@ pub fn walk_import(
                         start: located.start.clone(),
                         end: located.end.clone(),
                         kind: WalkErrorType::InvalidRedefinition(name),
+                        module: None,
                     });
                 }
                 inserted.insert(name);
@@ -63,6 +68,7 @@ pub fn walk_import(
                 let contains = match &import.module {
                     Module::Custom(ind) => modules[*ind].get_function(name).is_some(),
                     Module::Native(pkg) => pkg.fn_from_str(&*strings[name]).is_some(),
+                    Module::Host(pkg) => host.function(*pkg, &strings[name]).is_some(),
                 };
 
                 if !contains {
@@ -70,6 +76,7 @@ pub fn walk_import(
                         start: located.start.clone(),
                         end: located.end.clone(),
                         kind: WalkErrorType::IdentifierNotFound(name),
+                        module: None,
                     });
                 }
 
@@ -84,6 +91,11 @@ pub fn walk_import(
                             scope.native_function(name, fnc.clone());
                         }
                     }
+                    Module::Host(pkg) => {
+                        if let Some(fnc) = host.function(*pkg, &strings[name]) {
+                            scope.host_function(name, fnc);
+                        }
+                    }
                 }
             }
         }
@@ -99,11 +111,12 @@ pub fn walk_module(
     lams: &mut Lams,
     cap: &mut CaptureStack,
     strings: Strings,
+    host: &HostRegistry,
 ) -> WalkResult {
     let mut scope = Scope::new();
 
     for import in &module.imports {
-        walk_import(import, modules, strings, &mut scope)?;
+        walk_import(import, modules, strings, host, &mut scope)?;
     }
 
     for (ind, Procedure { identifier, .. }) in module.functions.iter().enumerate() {
@@ -112,13 +125,16 @@ pub fn walk_module(
                 start: identifier.start.clone(),
                 end: identifier.end.clone(),
                 kind: WalkErrorType::InvalidRedefinition(identifier.data),
+                module: None,
             });
         }
     }
 
+    lams.module = Some(module_ind);
     for p in &mut module.functions {
         walk_procedure(p, &mut scope, lams, cap)?;
     }
+    lams.module = None;
 
     Ok(())
 }
@@ -6,6 +6,11 @@ pub trait LambdaLike {
     fn add_params(&self, scope: &mut Scope);
     fn check(&mut self, scope: &mut Scope, cap: &mut CaptureStack, top_stack: &mut Scope);
     fn get_body(&mut self) -> &mut LambdaBody;
+    fn set_module(&mut self, module: Option<usize>);
+    /// Parameters that can go unused.  A setter has to take its parameter
+    fn params(&self) -> Option<&Parameters> {
+        None
+    }
 }
 
 impl LambdaLike for Lambda {
@@ -15,24 +20,40 @@ impl LambdaLike for Lambda {
 
     fn check(&mut self, scope: &mut Scope, cap: &mut CaptureStack, top_stack: &mut Scope) {
         scope.check_params(&mut self.param_list);
-        self.captured = cap.pop(top_stack);
+        let (names, captured) = cap.pop(top_stack);
+        self.captured = captured;
+        self.captured_names = names;
     }
 
     fn get_body(&mut self) -> &mut LambdaBody {
         &mut self.body
     }
+
+    fn params(&self) -> Option<&Parameters> {
+        Some(&self.param_list)
+    }
+
+    fn set_module(&mut self, module: Option<usize>) {
+        self.module = module;
+    }
 }
 
 impl LambdaLike for GetProp {
     fn add_params(&self, _: &mut Scope) {}
 
     fn check(&mut self, _: &mut Scope, cap: &mut CaptureStack, top_stack: &mut Scope) {
-        self.capture = cap.pop(top_stack);
+        let (names, capture) = cap.pop(top_stack);
+        self.capture = capture;
+        self.capture_names = names;
     }
 
     fn get_body(&mut self) -> &mut LambdaBody {
         &mut self.block
     }
+
+    fn set_module(&mut self, module: Option<usize>) {
+        self.module = module;
+    }
 }
 
 impl LambdaLike for SetProp {
@@ -42,12 +63,18 @@ impl LambdaLike for SetProp {
 
     fn check(&mut self, scope: &mut Scope, cap: &mut CaptureStack, top_stack: &mut Scope) {
         self.param_captured = scope.take_captured(self.param);
-        self.capture = cap.pop(top_stack);
+        let (names, capture) = cap.pop(top_stack);
+        self.capture = capture;
+        self.capture_names = names;
     }
 
     fn get_body(&mut self) -> &mut LambdaBody {
         &mut self.block
     }
+
+    fn set_module(&mut self, module: Option<usize>) {
+        self.module = module;
+    }
 }
 
 fn walk_lambda_block(
@@ -72,6 +99,8 @@ pub fn eval_lambda<T: LambdaLike>(
     cap: &mut CaptureStack,
     lams: &mut Lams,
 ) -> WalkResult {
+    lam.set_module(lams.module);
+
     scope.sub_with(SubState::InFunc, |scope| {
         let mut second = scope.clone();
         cap.add(scope.level);
@@ -80,6 +109,9 @@ pub fn eval_lambda<T: LambdaLike>(
         second.lam_pass = Some(LamPass::Second);
 
         lam.add_params(scope);
+        if let Some(params) = lam.params() {
+            lams.bind_params(scope, params);
+        }
         walk_lambda_block(lam.get_body(), scope, lams, cap)?;
 
         lam.check(scope, cap, &mut second);
@@ -0,0 +1,292 @@
+///
+/// Warnings about code that runs but is likely a mistake.  Lints are
+/// collected while the program is analyzed, from the same scopes
+///
+use super::{Lams, Scope};
+use crate::keyword_map;
+use ast::node::*;
+use location::Location;
+
+keyword_map!(LintKind {
+    UnusedVariable -> "unused-variable",
+    UnusedParameter -> "unused-parameter",
+    UnusedImport -> "unused-import",
+    ShadowedImport -> "shadowed-import",
+    UnreachableCode -> "unreachable-code",
+    UnreadIm -> "unread-im",
+});
+
+impl LintKind {
+    /// Every lint, by name
+    pub fn names() -> &'static [&'static str] {
+        Self::MEMBERS
+    }
+}
+
+/// Which lints are reported
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintConfig {
+    enabled: Vec<LintKind>,
+}
+
+impl LintConfig {
+    /// Every lint but unused-parameter, since callbacks often ignore some of theirs
+    pub fn new() -> Self {
+        let mut config = Self::all();
+        config.disable(LintKind::UnusedParameter);
+        config
+    }
+
+    pub fn all() -> Self {
+        Self {
+            enabled: LintKind::names()
+                .iter()
+                .flat_map(|name| LintKind::from_str(name))
+                .collect(),
+        }
+    }
+
+    pub fn enable(&mut self, kind: LintKind) {
+        if !self.is_enabled(&kind) {
+            self.enabled.push(kind);
+        }
+    }
+
+    pub fn disable(&mut self, kind: LintKind) {
+        self.enabled.retain(|k| *k != kind);
+    }
+
+    pub fn is_enabled(&self, kind: &LintKind) -> bool {
+        self.enabled.contains(kind)
+    }
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Lint {
+    pub kind: LintKind,
+    /// The variable or import the lint is about, if there is one
+    pub name: Option<usize>,
+    pub start: Location,
+    pub end: Location,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum BindingKind {
+    Variable,
+    Im,
+    Parameter,
+    Import,
+}
+
+/// A definition in the main program and whether it's been used
+#[derive(Debug)]
+struct Binding {
+    kind: BindingKind,
+    name: Option<usize>,
+    start: Location,
+    end: Location,
+    read: bool,
+}
+
+/// Records what the program defines and reads while it's analyzed.
+/// Definitions in scopes refer to their binding by its index, which
+/// copies of the scope share
+#[derive(Debug, Default)]
+pub(super) struct Usage {
+    bindings: Vec<Binding>,
+    lints: Vec<Lint>,
+}
+
+impl Usage {
+    pub fn bind(
+        &mut self,
+        kind: BindingKind,
+        name: Option<usize>,
+        start: Location,
+        end: Location,
+    ) -> usize {
+        self.bindings.push(Binding {
+            kind,
+            name,
+            start,
+            end,
+            read: false,
+        });
+        self.bindings.len() - 1
+    }
+
+    pub fn read(&mut self, binding: usize) {
+        self.bindings[binding].read = true;
+    }
+
+    pub fn report(&mut self, lint: Lint) {
+        self.lints.push(lint);
+    }
+
+    /// The enabled lints in the order they appear in the program.
+    /// Names starting with an underscore are allowed to go unused
+    pub fn finish(self, config: &LintConfig, strings: &[String]) -> Vec<Lint> {
+        let unused = self.bindings.into_iter().filter(|b| !b.read).flat_map(|b| {
+            let name = b.name.map(|n| strings[n].as_str());
+            let end = match name {
+                Some(name) if b.end == b.start => {
+                    Location::at(b.start.get_row(), b.start.get_col() + name.chars().count())
+                }
+                _ => b.end,
+            };
+            let kind = match b.kind {
+                _ if name.is_some_and(|n| n.starts_with('_')) => return None,
+                BindingKind::Variable => LintKind::UnusedVariable,
+                BindingKind::Im => LintKind::UnreadIm,
+                BindingKind::Parameter => LintKind::UnusedParameter,
+                BindingKind::Import => LintKind::UnusedImport,
+            };
+            Some(Lint {
+                kind,
+                name: b.name,
+                start: b.start,
+                end,
+            })
+        });
+
+        let mut lints = self
+            .lints
+            .into_iter()
+            .chain(unused)
+            .filter(|lint| config.is_enabled(&lint.kind))
+            .collect::<Vec<_>>();
+        lints.sort_by(|a, b| a.start.cmp(&b.start));
+        lints
+    }
+}
+
+impl<'a> Lams<'a> {
+    /// Whether lints are being collected for the code being walked.
+    /// Lambdas are walked twice, so only their first pass counts
+    fn linting(&self, scope: &Scope) -> bool {
+        self.usage.is_some() && self.module.is_none() && scope.in_first_pass()
+    }
+
+    /// Records a definition that was just added to the scope
+    pub(super) fn bind(
+        &mut self,
+        scope: &mut Scope,
+        kind: BindingKind,
+        name: usize,
+        start: Location,
+        end: Location,
+    ) {
+        if !self.linting(scope) {
+            return;
+        }
+        if let Some(usage) = self.usage.as_mut() {
+            let binding = usage.bind(kind, Some(name), start, end);
+            scope.set_binding(name, binding);
+        }
+    }
+
+    /// Records the parameters that were just added to the scope.
+    /// Parameters only keep where they start
+    pub(super) fn bind_params(&mut self, scope: &mut Scope, params: &Parameters) {
+        for (param, start) in params.all_params().zip(&params.locations) {
+            self.bind(
+                scope,
+                BindingKind::Parameter,
+                param.name,
+                start.clone(),
+                start.clone(),
+            );
+        }
+    }
+
+    /// Marks the definition a name refers to as used
+    pub(super) fn read(&mut self, scope: &Scope, name: usize) {
+        if !self.linting(scope) {
+            return;
+        }
+        if let (Some(usage), Some(binding)) = (self.usage.as_mut(), scope.binding(name)) {
+            usage.read(binding);
+        }
+    }
+
+    pub(super) fn report(
+        &mut self,
+        scope: &Scope,
+        kind: LintKind,
+        name: Option<usize>,
+        start: Location,
+        end: Location,
+    ) {
+        if !self.linting(scope) {
+            return;
+        }
+        if let Some(usage) = self.usage.as_mut() {
+            usage.report(Lint {
+                kind,
+                name,
+                start,
+                end,
+            });
+        }
+    }
+}
+
+/// Records the names a main program import brings into scope.  Wildcard
+/// imports of packages only list the names the program uses
+pub(super) fn bind_import(
+    import: &Import,
+    modules: &ModuleStore,
+    scope: &mut Scope,
+    lams: &mut Lams,
+) {
+    let (start, end) = (import.start.clone(), import.end.clone());
+
+    match &import.kind {
+        ImportKind::ModuleObject(name) => lams.bind(
+            scope,
+            BindingKind::Import,
+            name.data,
+            name.start.clone(),
+            name.end.clone(),
+        ),
+        ImportKind::List(names) if names.is_empty() => {
+            lams.report(scope, LintKind::UnusedImport, None, start, end)
+        }
+        ImportKind::List(names) => {
+            for name in names.iter().filter(|n| n.start != Location::default()) {
+                lams.bind(
+                    scope,
+                    BindingKind::Import,
+                    name.data,
+                    name.start.clone(),
+                    name.end.clone(),
+                );
+            }
+        }
+        // Every function shares one binding, so using any of them uses the import
+        ImportKind::All => {
+            if let (Module::Custom(module), Some(usage)) = (&import.module, lams.usage.as_mut()) {
+                let binding = usage.bind(BindingKind::Import, None, start, end);
+                for proc in modules[*module].pub_functions() {
+                    scope.set_binding(proc.identifier.data, binding);
+                }
+            }
+        }
+    }
+}
+
+/// Reports the first statement after a return, break, continue, or throw
+pub(super) fn unreachable(block: &Block, scope: &Scope, lams: &mut Lams) {
+    let flow = block.iter().position(|n| matches!(n, Node::ControlFlow(_)));
+
+    if let Some(start) = flow.and_then(|i| block.location(i + 1)) {
+        let end = block.end.clone();
+        lams.report(scope, LintKind::UnreachableCode, None, start.clone(), end);
+    }
+}
@@ -2,12 +2,16 @@ mod block;
 mod expression;
 mod imports;
 mod lambda;
+mod lint;
 mod scope;
 
 use self::block::walk_ast;
 use self::imports::*;
+use self::lint::{bind_import, Usage};
 use self::scope::*;
 
+pub use self::lint::{Lint, LintConfig, LintKind};
+
 use ast::node::*;
 use location::Location;
 
@@ -27,63 +31,208 @@ pub enum WalkErrorType {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct WalkError {
-    start: Location,
-    end: Location,
-    kind: WalkErrorType,
+    pub start: Location,
+    pub end: Location,
+    pub kind: WalkErrorType,
+    /// Index of the custom module the error was found in.
+    /// `None` if the error is in the main program
+    pub module: Option<usize>,
+}
+
+impl WalkError {
+    fn in_module(self, module: usize) -> Self {
+        Self {
+            module: Some(module),
+            ..self
+        }
+    }
 }
 
 pub struct Lams<'a> {
     lambdas: &'a mut Lambdas,
     getters: &'a mut Vec<GetProp>,
     setters: &'a mut Vec<SetProp>,
+    /// The custom module currently being walked
+    module: Option<usize>,
+    /// What the program defines and uses, when it's being linted
+    usage: Option<&'a mut Usage>,
+}
+
+/// The number of each kind of program item that has been analyzed
+#[derive(Clone, Copy, Debug, Default)]
+struct Checked {
+    modules: usize,
+    imports: usize,
+    functions: usize,
+    lambdas: usize,
+    getters: usize,
+    setters: usize,
+}
+
+impl Checked {
+    fn of(program: &Program) -> Self {
+        Self {
+            modules: program.modules.len(),
+            imports: program.imports.len(),
+            functions: program.functions.len(),
+            lambdas: program.lambdas.len(),
+            getters: program.getters.len(),
+            setters: program.setters.len(),
+        }
+    }
+
+    /// Removes any items that were added after the count was taken
+    fn truncate(&self, program: &mut Program) {
+        program.modules.truncate(self.modules);
+        program.imports.truncate(self.imports);
+        program.functions.truncate(self.functions);
+        program.lambdas.truncate(self.lambdas);
+        program.getters.truncate(self.getters);
+        program.setters.truncate(self.setters);
+    }
 }
 
 pub fn ref_check(program: &mut Program) -> Result<(), WalkError> {
+    let mut scope = Scope::new();
+    walk_program(program, &mut scope, Checked::default(), None)?;
+    scope.check_decls(&mut program.body);
+
+    Ok(())
+}
+
+/// Analyzes the program like `ref_check`, returning the enabled lints
+/// found in the main program.  Imported modules aren't linted
+pub fn lint(program: &mut Program, config: &LintConfig) -> Result<Vec<Lint>, WalkError> {
+    let mut scope = Scope::new();
+    let mut usage = Usage::default();
+    walk_program(program, &mut scope, Checked::default(), Some(&mut usage))?;
+    scope.check_decls(&mut program.body);
+
+    Ok(usage.finish(config, &program.strings))
+}
+
+/// Analysis state that is kept between REPL inputs so that
+/// declarations, procedures, and imports stay in scope
+pub struct ReplScope {
+    scope: Scope,
+    /// The scope before the most recent input
+    previous: Scope,
+    checked: Checked,
+}
+
+impl ReplScope {
+    pub fn new() -> Self {
+        Self {
+            scope: Scope::new(),
+            previous: Scope::new(),
+            checked: Checked::default(),
+        }
+    }
+
+    /// Checks the program's body along with any items that were added to
+    /// the program since the last check.  If an error is found the new
+    /// items are removed from the program and the scope is left unchanged
+    pub fn check(&mut self, program: &mut Program) -> Result<(), WalkError> {
+        let mut scope = self.scope.clone();
+        if let Err(e) = walk_program(program, &mut scope, self.checked, None) {
+            self.discard(program);
+            return Err(e);
+        }
+
+        // Top level declarations outlive the input they were made in, so
+        // they are kept on the heap where lambdas from later inputs can share them
+        program.body.allocations = 0;
+        for node in program.body.iter_mut() {
+            if let Node::Declaration(Declaration {
+                mutable: true,
+                declarations,
+            }) = node
+            {
+                for decl in declarations {
+                    decl.captured = true;
+                }
+            }
+        }
+
+        self.previous = std::mem::replace(&mut self.scope, scope);
+        self.checked = Checked::of(program);
+
+        Ok(())
+    }
+
+    /// Removes items added to the program since the last successful check
+    pub fn discard(&self, program: &mut Program) {
+        self.checked.truncate(program);
+    }
+
+    /// Undoes the declarations made by the last checked input
+    /// after it failed to run to completion
+    pub fn rollback(&mut self) {
+        self.scope = self.previous.clone();
+    }
+}
+
+/// Walks the program body and all items past the ones that were already checked
+fn walk_program(
+    program: &mut Program,
+    scope: &mut Scope,
+    checked: Checked,
+    usage: Option<&mut Usage>,
+) -> WalkResult {
     let body = &mut program.body;
     let modules = &mut program.modules;
 
-    let mut scope = Scope::new();
     let mut stack = CaptureStack::new();
 
     let mut lambdas = Lams {
         lambdas: &mut program.lambdas,
         getters: &mut program.getters,
         setters: &mut program.setters,
+        module: None,
+        usage,
     };
 
-    for mod_ind in 0..modules.len() {
+    for mod_ind in checked.modules..modules.len() {
         let mut module = std::mem::take(&mut modules[mod_ind]);
-        walk_module(
+        let result = walk_module(
             &mut module,
             mod_ind,
             modules,
             &mut lambdas,
             &mut stack,
             &program.strings,
-        )?;
+            &program.host,
+        );
         modules[mod_ind] = module;
+        result.map_err(|e| e.in_module(mod_ind))?;
     }
 
-    for import in &program.imports {
-        walk_import(import, &program.modules, &program.strings, &mut scope)?;
+    for import in &program.imports[checked.imports..] {
+        walk_import(
+            import,
+            &program.modules,
+            &program.strings,
+            &program.host,
+            scope,
+        )?;
+        bind_import(import, &program.modules, scope, &mut lambdas);
     }
 
-    for (ind, Procedure { identifier, .. }) in program.functions.iter().enumerate() {
+    let functions = program.functions.iter().enumerate().skip(checked.functions);
+    for (ind, Procedure { identifier, .. }) in functions {
         if !scope.insert_fn(identifier.data, ind, None) {
             return Err(WalkError {
                 start: identifier.start.clone(),
                 end: identifier.end.clone(),
                 kind: WalkErrorType::InvalidRedefinition(identifier.data),
+                module: None,
             });
         }
     }
 
-    for function in &mut program.functions {
-        walk_procedure(function, &mut scope, &mut lambdas, &mut stack)?;
+    for function in &mut program.functions[checked.functions..] {
+        walk_procedure(function, scope, &mut lambdas, &mut stack)?;
     }
 
-    walk_ast(body, &mut scope, &mut lambdas, &mut stack)?;
-    scope.check_decls(body);
-
-    Ok(())
+    walk_ast(body, scope, &mut lambdas, &mut stack)
 }
@@ -41,9 +41,9 @@ impl CaptureStack {
     }
 
     /// Pops off the top captured stack and converts the array of identifiers
-    /// to an array of index offsets.  The scope passed in must be a copy of
-    /// self before the analysis took place.
-    pub fn pop(&mut self, top_scope: &mut Scope) -> Vec<usize> {
+    /// to an array of index offsets, returned along with the identifiers.  The
+    /// scope passed in must be a copy of self before the analysis took place.
+    pub fn pop(&mut self, top_scope: &mut Scope) -> (Vec<usize>, Vec<usize>) {
         self.stack
             .pop()
             .map(|end| {
@@ -65,11 +65,11 @@ impl CaptureStack {
                 // We can insert all the valid names as mutable because we've already checked
                 // for mutability errors in the first lambda pass
                 // This second pass serves only to calculate the stack offsets
-                for name in names {
+                for &name in &names {
                     top_scope.insert_mut(name);
                 }
 
-                captured
+                (names, captured)
             })
             .unwrap_or_default()
     }
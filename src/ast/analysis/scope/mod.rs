@@ -37,6 +37,8 @@ enum DefType {
 struct DefData {
     kind: DefType,
     level: usize,
+    /// Index of the definition's binding when linting
+    binding: Option<usize>,
 }
 
 impl DefData {
@@ -246,6 +248,7 @@ impl Scope {
                 DefData {
                     level: self.level,
                     kind,
+                    binding: None,
                 },
             )
             .filter(|d| d.level == self.level && !d.is_import())
@@ -314,11 +317,17 @@ impl Scope {
 
     /// Imports native function
     pub fn native_function(&mut self, name: usize, fnc: NativeFunction) {
+        self.import_native(name, Callable::Native(fnc));
+    }
+
+    /// Imports a function registered by the host
+    pub fn host_function(&mut self, name: usize, fnc: HostFunction) {
+        self.import_native(name, Callable::Host(fnc));
+    }
+
+    fn import_native(&mut self, name: usize, callable: Callable) {
         if self.level == 0 {
-            self.insert(
-                name,
-                DefType::Import(StaticValue::Function(Callable::Native(fnc))),
-            );
+            self.insert(name, DefType::Import(StaticValue::Function(callable)));
         }
     }
 
@@ -332,6 +341,23 @@ impl Scope {
         }
     }
 
+    /// Ties a definition to the binding the lint pass made for it
+    pub fn set_binding(&mut self, name: usize, binding: usize) {
+        if let Some(data) = self.scope.get_mut(&name) {
+            data.binding = Some(binding);
+        }
+    }
+
+    /// The lint pass's binding for a definition, if it has one
+    pub fn binding(&self, name: usize) -> Option<usize> {
+        self.scope.get(&name).and_then(|d| d.binding)
+    }
+
+    /// Whether the name currently refers to an import
+    pub fn is_import(&self, name: usize) -> bool {
+        self.scope.get(&name).filter(|d| d.is_import()).is_some()
+    }
+
     fn is_captured(&self, name: usize) -> bool {
         self.scope.get(&name).filter(|d| d.is_captured()).is_some()
     }
@@ -0,0 +1,423 @@
+///
+/// Reading and writing analyzed programs so they can be run without being parsed again
+///
+use ast::node::*;
+use runtime::values::Callable;
+use std::fmt::{self, Display, Formatter};
+
+/// The first word of a compiled program file
+pub const COMPILED_MAGIC: &str = "gribc-program";
+
+/// Incremented whenever the layout of the syntax tree changes
+pub const COMPILED_VERSION: u32 = 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadError {
+    MissingHeader,
+    Version {
+        found: String,
+    },
+    Malformed(String),
+    /// The tree refers to an item that doesn't exist
+    InvalidReference(String),
+    /// The tree contains names that are only present before analysis
+    Unanalyzed,
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            LoadError::MissingHeader => write!(f, "missing `{}` header", COMPILED_MAGIC),
+            LoadError::Version { found } => write!(
+                f,
+                "compiled with format version {}, but this gribc reads version {}",
+                found, COMPILED_VERSION
+            ),
+            LoadError::Malformed(msg) => write!(f, "malformed program: {}", msg),
+            LoadError::InvalidReference(msg) => write!(f, "invalid reference to {}", msg),
+            LoadError::Unanalyzed => write!(f, "program contains unresolved identifiers"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Why a program can't be written by `write_compiled`
+#[derive(Clone, Debug, PartialEq)]
+pub enum SaveError {
+    /// Host packages aren't saved with the program, so it couldn't be loaded again
+    HostImport(String),
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            SaveError::HostImport(name) => write!(
+                f,
+                "programs that import the host package `{}` can't be compiled",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+/// Whether the file contents look like a compiled program
+pub fn is_compiled(contents: &str) -> bool {
+    contents.starts_with(COMPILED_MAGIC)
+}
+
+/// Serializes an analyzed program behind a version header
+pub fn write_compiled(program: &Program) -> Result<String, SaveError> {
+    let imports = program
+        .imports
+        .iter()
+        .chain(program.modules.iter().flat_map(|m| &m.imports));
+    for import in imports {
+        if let Module::Host(index) = import.module {
+            let name = program.host.get(index).map_or("", |p| &p.name);
+            return Err(SaveError::HostImport(name.to_string()));
+        }
+    }
+
+    let body = serde_json::to_string(program).expect("Syntax trees are always serializable");
+    Ok(format!(
+        "{} {}\n{}\n",
+        COMPILED_MAGIC, COMPILED_VERSION, body
+    ))
+}
+
+/// Loads a program written by `write_compiled`, checking that
+/// every index in the tree points to an existing item
+pub fn read_compiled(contents: &str) -> Result<Program, LoadError> {
+    let (header, body) = contents.split_at(contents.find('\n').unwrap_or(contents.len()));
+    let version = match header.trim_end().split(' ').collect::<Vec<_>>()[..] {
+        [COMPILED_MAGIC, version] => version,
+        _ => return Err(LoadError::MissingHeader),
+    };
+    if version.parse() != Ok(COMPILED_VERSION) {
+        return Err(LoadError::Version {
+            found: version.to_string(),
+        });
+    }
+
+    let program =
+        serde_json::from_str::<Program>(body).map_err(|e| LoadError::Malformed(e.to_string()))?;
+    Validator {
+        program: &program,
+        depth: 0,
+    }
+    .program()?;
+
+    Ok(program)
+}
+
+type Checked = Result<(), LoadError>;
+
+fn check_index(index: usize, len: usize, item: &str) -> Checked {
+    if index < len {
+        Ok(())
+    } else {
+        Err(LoadError::InvalidReference(format!("{} {}", item, index)))
+    }
+}
+
+struct Validator<'a> {
+    program: &'a Program,
+    /// The number of variables on the stack where the code being checked runs
+    depth: usize,
+}
+
+impl<'a> Validator<'a> {
+    fn string(&self, index: usize) -> Checked {
+        check_index(index, self.program.strings.len(), "string")
+    }
+
+    fn module(&self, module: &Module) -> Checked {
+        match module {
+            Module::Custom(index) => check_index(*index, self.program.modules.len(), "module"),
+            Module::Native(_) => Ok(()),
+            Module::Host(index) => check_index(*index, self.program.host.len(), "host package"),
+        }
+    }
+
+    /// Checks the module a lambda, getter, or setter was defined in
+    fn defined_in(&self, module: Option<usize>) -> Checked {
+        module.map_or(Ok(()), |index| {
+            check_index(index, self.program.modules.len(), "module")
+        })
+    }
+
+    fn procedure(&self, module: Option<usize>, index: usize) -> Checked {
+        let functions = match module {
+            Some(module) => {
+                check_index(module, self.program.modules.len(), "module")?;
+                &self.program.modules[module].functions
+            }
+            None => &self.program.functions,
+        };
+        check_index(index, functions.len(), "function")
+    }
+
+    /// Checks that an offset points to a variable on the stack
+    fn offset(&self, offset: usize) -> Checked {
+        if offset > 0 && offset <= self.depth {
+            Ok(())
+        } else {
+            Err(LoadError::InvalidReference(format!(
+                "stack offset {}",
+                offset
+            )))
+        }
+    }
+
+    /// Checks code that starts with `depth` variables on the stack
+    fn with_depth(&mut self, depth: usize, check: impl FnOnce(&mut Self) -> Checked) -> Checked {
+        let outer = std::mem::replace(&mut self.depth, depth);
+        let result = check(self);
+        self.depth = outer;
+        result
+    }
+
+    fn host_function(&self, function: HostFunction) -> Checked {
+        let package = self.program.host.get(function.package);
+        let count = package.map_or(0, |p| p.names().count());
+        check_index(function.package, self.program.host.len(), "host package")?;
+        check_index(function.index, count, "host function")
+    }
+
+    fn program(&mut self) -> Checked {
+        let program = self.program;
+        for import in &program.imports {
+            self.import(import)?;
+        }
+        for function in &program.functions {
+            self.function(function)?;
+        }
+
+        for (index, module) in program.modules.iter().enumerate() {
+            for import in &module.imports {
+                self.import(import)?;
+            }
+            for function in &module.functions {
+                self.function(function)?;
+            }
+            for &function in module.lookup.values() {
+                self.procedure(Some(index), function)?;
+            }
+        }
+
+        // Captured variables are pushed before the parameters
+        for lambda in &program.lambdas {
+            self.defined_in(lambda.module)?;
+            let depth = lambda.captured.len() + lambda.param_list.all_params().count();
+            self.with_depth(depth, |v| v.body(&lambda.body))?;
+        }
+        for getter in &program.getters {
+            self.defined_in(getter.module)?;
+            self.with_depth(getter.capture.len(), |v| v.body(&getter.block))?;
+        }
+        for setter in &program.setters {
+            self.defined_in(setter.module)?;
+            self.string(setter.param)?;
+            self.with_depth(setter.capture.len() + 1, |v| v.body(&setter.block))?;
+        }
+
+        self.block(&program.body, false)
+    }
+
+    fn import(&self, import: &Import) -> Checked {
+        self.module(&import.module)?;
+        match &import.kind {
+            ImportKind::All => Ok(()),
+            ImportKind::ModuleObject(name) => self.string(name.data),
+            ImportKind::List(names) => names.iter().try_for_each(|name| self.string(name.data)),
+        }
+    }
+
+    fn function(&mut self, function: &Procedure) -> Checked {
+        self.string(function.identifier.data)?;
+        for param in function.param_list.all_params() {
+            self.string(param.name)?;
+        }
+        let depth = function.param_list.all_params().count();
+        self.with_depth(depth, |v| v.block(&function.body, false))
+    }
+
+    fn body(&mut self, body: &LambdaBody) -> Checked {
+        match body {
+            LambdaBody::Block(block) => self.block(block, false),
+            LambdaBody::ImplicitReturn(expr) => self.expr(expr),
+        }
+    }
+
+    fn block(&mut self, block: &Block, in_loop: bool) -> Checked {
+        let depth = self.depth;
+        let result = block.iter().try_for_each(|node| self.node(node, in_loop));
+        self.depth = depth;
+        result
+    }
+
+    fn declaration(&mut self, decl: &Declaration) -> Checked {
+        for declarator in &decl.declarations {
+            self.string(declarator.identifier.data)?;
+            self.expr(&declarator.value)?;
+            self.depth += 1;
+        }
+        Ok(())
+    }
+
+    fn node(&mut self, node: &Node, in_loop: bool) -> Checked {
+        match node {
+            Node::Expression(expr) => self.expr(expr),
+            Node::Block(block) => self.block(block, in_loop),
+            Node::Declaration(decl) => self.declaration(decl),
+            Node::ControlFlow(flow) => match &flow.kind {
+                BreakType::Return(expr) | BreakType::Throw(expr) => self.expr(expr),
+                BreakType::Break | BreakType::Continue if in_loop => Ok(()),
+                BreakType::Break | BreakType::Continue => Err(LoadError::Malformed(
+                    "control break outside of a loop".to_string(),
+                )),
+            },
+            Node::LogicChain {
+                if_block,
+                elseifs,
+                else_block,
+            } => {
+                for pair in std::iter::once(if_block).chain(elseifs) {
+                    self.expr(&pair.condition)?;
+                    self.block(&pair.block, in_loop)?;
+                }
+                else_block
+                    .iter()
+                    .try_for_each(|block| self.block(block, in_loop))
+            }
+            Node::While(pair) => {
+                self.expr(&pair.condition)?;
+                self.block(&pair.block, true)
+            }
+            Node::Try { body, catch } => {
+                self.block(body, in_loop)?;
+                let depth = self.depth + catch.param.is_some() as usize;
+                if let Some(param) = &catch.param {
+                    self.string(param.name)?;
+                }
+                self.with_depth(depth, |v| v.block(&catch.block, in_loop))
+            }
+            Node::For {
+                declaration,
+                condition,
+                increment,
+                body,
+            } => {
+                let depth = self.depth;
+                declaration.iter().try_for_each(|d| self.declaration(d))?;
+                condition.iter().try_for_each(|e| self.expr(e))?;
+                increment.iter().try_for_each(|e| self.expr(e))?;
+                self.block(body, true)?;
+                self.depth = depth;
+                Ok(())
+            }
+        }
+    }
+
+    fn runtime_value(&self, value: &RuntimeValue) -> Checked {
+        match value {
+            RuntimeValue::StackOffset(offset) => self.offset(*offset),
+            RuntimeValue::Static(StaticValue::Module(module)) => self.module(module),
+            RuntimeValue::Static(StaticValue::Function(callable)) => match callable {
+                Callable::Native(_) => Ok(()),
+                Callable::Host(f) => self.host_function(*f),
+                Callable::Procedure { module, index } => self.procedure(*module, *index),
+                // Only exist while a program is running
+                Callable::Lambda { .. } | Callable::Method { .. } => Err(LoadError::Malformed(
+                    "static values can't be lambdas or methods".to_string(),
+                )),
+            },
+        }
+    }
+
+    fn hash(&mut self, hash: &Hash) -> Checked {
+        for (label, value) in hash {
+            self.string(*label)?;
+            match value {
+                ObjectValue::Expression(expr) => self.expr(expr)?,
+                ObjectValue::AutoProp(prop) => {
+                    // The variables each accessor captures when the hash is created
+                    let getters = self.program.getters.iter().map(|g| &g.capture);
+                    let setters = self.program.setters.iter().map(|s| &s.capture);
+                    let accessors = [
+                        (&prop.get, getters.collect::<Vec<_>>(), "getter"),
+                        (&prop.set, setters.collect::<Vec<_>>(), "setter"),
+                    ];
+                    for (accessor, captures, item) in accessors.iter() {
+                        match accessor {
+                            Some(AutoPropValue::String(_)) => return Err(LoadError::Unanalyzed),
+                            Some(AutoPropValue::Lambda(index)) => {
+                                check_index(*index, captures.len(), item)?;
+                                captures[*index].iter().try_for_each(|&o| self.offset(o))?;
+                            }
+                            Some(AutoPropValue::Value(value)) => self.runtime_value(value)?,
+                            None => {}
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn assignable(&mut self, assignable: &Assignable) -> Checked {
+        match assignable {
+            Assignable::Identifier(_) => Err(LoadError::Unanalyzed),
+            Assignable::Offset(offset) => self.offset(*offset),
+            Assignable::IndexAccess { item, index } => {
+                self.expr(item)?;
+                self.expr(index)
+            }
+            Assignable::PropertyAccess { item, property } => {
+                self.string(*property)?;
+                self.expr(item)
+            }
+        }
+    }
+
+    fn expr(&mut self, expression: &Expression) -> Checked {
+        use self::Expression::*;
+        match expression {
+            Bool(_) | Number(_) | Nil | This { .. } => Ok(()),
+            Identifier(_) => Err(LoadError::Unanalyzed),
+            String(index) => self.string(*index),
+            Lambda(index) => {
+                check_index(*index, self.program.lambdas.len(), "lambda")?;
+                let captured = &self.program.lambdas[*index].captured;
+                captured.iter().try_for_each(|&offset| self.offset(offset))
+            }
+            Value(value) => self.runtime_value(value),
+            Hash(hash) | MutableHash(hash) => self.hash(hash),
+            ArrayCreation(items) => items.iter().try_for_each(|item| self.expr(item)),
+            Unary { expr, .. } => self.expr(expr),
+            Binary { left, right, .. } => {
+                self.expr(left)?;
+                self.expr(right)
+            }
+            Assignment { left, right, .. } => {
+                self.assignable(left)?;
+                self.expr(right)
+            }
+            FunctionCall { function, args, .. } => {
+                self.expr(function)?;
+                args.iter().try_for_each(|arg| self.expr(arg))
+            }
+            IndexAccess { item, index } => {
+                self.expr(item)?;
+                self.expr(index)
+            }
+            PropertyAccess { item, property } => {
+                self.string(*property)?;
+                self.expr(item)
+            }
+        }
+    }
+}
@@ -0,0 +1,659 @@
+///
+/// Prints programs in the canonical Grib style.  Formatting works on the
+/// tree from `ast`, before analysis, along with the comments kept by
+/// `lex_with_comments`, since the tree itself doesn't hold comments
+///
+use ast::node::*;
+use lex::tokens::{Keyword, Token};
+use lex::{valid_ident_char, Comment};
+use location::{Located, Location};
+use operators::{op_precedence, Binary, Precedence, Unary};
+use std::iter::Peekable;
+use std::vec;
+
+const INDENT: &str = "    ";
+
+/// Hashes and declarations are split over several lines past this width
+const WIDTH: usize = 80;
+
+/// How tightly an expression binds, used to decide where parentheses go
+const ASSIGNMENT: usize = 0;
+const UNARY: usize = Precedence::ITEMS.len() + 1;
+const POSTFIX: usize = UNARY + 1;
+
+/// Formats a parsed program.  `tokens` and `comments` are the program's
+/// source as lexed by `lex_with_comments`
+pub fn format(
+    program: &Program,
+    tokens: &[Located<Token>],
+    comments: Vec<Comment>,
+    source: &str,
+) -> String {
+    let mut formatter = Formatter {
+        program,
+        lines: source.lines().collect(),
+        comments: comments.into_iter().peekable(),
+    };
+
+    // The tree doesn't keep where imports were written, the module as it was
+    // named, or which were wildcards, so those come from the tokens instead
+    let sources = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| t.data == Token::Keyword(Keyword::Import))
+        .flat_map(|(i, t)| {
+            let all = tokens.get(i + 1).map(|t| &t.data) == Some(&Token::BinaryOp(Binary::Mult));
+            let module = tokens[i..].iter().find_map(|t| match &t.data {
+                Token::String(module) => Some(module.as_str()),
+                _ => None,
+            })?;
+            Some((&t.start, module, all))
+        });
+
+    let mut items = sources
+        .zip(&program.imports)
+        .map(|((start, module, all), import)| (start, Item::Import(import, module, all)))
+        .collect::<Vec<_>>();
+
+    let mut rest = program
+        .functions
+        .iter()
+        .map(|proc| (&proc.identifier.start, Item::Proc(proc)))
+        .chain(
+            program
+                .body
+                .locations
+                .iter()
+                .zip(&program.body.stmts)
+                .map(|(start, node)| (start, Item::Node(node))),
+        )
+        .collect::<Vec<_>>();
+    rest.sort_by_key(|(start, _)| *start);
+    items.extend(rest);
+
+    formatter.items(items, None, 0)
+}
+
+/// Something that's written on its own line at the top level or in a block
+enum Item<'a> {
+    /// An import, the module as it was written, and whether it imports everything
+    Import(&'a Import, &'a str, bool),
+    Proc(&'a Procedure),
+    Node(&'a Node),
+}
+
+struct Formatter<'a> {
+    program: &'a Program,
+    /// The source lines, for keeping blank lines between statements
+    lines: Vec<&'a str>,
+    /// Comments that haven't been written yet
+    comments: Peekable<vec::IntoIter<Comment>>,
+}
+
+fn pad(indent: usize) -> String {
+    INDENT.repeat(indent)
+}
+
+impl<'a> Formatter<'a> {
+    fn string(&self, index: usize) -> &'a str {
+        &self.program.strings[index]
+    }
+
+    /// Whether the line before `row` was left blank
+    fn blank_before(&self, row: usize) -> bool {
+        row.checked_sub(2)
+            .and_then(|i| self.lines.get(i))
+            .is_some_and(|line| line.trim().is_empty())
+    }
+
+    /// Writes the items one per line along with the comments before them.
+    /// Comments up to `end` are written after the last item, or every
+    /// remaining comment if there is no end
+    fn items(
+        &mut self,
+        items: Vec<(&Location, Item)>,
+        end: Option<&Location>,
+        indent: usize,
+    ) -> String {
+        let mut lines = Lines {
+            out: String::new(),
+            last_row: None,
+            gap: false,
+        };
+        let mut last_import = false;
+
+        for (start, item) in items {
+            let import = matches!(item, Item::Import(..));
+            lines.gap = last_import && !import;
+            last_import = import;
+
+            self.comments_before(Some(start), &mut lines, indent);
+            let text = match &item {
+                Item::Import(import, module, all) => self.import(import, module, *all),
+                Item::Proc(proc) => self.procedure(proc, indent),
+                Item::Node(node) => self.node(node, indent),
+            };
+            lines.push(&pad(indent), &text, self.blank_before(start.get_row()));
+
+            let single = Some(start.get_row()).filter(|_| !text.contains('\n'));
+            lines.last_row = end_row(&item).or(single);
+        }
+
+        self.comments_before(end, &mut lines, indent);
+        lines.out
+    }
+
+    fn comments_before(&mut self, loc: Option<&Location>, lines: &mut Lines, indent: usize) {
+        while let Some(comment) = self
+            .comments
+            .next_if(|c| loc.is_none_or(|loc| c.start < *loc))
+        {
+            let text = comment.data.trim_end();
+            let row = comment.start.get_row();
+
+            if lines.last_row == Some(row) && !text.contains('\n') {
+                lines.out.pop();
+                lines.out.push(' ');
+                lines.out.push_str(text);
+                lines.out.push('\n');
+            } else {
+                lines.push(&pad(indent), text, self.blank_before(row));
+            }
+            lines.last_row = Some(row).filter(|_| !text.contains('\n'));
+        }
+    }
+
+    fn block(&mut self, block: &Block, indent: usize) -> String {
+        let items = block
+            .locations
+            .iter()
+            .zip(&block.stmts)
+            .map(|(start, node)| (start, Item::Node(node)))
+            .collect();
+        let body = self.items(items, Some(&block.end), indent + 1);
+
+        if body.is_empty() {
+            "{}".to_string()
+        } else {
+            format!("{{\n{}{}}}", body, pad(indent))
+        }
+    }
+
+    fn import(&self, import: &Import, module: &str, all: bool) -> String {
+        let kind = match &import.kind {
+            ImportKind::All => "*".to_string(),
+            // Wildcard imports of packages are rewritten into the names the program uses
+            _ if all => "*".to_string(),
+            ImportKind::ModuleObject(name) => self.string(name.data).to_string(),
+            ImportKind::List(names) => {
+                let names = names
+                    .iter()
+                    .map(|n| self.string(n.data))
+                    .collect::<Vec<_>>();
+                format!("|{}|", names.join(" "))
+            }
+        };
+        format!("import {} from {};", kind, quote(module))
+    }
+
+    fn procedure(&mut self, proc: &Procedure, indent: usize) -> String {
+        format!(
+            "{}proc {}{} {}",
+            if proc.public { "public " } else { "" },
+            self.string(proc.identifier.data),
+            self.params(&proc.param_list),
+            self.block(&proc.body, indent)
+        )
+    }
+
+    /// Parameters with a leading space, or nothing if there are none
+    fn params(&self, params: &Parameters) -> String {
+        let names = params
+            .params
+            .iter()
+            .map(|p| self.string(p.name).to_string())
+            .chain(
+                params
+                    .vardic
+                    .iter()
+                    .map(|p| format!("...{}", self.string(p.name))),
+            )
+            .collect::<Vec<_>>();
+
+        if names.is_empty() {
+            String::new()
+        } else {
+            format!(" |{}|", names.join(" "))
+        }
+    }
+
+    fn node(&mut self, node: &Node, indent: usize) -> String {
+        match node {
+            Node::Expression(expr) => format!("{};", self.expr(expr, indent)),
+            Node::Declaration(decl) => {
+                let keyword = if decl.mutable { "decl" } else { "im" };
+                if decl.declarations.len() == 1 {
+                    return format!("{} {};", keyword, self.declarators(decl, indent)[0]);
+                }
+
+                // Declarators are indented past the keyword if they need their own lines
+                let declarators = self.declarators(decl, indent + 1);
+                let inline = declarators.join(", ");
+
+                if inline.contains('\n')
+                    || pad(indent).len() + keyword.len() + inline.len() + 2 > WIDTH
+                {
+                    let sep = format!(",\n{}", pad(indent + 1));
+                    format!("{} {};", keyword, declarators.join(&sep))
+                } else {
+                    format!("{} {};", keyword, inline)
+                }
+            }
+            Node::ControlFlow(flow) => match &flow.kind {
+                BreakType::Break => "break;".to_string(),
+                BreakType::Continue => "continue;".to_string(),
+                BreakType::Return(Expression::Nil) => "return;".to_string(),
+                BreakType::Return(expr) => format!("return {};", self.expr(expr, indent)),
+                BreakType::Throw(expr) => format!("throw {};", self.expr(expr, indent)),
+            },
+            Node::LogicChain {
+                if_block,
+                elseifs,
+                else_block,
+            } => {
+                let mut out = format!("if {}", self.condition_body(if_block, indent));
+                for pair in elseifs {
+                    out.push_str(" else if ");
+                    out.push_str(&self.condition_body(pair, indent));
+                }
+                if let Some(block) = else_block {
+                    out.push_str(" else ");
+                    out.push_str(&self.block(block, indent));
+                }
+                out
+            }
+            Node::While(pair) => format!("while {}", self.condition_body(pair, indent)),
+            Node::For {
+                declaration,
+                condition,
+                increment,
+                body,
+            } => {
+                let mut out = "for".to_string();
+                if let Some(decl) = declaration {
+                    let keyword = if decl.mutable { " decl " } else { " im " };
+                    out.push_str(keyword);
+                    out.push_str(&self.declarators(decl, indent + 1).join(", "));
+                }
+                out.push(';');
+                if let Some(condition) = condition {
+                    out.push(' ');
+                    out.push_str(&self.expr(condition, indent));
+                }
+                out.push(';');
+                if let Some(increment) = increment {
+                    out.push(' ');
+                    out.push_str(&group_braces(self.expr(increment, indent)));
+                }
+                out.push(' ');
+                out.push_str(&self.block(body, indent));
+                out
+            }
+            Node::Try { body, catch } => {
+                let body = self.block(body, indent);
+                let param = match &catch.param {
+                    Some(param) => format!("|{}| ", self.string(param.name)),
+                    None => String::new(),
+                };
+                format!(
+                    "try {} catch {}{}",
+                    body,
+                    param,
+                    self.block(&catch.block, indent)
+                )
+            }
+            // Standalone blocks are opened with a bracket by the parser
+            Node::Block(block) => {
+                let block = self.block(block, indent);
+                format!("[{}", &block[1..])
+            }
+        }
+    }
+
+    fn condition_body(&mut self, pair: &ConditionBodyPair, indent: usize) -> String {
+        let condition = group_braces(self.expr(&pair.condition, indent));
+        format!("{} {}", condition, self.block(&pair.block, indent))
+    }
+
+    fn declarators(&mut self, decl: &Declaration, indent: usize) -> Vec<String> {
+        decl.declarations
+            .iter()
+            .map(|d| match &d.value {
+                Expression::Nil => self.string(d.identifier.data).to_string(),
+                value => format!(
+                    "{} = {}",
+                    self.string(d.identifier.data),
+                    self.expr(value, indent)
+                ),
+            })
+            .collect()
+    }
+
+    /// Formats an expression, wrapping it in parentheses if it binds looser than `level`
+    fn operand(&mut self, expr: &Expression, level: usize, indent: usize) -> String {
+        let text = self.expr(expr, indent);
+        if binding(expr) < level {
+            format!("({})", text)
+        } else {
+            text
+        }
+    }
+
+    fn list(&mut self, exprs: &[Expression], indent: usize) -> String {
+        exprs
+            .iter()
+            .map(|e| self.expr(e, indent))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn expr(&mut self, expr: &Expression, indent: usize) -> String {
+        match expr {
+            Expression::Binary { op, left, right } => {
+                let level = binary_level(op);
+                format!(
+                    "{} {} {}",
+                    self.operand(left, level, indent),
+                    op.str(),
+                    // Operators group to the left, so an equal one on the right needs parentheses
+                    self.operand(right, level + 1, indent)
+                )
+            }
+            Expression::Unary { op, expr } => {
+                let op = match op {
+                    Unary::Negation => "-",
+                    Unary::LogicalNegation => "!",
+                };
+                format!("{}{}", op, self.operand(expr, UNARY, indent))
+            }
+            Expression::Assignment { op, left, right } => {
+                let left = match left {
+                    Assignable::Identifier(name) => self.string(name.data).to_string(),
+                    Assignable::IndexAccess { item, index } => {
+                        format!(
+                            "{}[{}]",
+                            self.operand(item, POSTFIX, indent),
+                            self.expr(index, indent)
+                        )
+                    }
+                    Assignable::PropertyAccess { item, property } => {
+                        format!(
+                            "{}.{}",
+                            self.operand(item, POSTFIX, indent),
+                            self.string(*property)
+                        )
+                    }
+                    Assignable::Offset(_) => panic!("Stack offsets only appear after analysis"),
+                };
+                format!(
+                    "{} {} {}",
+                    left,
+                    op.str(),
+                    self.operand(right, ASSIGNMENT + 1, indent)
+                )
+            }
+            Expression::Bool(b) => b.to_string(),
+            Expression::Number(n) => number(*n),
+            Expression::String(s) => quote(self.string(*s)),
+            Expression::Identifier(name) => self.string(name.data).to_string(),
+            Expression::Nil => "nil".to_string(),
+            Expression::This { .. } => "this".to_string(),
+            Expression::ArrayCreation(items) => format!("[{}]", self.list(items, indent)),
+            Expression::Hash(hash) => self.hash("#", hash, indent),
+            Expression::MutableHash(hash) => self.hash("$", hash, indent),
+            Expression::FunctionCall { function, args, .. } => {
+                let function = self.operand(function, POSTFIX, indent);
+                format!("{}({})", function, self.list(args, indent))
+            }
+            Expression::IndexAccess { item, index } => {
+                format!(
+                    "{}[{}]",
+                    self.operand(item, POSTFIX, indent),
+                    self.expr(index, indent)
+                )
+            }
+            Expression::PropertyAccess { item, property } => {
+                format!(
+                    "{}.{}",
+                    self.operand(item, POSTFIX, indent),
+                    self.string(*property)
+                )
+            }
+            Expression::Lambda(index) => {
+                let lambda = &self.program.lambdas[*index];
+                format!(
+                    "lam{} {}",
+                    self.params(&lambda.param_list),
+                    self.lambda_body(&lambda.body, indent)
+                )
+            }
+            Expression::Value(_) => panic!("Values only appear in expressions after analysis"),
+        }
+    }
+
+    fn lambda_body(&mut self, body: &LambdaBody, indent: usize) -> String {
+        match body {
+            LambdaBody::ImplicitReturn(expr) => format!("{{ {} }}", self.expr(expr, indent)),
+            LambdaBody::Block(block) => self.block(block, indent),
+        }
+    }
+
+    /// Formats a hash on one line if it's short enough, otherwise one entry per line
+    fn hash(&mut self, sigil: &str, hash: &Hash, indent: usize) -> String {
+        // Hashes don't keep the order their keys were written in, so they're sorted
+        let mut keys = hash.keys().collect::<Vec<_>>();
+        keys.sort_by_key(|&&k| self.string(k));
+
+        let entries = keys
+            .into_iter()
+            .map(|key| {
+                let name = self.key(*key);
+                match &hash[key] {
+                    ObjectValue::Expression(expr) => {
+                        format!("{} -> {}", name, self.expr(expr, indent + 1))
+                    }
+                    ObjectValue::AutoProp(prop) => {
+                        format!("{} {{ {} }}", name, self.auto_prop(prop, indent + 1))
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if entries.is_empty() {
+            return format!("{}{{}}", sigil);
+        }
+
+        let inline = format!("{}{{ {} }}", sigil, entries.join(", "));
+        if !inline.contains('\n') && pad(indent).len() + inline.len() <= WIDTH {
+            inline
+        } else {
+            let mut out = format!("{}{{\n", sigil);
+            for entry in entries {
+                out.push_str(&pad(indent + 1));
+                out.push_str(&entry);
+                out.push_str(",\n");
+            }
+            out.push_str(&pad(indent));
+            out.push('}');
+            out
+        }
+    }
+
+    fn key(&self, key: usize) -> String {
+        let key = self.string(key);
+        let bare = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(valid_ident_char)
+            && Keyword::from_str(key).is_none()
+            && !matches!(key, "true" | "false" | "NaN" | "Infinity");
+
+        if bare {
+            key.to_string()
+        } else {
+            quote(key)
+        }
+    }
+
+    fn auto_prop(&mut self, prop: &AutoProp, indent: usize) -> String {
+        let mut parts = vec![];
+
+        match &prop.get {
+            Some(AutoPropValue::String(name)) => {
+                parts.push(format!("get {}", self.string(name.data)))
+            }
+            Some(AutoPropValue::Lambda(index)) => {
+                let getter = &self.program.getters[*index];
+                parts.push(format!("get {}", self.lambda_body(&getter.block, indent)));
+            }
+            Some(AutoPropValue::Value(_)) => {
+                panic!("Values only appear in auto-properties after analysis")
+            }
+            None => {}
+        }
+        match &prop.set {
+            Some(AutoPropValue::String(name)) => {
+                parts.push(format!("set {}", self.string(name.data)))
+            }
+            Some(AutoPropValue::Lambda(index)) => {
+                let setter = &self.program.setters[*index];
+                parts.push(format!(
+                    "set |{}| {}",
+                    self.string(setter.param),
+                    self.lambda_body(&setter.block, indent)
+                ));
+            }
+            Some(AutoPropValue::Value(_)) => {
+                panic!("Values only appear in auto-properties after analysis")
+            }
+            None => {}
+        }
+
+        parts.join(", ")
+    }
+}
+
+/// The lines written for a block or the top level
+struct Lines {
+    out: String,
+    /// The row the last line ended on in the source, so that
+    /// comments after it on the same row can stay there
+    last_row: Option<usize>,
+    /// Whether a blank line sets the next line apart, as after imports
+    gap: bool,
+}
+
+impl Lines {
+    /// Writes a line, after a blank one if it was separated from the last
+    fn push(&mut self, pad: &str, text: &str, blank_before: bool) {
+        if !self.out.is_empty() && (self.gap || blank_before) {
+            self.out.push('\n');
+        }
+        self.gap = false;
+
+        self.out.push_str(pad);
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+}
+
+/// The row an item that ends with a block ends on in the source
+fn end_row(item: &Item) -> Option<usize> {
+    let block = match item {
+        Item::Import(..) => return None,
+        Item::Proc(proc) => &proc.body,
+        Item::Node(node) => match node {
+            Node::LogicChain {
+                if_block,
+                elseifs,
+                else_block,
+            } => else_block
+                .as_ref()
+                .or_else(|| elseifs.last().map(|pair| &pair.block))
+                .unwrap_or(&if_block.block),
+            Node::While(pair) => &pair.block,
+            Node::For { body, .. } => body,
+            Node::Try { catch, .. } => &catch.block,
+            Node::Block(block) => block,
+            _ => return None,
+        },
+    };
+    Some(block.end.get_row())
+}
+
+/// How tightly an expression binds to the operators around it
+fn binding(expr: &Expression) -> usize {
+    match expr {
+        Expression::Assignment { .. } => ASSIGNMENT,
+        Expression::Binary { op, .. } => binary_level(op),
+        Expression::Unary { .. } => UNARY,
+        // A period after a number would be lexed as a decimal point
+        Expression::Number(_) => UNARY,
+        _ => POSTFIX,
+    }
+}
+
+fn binary_level(op: &Binary) -> usize {
+    let precedence = op_precedence(op);
+    1 + Precedence::ITEMS
+        .iter()
+        .position(|p| *p == precedence)
+        .expect("Every precedence is listed")
+}
+
+/// Conditions end at the first brace outside of any group, so
+/// conditions containing braces of their own are parenthesized
+fn group_braces(condition: String) -> String {
+    let mut depth = 0usize;
+    let mut string = false;
+    let mut escaped = false;
+
+    for c in condition.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if string => escaped = true,
+            '"' => string = !string,
+            _ if string => {}
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            '{' if depth == 0 => return format!("({})", condition),
+            _ => {}
+        }
+    }
+    condition
+}
+
+fn number(n: f64) -> String {
+    if n.is_nan() {
+        "NaN".to_string()
+    } else if n.is_infinite() {
+        "Infinity".to_string()
+    } else {
+        n.to_string()
+    }
+}
+
+/// Writes a string literal, escaping what the lexer would otherwise misread
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -1,7 +1,10 @@
+pub mod format;
 pub mod node;
 
 mod analysis;
+mod compiled;
 mod parsing;
 
 pub use self::analysis::*;
+pub use self::compiled::*;
 pub use self::parsing::*;
@@ -1,4 +1,4 @@
-use super::Expression;
+use super::{Block, Expression, Param};
 use location::Location;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
@@ -6,6 +6,8 @@ pub enum BreakType {
     Break,
     Continue,
     Return(Expression),
+    /// Unwinds to the nearest enclosing catch block, even across function calls
+    Throw(Expression),
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
@@ -43,4 +45,21 @@ impl FlowBreak {
             end,
         }
     }
+
+    pub fn new_throw(expr: Expression, start: Location, end: Location) -> Self {
+        Self {
+            kind: BreakType::Throw(expr),
+            allocations: 0,
+            start,
+            end,
+        }
+    }
+}
+
+/// The handler of a try block.  The thrown value
+/// is bound to the parameter if there is one
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Catch {
+    pub param: Option<Param>,
+    pub block: Block,
 }
@@ -49,6 +49,7 @@ pub enum Expression {
     },
 
     Bool(bool),
+    #[serde(with = "number")]
     Number(f64),
     String(usize),
     Identifier(Located<usize>),
@@ -60,6 +61,10 @@ pub enum Expression {
     FunctionCall {
         function: Box<Expression>,
         args: Vec<Expression>,
+        #[serde(default)]
+        start: Location,
+        #[serde(default)]
+        end: Location,
     },
     Lambda(usize),
 
@@ -89,3 +94,37 @@ impl Expression {
         }
     }
 }
+
+/// JSON has no representation for infinite numbers or NaN,
+/// so they're written as the names Grib uses for them
+mod number {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(n: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        match *n {
+            n if n.is_finite() => serializer.serialize_f64(n),
+            n if n.is_nan() => serializer.serialize_str("NaN"),
+            n if n > 0.0 => serializer.serialize_str("Infinity"),
+            _ => serializer.serialize_str("-Infinity"),
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Finite(f64),
+        Name(String),
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        match Repr::deserialize(deserializer)? {
+            Repr::Finite(n) => Ok(n),
+            Repr::Name(name) => match name.as_str() {
+                "NaN" => Ok(f64::NAN),
+                "Infinity" => Ok(f64::INFINITY),
+                "-Infinity" => Ok(f64::NEG_INFINITY),
+                _ => Err(de::Error::custom(format!("invalid number `{}`", name))),
+            },
+        }
+    }
+}
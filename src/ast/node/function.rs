@@ -1,5 +1,5 @@
 use super::{Block, Expression};
-use location::Located;
+use location::{Located, Location};
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct Param {
@@ -11,6 +11,9 @@ pub struct Param {
 pub struct Parameters {
     pub params: Vec<Param>,
     pub vardic: Option<Param>,
+    /// Where each parameter's name starts, in the order of `all_params`
+    #[serde(default)]
+    pub locations: Vec<Location>,
 }
 
 impl Parameters {
@@ -18,6 +21,7 @@ impl Parameters {
         Parameters {
             params: Vec::new(),
             vardic: None,
+            locations: Vec::new(),
         }
     }
 
@@ -73,6 +77,12 @@ pub struct Lambda {
     pub param_list: Parameters,
     pub body: LambdaBody,
     pub captured: Vec<usize>,
+    /// The names of the captured variables
+    #[serde(default)]
+    pub captured_names: Vec<usize>,
+    /// Index of the custom module the lambda was defined in
+    #[serde(default)]
+    pub module: Option<usize>,
 }
 
 impl Lambda {
@@ -81,6 +91,8 @@ impl Lambda {
             body,
             param_list,
             captured: Vec::new(),
+            captured_names: Vec::new(),
+            module: None,
         }
     }
 }
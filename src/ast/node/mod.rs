@@ -5,7 +5,8 @@ pub mod module;
 pub mod object;
 pub mod statement;
 
-use std::path::Path;
+use location::Location;
+use std::path::{Path, PathBuf};
 
 pub use self::controlflow::*;
 pub use self::expression::*;
@@ -14,6 +15,7 @@ pub use self::module::*;
 pub use self::object::*;
 pub use self::statement::*;
 
+pub use runtime::host::{HostFunction, HostRegistry};
 pub use runtime::native_fn::{NativeFunction, NativePackage};
 
 pub type ModuleStore = Vec<CustomModule>;
@@ -22,6 +24,13 @@ pub type ModuleStore = Vec<CustomModule>;
 pub struct Block {
     pub stmts: Vec<Node>,
     pub allocations: usize,
+    /// Where each statement starts.  Used by the debugger
+    #[serde(default)]
+    pub locations: Vec<Location>,
+    /// Where the closing brace is.  Used by the formatter to keep
+    /// comments at the end of the block inside of it
+    #[serde(default)]
+    pub end: Location,
 }
 
 impl Block {
@@ -37,8 +46,14 @@ impl Block {
         self.stmts.iter_mut()
     }
 
-    pub fn push(&mut self, node: Node) {
+    pub fn push(&mut self, node: Node, start: Location) {
         self.stmts.push(node);
+        self.locations.push(start);
+    }
+
+    /// The location of a statement, if it was recorded
+    pub fn location(&self, index: usize) -> Option<&Location> {
+        self.locations.get(index)
     }
 }
 
@@ -52,11 +67,21 @@ pub struct Program {
     pub setters: Vec<SetProp>,
     pub strings: Vec<String>,
     pub body: Block,
+    /// Path of the file the program was loaded from
+    #[serde(default)]
+    pub path: PathBuf,
+    /// Packages registered by the program embedding the interpreter
+    #[serde(skip)]
+    pub host: HostRegistry,
+    /// The directory custom modules have to be inside of, if imports are restricted
+    #[serde(skip)]
+    pub import_root: Option<PathBuf>,
 }
 
 impl Program {
     pub fn new() -> Self {
         Self {
+            path: PathBuf::new(),
             modules: Vec::new(),
             body: Block::default(),
             functions: Vec::new(),
@@ -65,6 +90,8 @@ impl Program {
             getters: Vec::new(),
             setters: Vec::new(),
             strings: Vec::new(),
+            host: HostRegistry::new(),
+            import_root: None,
         }
     }
 
@@ -103,4 +130,8 @@ pub enum Node {
     },
     Declaration(Declaration),
     ControlFlow(FlowBreak),
+    Try {
+        body: Block,
+        catch: Catch,
+    },
 }
@@ -8,11 +8,13 @@ use std::path::PathBuf;
 pub enum Module {
     Custom(usize),
     Native(NativePackage),
+    /// Index of a package registered by the host
+    Host(usize),
 }
 
 impl Module {
     pub fn is_native(&self) -> bool {
-        if let Self::Native { .. } = self {
+        if let Self::Native(_) | Self::Host(_) = self {
             true
         } else {
             false
@@ -22,6 +24,7 @@ impl Module {
     pub fn get_callable(&self, string: &str, program: &Program) -> Option<Callable> {
         match self {
             Self::Native(native) => native.fn_from_str(&string).map(Callable::Native),
+            Self::Host(package) => program.host.function(*package, string).map(Callable::Host),
             Self::Custom(mod_ind) => {
                 program.modules[*mod_ind]
                     .lookup
@@ -18,6 +18,13 @@ pub struct SetProp {
     /// stored after first pass.  Stack offsets
     /// are stored after second pass
     pub capture: Vec<usize>,
+    /// The names of the captured variables
+    #[serde(default)]
+    pub capture_names: Vec<usize>,
+
+    /// Index of the custom module the setter was defined in
+    #[serde(default)]
+    pub module: Option<usize>,
 }
 
 impl SetProp {
@@ -27,6 +34,8 @@ impl SetProp {
             param_captured: false,
             block,
             capture: Vec::new(),
+            capture_names: Vec::new(),
+            module: None,
         }
     }
 }
@@ -41,6 +50,13 @@ pub struct GetProp {
     /// Once again, identifier names in first pass
     /// Stack offsets after second pass
     pub capture: Vec<usize>,
+    /// The names of the captured variables
+    #[serde(default)]
+    pub capture_names: Vec<usize>,
+
+    /// Index of the custom module the getter was defined in
+    #[serde(default)]
+    pub module: Option<usize>,
 }
 
 impl GetProp {
@@ -48,6 +64,8 @@ impl GetProp {
         Self {
             block,
             capture: Vec::new(),
+            capture_names: Vec::new(),
+            module: None,
         }
     }
 }
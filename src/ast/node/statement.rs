@@ -1,5 +1,5 @@
 use super::{Block, Expression, Module};
-use location::Located;
+use location::{Located, Location};
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct ConditionBodyPair {
@@ -31,4 +31,9 @@ pub enum ImportKind {
 pub struct Import {
     pub module: Module,
     pub kind: ImportKind,
+    /// Where the import statement starts and ends
+    #[serde(default)]
+    pub start: Location,
+    #[serde(default)]
+    pub end: Location,
 }
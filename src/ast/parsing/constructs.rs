@@ -1,10 +1,10 @@
 use super::parse_expr;
 use crate::next_guard;
 use ast::node::*;
-use ast::parsing::{ast_level, util::*, Store};
+use ast::parsing::{parse_block, util::*, Store};
 use ast::{ModuleError, ModuleErrorBody, ParseError, ParseResult};
 use lex::{lex, tokens::*};
-use location::Located;
+use location::{Located, Location};
 use operators::{Assignment, Binary};
 use std::{
     collections::HashMap,
@@ -20,7 +20,39 @@ pub fn parse_if_block<T: Iterator<Item = Located<Token>>>(
     Ok(ConditionBodyPair {
         condition: zero_level(tokens, |t| *t == Token::OpenGroup(Grouper::Brace))
             .and_then(|(v, _)| parse_expr(v, store))?,
-        block: take_until(tokens, Grouper::Brace).and_then(|(v, _)| ast_level(v, store))?,
+        block: parse_block(tokens, store)?,
+    })
+}
+
+/// Parses a try block and its handler, `try { ... } catch |e| { ... }`.
+/// The catch parameter can be left out
+pub fn parse_try<T: Iterator<Item = Located<Token>>>(
+    tokens: &mut T,
+    store: &mut Store,
+) -> ParseResult<Node> {
+    next_guard!({ tokens.next() } { Token::OpenGroup(Grouper::Brace) => {} });
+    let body = parse_block(tokens, store)?;
+
+    next_guard!({ tokens.next() } { Token::Keyword(Keyword::Catch) => {} });
+    let param = next_guard!({ tokens.next() } {
+        Token::OpenGroup(Grouper::Brace) => None,
+        Token::Pipe => {
+            let name = next_guard!({ tokens.next() } {
+                Token::Identifier(name) => store.ins_str(name)
+            });
+            next_guard!({ tokens.next() } { Token::Pipe => {} });
+            next_guard!({ tokens.next() } { Token::OpenGroup(Grouper::Brace) => {} });
+            Some(Param {
+                name,
+                captured: false,
+            })
+        }
+    });
+    let block = parse_block(tokens, store)?;
+
+    Ok(Node::Try {
+        body,
+        catch: Catch { param, block },
     })
 }
 
@@ -91,6 +123,8 @@ pub fn parse_params<T: Iterator<Item = Located<Token>>>(
                         return Err(ParseError::DuplicateParam(Located {
                             data: s, start, end
                         }));
+                    } else {
+                        params.locations.push(start);
                     },
                     Token::Spread => next_guard!({ list.next() } (start, end) {
                         Token::Identifier(s) => if params.vardic.is_some() {
@@ -106,6 +140,7 @@ pub fn parse_params<T: Iterator<Item = Located<Token>>>(
                                 name: store.ins_str(s),
                                 captured: false,
                             }.into();
+                            params.locations.push(start);
                         }
                     })
                 });
@@ -133,7 +168,7 @@ pub fn parse_proc<T: Iterator<Item = Located<Token>>>(
             end,
         },
         param_list,
-        body: take_until(tokens, Grouper::Brace).and_then(|(v, _)| ast_level(v, store))?,
+        body: parse_block(tokens, store)?,
         public,
     })
 }
@@ -158,30 +193,45 @@ pub fn parse_module(path: &Located<PathBuf>, store: &mut Store) -> ParseResult<C
     let mut functions = vec![];
     let mut imports = vec![];
 
+    parse_module_body(&mut tokens, &dir, &mut functions, &mut imports, store)
+        .map_err(|e| module_err(ModuleErrorBody::ParseError(e.into()), path.clone()))?;
+
+    let fnc_len = functions.len();
+    Ok(CustomModule {
+        functions,
+        imports,
+        path: path.data.clone(),
+        lookup: HashMap::with_capacity(fnc_len),
+    })
+}
+
+fn parse_module_body<T: Iterator<Item = Located<Token>>>(
+    tokens: &mut T,
+    dir: &Path,
+    functions: &mut Vec<Procedure>,
+    imports: &mut Vec<Import>,
+    store: &mut Store,
+) -> ParseResult<()> {
     while let Some(token) = tokens.next() {
         match token.data {
             Token::Keyword(Keyword::Public) => next_guard!({ tokens.next() } {
-                Token::Keyword(Keyword::Proc) => functions.push(parse_proc(&mut tokens, true, store)?)
+                Token::Keyword(Keyword::Proc) => functions.push(parse_proc(tokens, true, store)?)
             }),
-            Token::Keyword(Keyword::Proc) => functions.push(parse_proc(&mut tokens, true, store)?),
+            Token::Keyword(Keyword::Proc) => functions.push(parse_proc(tokens, true, store)?),
             Token::Keyword(Keyword::Import) => {
-                imports.push(parse_import(&mut tokens, dir.as_path(), store)?)
+                imports.push(parse_import(tokens, token.start, dir, store)?)
             }
             _ => return Err(ParseError::UnexpectedToken(token)),
         };
     }
 
-    let fnc_len = functions.len();
-    Ok(CustomModule {
-        functions,
-        imports,
-        path: dir,
-        lookup: HashMap::with_capacity(fnc_len),
-    })
+    Ok(())
 }
 
+/// Parses an import after its `import` keyword, which starts at `start`
 pub fn parse_import<T: Iterator<Item = Located<Token>>>(
     tokens: &mut T,
+    start: Location,
     path: &Path,
     store: &mut Store,
 ) -> ParseResult<Import> {
@@ -214,11 +264,14 @@ pub fn parse_import<T: Iterator<Item = Located<Token>>>(
     });
 
     let module = next_guard!({ tokens.next() } (start, end) {
-        Token::String(s) => match NativePackage::from_str(&s) {
-            Some(package) => {
+        Token::String(s) => match (NativePackage::from_str(&s), store.host_package(&s)) {
+            (Some(package), _) => {
                 Module::Native(package)
             },
-            None => {
+            (None, Some(package)) => {
+                Module::Host(package)
+            },
+            (None, None) => {
                 let new_buf = path.join(&s);
                 let new_path = new_buf.as_path()
                     .canonicalize()
@@ -230,6 +283,14 @@ pub fn parse_import<T: Iterator<Item = Located<Token>>>(
                             start: start.clone(),
                         }))?;
 
+                if !store.can_import(&new_path) {
+                    return Err(module_err(ModuleErrorBody::OutsideImportRoot, Located {
+                        data: new_path,
+                        end,
+                        start,
+                    }));
+                }
+
                 Module::Custom(match store.get_mod(&new_path) {
                     Some(ind) => *ind,
                     None => {
@@ -245,9 +306,14 @@ pub fn parse_import<T: Iterator<Item = Located<Token>>>(
         }
     });
 
-    next_guard!({ tokens.next() } {
-        Token::Semicolon => {}
+    let end = next_guard!({ tokens.next() } (_start, end) {
+        Token::Semicolon => end
     });
 
-    Ok(Import { module, kind })
+    Ok(Import {
+        module,
+        kind,
+        start,
+        end,
+    })
 }
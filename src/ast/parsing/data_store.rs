@@ -20,6 +20,8 @@ pub struct Store {
     lambdas: Vec<Lambda>,
     getters: Vec<GetProp>,
     setters: Vec<SetProp>,
+    host: HostRegistry,
+    import_root: Option<PathBuf>,
 }
 
 impl Store {
@@ -32,9 +34,44 @@ impl Store {
             lambdas: Vec::new(),
             getters: Vec::new(),
             setters: Vec::new(),
+            host: HostRegistry::new(),
+            import_root: None,
         }
     }
 
+    /// Creates an empty store that resolves imports of the host's packages
+    pub fn with_host(host: HostRegistry) -> Self {
+        Self {
+            host,
+            ..Self::new()
+        }
+    }
+
+    /// Creates a store containing a parsed program's strings, modules,
+    /// and functions so that more code can be parsed into the program
+    pub fn resume(program: &mut Program) -> Self {
+        let mut store = Self::new();
+
+        for (ind, string) in program.strings.drain(..).enumerate() {
+            store.str_map.insert(string, ind);
+        }
+
+        for (index, module) in program.modules.drain(..).enumerate() {
+            let path = module.path.clone();
+            store.mod_map.insert(path, ModuleData { module, index });
+        }
+
+        store.imports = std::mem::take(&mut program.imports);
+        store.functions = std::mem::take(&mut program.functions);
+        store.lambdas = std::mem::take(&mut program.lambdas);
+        store.getters = std::mem::take(&mut program.getters);
+        store.setters = std::mem::take(&mut program.setters);
+        store.host = std::mem::take(&mut program.host);
+        store.import_root = program.import_root.take();
+
+        store
+    }
+
     pub fn ins_str<'a>(&mut self, s: impl Into<Cow<'a, str>>) -> usize {
         let s = s.into();
         if let Some(ind) = self.str_map.get(s.as_ref()) {
@@ -61,6 +98,31 @@ impl Store {
         self.mod_map.get(path).map(|d| &d.index)
     }
 
+    /// Finds a package registered by the host
+    pub fn host_package(&self, name: &str) -> Option<usize> {
+        self.host.find(name)
+    }
+
+    /// Only lets custom modules be imported from inside the directory
+    pub fn restrict_imports(&mut self, root: PathBuf) {
+        self.import_root = Some(root);
+    }
+
+    /// Whether a canonical module path can be imported
+    pub fn can_import(&self, path: &Path) -> bool {
+        self.import_root
+            .as_ref()
+            .is_none_or(|root| path.starts_with(root))
+    }
+
+    /// Names of the functions in a package registered by the host
+    pub fn host_names(&self, package: usize) -> Vec<String> {
+        self.host
+            .get(package)
+            .map(|p| p.names().map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+
     pub fn add_import(&mut self, import: Import) -> usize {
         let ind = self.imports.len();
         self.imports.push(import);
@@ -95,17 +157,17 @@ impl Store {
 // Change imports so that "all" imports have a list of string indexes
 // of all identifiers that are actually used in the program
 // This is specifically fo native modules
-fn rewrite_imports(imports: &mut Vec<Import>, str_map: &StrMap) {
+fn rewrite_imports(imports: &mut Vec<Import>, str_map: &StrMap, host: &HostRegistry) {
     for import in imports {
-        if let Import {
-            module: Module::Native(package),
-            kind: ImportKind::All,
-        } = import
-        {
-            let items = package
-                .raw_names()
-                .iter()
-                .filter_map(|n| str_map.get(*n))
+        if let ImportKind::All = import.kind {
+            let names: Vec<&str> = match &import.module {
+                Module::Native(package) => package.raw_names().to_vec(),
+                Module::Host(package) => host.get(*package).map_or(vec![], |p| p.names().collect()),
+                Module::Custom(_) => continue,
+            };
+            let items = names
+                .into_iter()
+                .filter_map(|n| str_map.get(n))
                 .map(|&data| Located {
                     data,
                     start: Location::default(),
@@ -117,29 +179,30 @@ fn rewrite_imports(imports: &mut Vec<Import>, str_map: &StrMap) {
     }
 }
 
-impl From<Store> for Program {
-    fn from(s: Store) -> Self {
-        let mut p = Program::new();
-
-        p.functions = s.functions;
-        p.getters = s.getters;
-        p.setters = s.setters;
-        p.imports = s.imports;
-        p.lambdas = s.lambdas;
+impl Store {
+    /// Moves the store's contents into a program, leaving its body intact
+    pub fn fill(self, p: &mut Program) {
+        p.functions = self.functions;
+        p.getters = self.getters;
+        p.setters = self.setters;
+        p.imports = self.imports;
+        p.lambdas = self.lambdas;
 
-        p.modules = vec![CustomModule::default(); s.mod_map.len()];
+        p.modules = vec![CustomModule::default(); self.mod_map.len()];
 
-        rewrite_imports(&mut p.imports, &s.str_map);
+        rewrite_imports(&mut p.imports, &self.str_map, &self.host);
 
-        for (_, mut data) in s.mod_map {
-            rewrite_imports(&mut data.module.imports, &s.str_map);
+        for (_, mut data) in self.mod_map {
+            rewrite_imports(&mut data.module.imports, &self.str_map, &self.host);
             p.modules[data.index] = data.module;
         }
 
-        p.strings = vec![String::new(); s.str_map.len()];
-        for (string, index) in s.str_map {
+        p.strings = vec![String::new(); self.str_map.len()];
+        for (string, index) in self.str_map {
             p.strings[index] = string;
         }
+        p.host = self.host;
+        p.import_root = self.import_root;
 
         for module in &mut p.modules {
             for (ind, function) in module.functions.iter().enumerate() {
@@ -147,7 +210,13 @@ impl From<Store> for Program {
                 module.lookup.insert(key, ind);
             }
         }
+    }
+}
 
+impl From<Store> for Program {
+    fn from(s: Store) -> Self {
+        let mut p = Program::new();
+        s.fill(&mut p);
         p
     }
 }
@@ -4,7 +4,7 @@ use ast::node::*;
 use ast::parsing::{ast_level, util::*, Store};
 use ast::{ParseError, ParseResult};
 use lex::tokens::*;
-use location::Located;
+use location::{Located, Location};
 use operators::Binary;
 use util::next_if;
 
@@ -34,7 +34,7 @@ pub fn parse_prop(
 
                     let body = next_guard!({ tokens.next() } {
                         Token::OpenGroup(Grouper::Brace) => take_until(&mut tokens, Grouper::Brace)
-                            .and_then(|(t, e)| lam_body(t, store).map_err(|err| {
+                            .and_then(|(t, e)| lam_body(t, e.start.clone(), store).map_err(|err| {
                                 err.neof_or(ParseError::UnexpectedToken(e))
                             }))?
                     });
@@ -58,7 +58,7 @@ pub fn parse_prop(
                     next_guard!({ tokens.next() } { Token::OpenGroup(Grouper::Brace) => {} });
 
                     let body = take_until(&mut tokens, Grouper::Brace)
-                        .and_then(|(t, e)| lam_body(t, store).map_err(|err| {
+                        .and_then(|(t, e)| lam_body(t, e.start.clone(), store).map_err(|err| {
                             err.neof_or(ParseError::UnexpectedToken(e))
                         }))?;
 
@@ -116,7 +116,12 @@ fn leading_stmt(v: &Vec<Located<Token>>) -> bool {
         .is_some()
 }
 
-pub fn lam_body(body: Vec<Located<Token>>, store: &mut Store) -> ParseResult<LambdaBody> {
+/// Parses the body of a lambda, getter, or setter.  `end` is where its closing brace is
+pub fn lam_body(
+    body: Vec<Located<Token>>,
+    end: Location,
+    store: &mut Store,
+) -> ParseResult<LambdaBody> {
     let mut level = 0;
     let mut semicolons = 0;
     for token in &body {
@@ -128,13 +133,13 @@ pub fn lam_body(body: Vec<Located<Token>>, store: &mut Store) -> ParseResult<Lam
         }
     }
 
-    if body.is_empty() {
-        Ok(LambdaBody::Block(Block::new()))
-    } else if semicolons == 0 && !leading_stmt(&body) {
+    if semicolons == 0 && !body.is_empty() && !leading_stmt(&body) {
         parse_expr(body, store)
             .map(Box::from)
             .map(LambdaBody::ImplicitReturn)
     } else {
-        ast_level(body, store).map(LambdaBody::Block)
+        let mut block = ast_level(body, store)?;
+        block.end = end;
+        Ok(LambdaBody::Block(block))
     }
 }
@@ -120,9 +120,9 @@ pub fn parse_expr(
             }
             Token::Keyword(Keyword::Lam) => {
                 let params = parse_params(&mut tokens, store)?;
-                let (body, _) = take_until(&mut tokens, Grouper::Brace)?;
+                let (body, close) = take_until(&mut tokens, Grouper::Brace)?;
 
-                let lambda = Lambda::new(lam_body(body, store)?, params);
+                let lambda = Lambda::new(lam_body(body, close.start, store)?, params);
                 let index = store.add_lam(lambda);
 
                 expr = Expression::Lambda(index).into();
@@ -164,10 +164,17 @@ pub fn parse_expr(
         if let Some(mut expression) = expr {
             while let Some(token) = next_if(tokens.by_ref(), |Located { data, .. }| !data.is_op()) {
                 expression = match token.data {
-                    Token::OpenGroup(Grouper::Parentheses) => Expression::FunctionCall {
-                        function: expression.into(),
-                        args: list_callback(take_until(&mut tokens, Grouper::Parentheses), store)?,
-                    },
+                    Token::OpenGroup(Grouper::Parentheses) => {
+                        let (inner, close) = take_until(&mut tokens, Grouper::Parentheses)?;
+                        let end = close.end.clone();
+
+                        Expression::FunctionCall {
+                            function: expression.into(),
+                            args: list_callback(Ok((inner, close)), store)?,
+                            start: start.clone(),
+                            end,
+                        }
+                    }
                     Token::OpenGroup(Grouper::Bracket) => Expression::IndexAccess {
                         item: expression.into(),
                         index: expr_callback(take_until(&mut tokens, Grouper::Bracket), store)?
@@ -62,6 +62,8 @@ pub enum ModuleErrorBody {
     ParseError(Box<ParseError>),
     PathNotFound,
     CantResolveImport,
+    /// The module is outside of the directory imports are restricted to
+    OutsideImportRoot,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -92,9 +94,7 @@ fn next_construct<T: Iterator<Item = Located<Token>>>(
     store: &mut Store,
 ) -> ParseResult<Node> {
     Ok(match token.data {
-        Token::OpenGroup(Grouper::Bracket) => {
-            Node::Block(take_until(tokens, Grouper::Brace).and_then(|(v, _)| ast_level(v, store))?)
-        }
+        Token::OpenGroup(Grouper::Bracket) => Node::Block(parse_block(tokens, store)?),
 
         Token::Keyword(Keyword::While) => parse_if_block(tokens, store).map(Node::While)?,
         Token::Keyword(Keyword::If) => {
@@ -106,8 +106,7 @@ fn next_construct<T: Iterator<Item = Located<Token>>>(
                 *t == Token::Keyword(Keyword::Else) && else_block.is_none()
             }) {
                 next_guard!({ tokens.next() } {
-                    Token::OpenGroup(Grouper::Brace) => else_block = take_until(tokens, Grouper::Brace)
-                        .and_then(|(v, _)| ast_level(v, store))?.into(),
+                    Token::OpenGroup(Grouper::Brace) => else_block = parse_block(tokens, store)?.into(),
                     Token::Keyword(Keyword::If) => elseifs.push(parse_if_block(tokens, store)?)
                 });
             }
@@ -132,6 +131,12 @@ fn next_construct<T: Iterator<Item = Located<Token>>>(
             };
             Node::ControlFlow(FlowBreak::new_return(expr, token.start, token.end))
         }
+        Token::Keyword(Keyword::Throw) => {
+            let (tokens, _) = zero_level(tokens, |t| *t == Token::Semicolon)?;
+            let expr = parse_expr(tokens, store)?;
+            Node::ControlFlow(FlowBreak::new_throw(expr, token.start, token.end))
+        }
+        Token::Keyword(Keyword::Try) => parse_try(tokens, store)?,
 
         Token::Keyword(Keyword::Decl) => Node::Declaration(parse_decl(tokens, true, store)?),
         Token::Keyword(Keyword::Im) => Node::Declaration(parse_decl(tokens, false, store)?),
@@ -164,7 +169,7 @@ fn next_construct<T: Iterator<Item = Located<Token>>>(
                 parse_expr(t, store)?.into()
             };
 
-            let body = take_until(tokens, Grouper::Brace).and_then(|(v, _)| ast_level(v, store))?;
+            let body = parse_block(tokens, store)?;
 
             Node::For {
                 declaration,
@@ -182,37 +187,47 @@ fn next_construct<T: Iterator<Item = Located<Token>>>(
             }))
         }
 
-        _ => {
-            let loc = token.start.clone();
-            let (mut tokens, semi) = zero_level(tokens, |t| *t == Token::Semicolon)?;
-            tokens.insert(0, token);
+        _ => parse_expr_stmt(token, tokens, true, store).map(Node::Expression)?,
+    })
+}
 
-            let expr = parse_expr(tokens, store)
-                .map_err(|e| e.neof_or(ParseError::UnexpectedToken(semi)))?;
+/// Parses an expression terminated by a semicolon.  Unless `statement_only`
+/// is false, the expression must be a function call or assignment
+fn parse_expr_stmt<T: Iterator<Item = Located<Token>>>(
+    token: Located<Token>,
+    tokens: &mut Peekable<T>,
+    statement_only: bool,
+    store: &mut Store,
+) -> ParseResult<Expression> {
+    let loc = token.start.clone();
+    // The first token is included so that groupers it opens are tracked
+    let mut tokens = std::iter::once(token).chain(tokens);
+    let (tokens, semi) = zero_level(&mut tokens, |t| *t == Token::Semicolon)?;
 
-            if !expr.is_statement() {
-                return Err(ParseError::IllegalExpression(loc));
-            }
+    let expr =
+        parse_expr(tokens, store).map_err(|e| e.neof_or(ParseError::UnexpectedToken(semi)))?;
 
-            Node::Expression(expr)
-        }
-    })
+    if statement_only && !expr.is_statement() {
+        return Err(ParseError::IllegalExpression(loc));
+    }
+
+    Ok(expr)
 }
 
 fn top_level(
     tokens: impl IntoIterator<Item = Located<Token>>,
     path: &Path,
+    mut store: Store,
 ) -> ParseResult<Program> {
     fn is_import(t: &Located<Token>) -> bool {
         t.data == Token::Keyword(Keyword::Import)
     }
 
-    let mut store = Store::new();
     let mut tokens = tokens.into_iter().peekable();
     let mut body = Block::default();
 
-    while next_if(&mut tokens, is_import).is_some() {
-        let import = parse_import(&mut tokens, path, &mut store)?;
+    while let Some(token) = next_if(&mut tokens, is_import) {
+        let import = parse_import(&mut tokens, token.start, path, &mut store)?;
         store.add_import(import);
     }
 
@@ -229,8 +244,9 @@ fn top_level(
                 }
             }),
             _ => {
-                let construct = next_construct(token.clone(), &mut tokens, &mut store)?;
-                body.push(construct);
+                let start = token.start.clone();
+                let construct = next_construct(token, &mut tokens, &mut store)?;
+                body.push(construct, start);
             }
         };
     }
@@ -241,6 +257,18 @@ fn top_level(
     Ok(program)
 }
 
+/// Parses the statements up to a closing brace, which is kept as the end of the block
+fn parse_block<T: Iterator<Item = Located<Token>>>(
+    tokens: &mut T,
+    store: &mut Store,
+) -> ParseResult<Block> {
+    let (tokens, close) = take_until(tokens, Grouper::Brace)?;
+    let mut block = ast_level(tokens, store)?;
+    block.end = close.start;
+
+    Ok(block)
+}
+
 fn ast_level(
     tokens: impl IntoIterator<Item = Located<Token>>,
     store: &mut Store,
@@ -249,20 +277,154 @@ fn ast_level(
     let mut program = Block::new();
 
     while let Some(token) = tokens.next() {
-        program.push(next_construct(token, &mut tokens, store)?);
+        let start = token.start.clone();
+        program.push(next_construct(token, &mut tokens, store)?, start);
     }
 
     Ok(program)
 }
 
+/// Parses a line of REPL input on top of a program from previous inputs.
+/// New procedures, imports, and modules are added to the program and the
+/// input's statements are returned.  Unlike regular programs, imports may
+/// come after other statements and any expression can be used as a statement
+pub fn parse_input(
+    tokens: impl IntoIterator<Item = Located<Token>>,
+    program: &mut Program,
+    dir: &Path,
+) -> ParseResult<Block> {
+    let mut store = Store::resume(program);
+    let result = input_level(tokens, dir, &mut store);
+    store.fill(program);
+
+    result
+}
+
+fn input_level(
+    tokens: impl IntoIterator<Item = Located<Token>>,
+    dir: &Path,
+    store: &mut Store,
+) -> ParseResult<Block> {
+    let mut tokens = tokens.into_iter().peekable();
+    let mut body = Block::new();
+
+    while let Some(token) = tokens.next() {
+        match token.data {
+            Token::Semicolon => {}
+            Token::Keyword(Keyword::Import) => {
+                let import = parse_import(&mut tokens, token.start, dir, store)?;
+
+                // Names from wildcard native imports are only brought into scope if
+                // they appear in the program, which may not happen until a later input
+                if let Import {
+                    module: Module::Native(package),
+                    kind: ImportKind::All,
+                    ..
+                } = &import
+                {
+                    for name in package.raw_names() {
+                        store.ins_str(*name);
+                    }
+                }
+                if let Import {
+                    module: Module::Host(package),
+                    kind: ImportKind::All,
+                    ..
+                } = &import
+                {
+                    for name in store.host_names(*package) {
+                        store.ins_str(name);
+                    }
+                }
+
+                store.add_import(import);
+            }
+            Token::Keyword(Keyword::Proc) => {
+                let proc = parse_proc(&mut tokens, false, store)?;
+                store.add_fn(proc);
+            }
+            Token::Keyword(Keyword::Public) => next_guard!({ tokens.next() } {
+                Token::Keyword(Keyword::Proc) => {
+                    let proc = parse_proc(&mut tokens, true, store)?;
+                    store.add_fn(proc);
+                }
+            }),
+            Token::Keyword(Keyword::Decl)
+            | Token::Keyword(Keyword::Im)
+            | Token::Keyword(Keyword::If)
+            | Token::Keyword(Keyword::While)
+            | Token::Keyword(Keyword::For)
+            | Token::Keyword(Keyword::Return)
+            | Token::Keyword(Keyword::Throw)
+            | Token::Keyword(Keyword::Try)
+            | Token::Keyword(Keyword::Break)
+            | Token::Keyword(Keyword::Continue) => {
+                let start = token.start.clone();
+                body.push(next_construct(token, &mut tokens, store)?, start);
+            }
+            _ => {
+                let start = token.start.clone();
+                let expr = parse_expr_stmt(token, &mut tokens, false, store)?;
+                body.push(Node::Expression(expr), start);
+            }
+        }
+    }
+
+    Ok(body)
+}
+
 pub fn ast(
     tokens: impl IntoIterator<Item = Located<Token>>,
     p: impl AsRef<Path>,
+) -> ParseResult<Program> {
+    ast_with_host(tokens, p, HostRegistry::new())
+}
+
+/// Parses a program that can import the packages registered by the host
+pub fn ast_with_host(
+    tokens: impl IntoIterator<Item = Located<Token>>,
+    p: impl AsRef<Path>,
+    host: HostRegistry,
+) -> ParseResult<Program> {
+    parse_program(tokens, p.as_ref(), Store::with_host(host))
+}
+
+/// Parses a program for the sandbox, which can only import custom
+/// modules from the program's directory and the directories inside it
+pub fn ast_sandboxed(
+    tokens: impl IntoIterator<Item = Located<Token>>,
+    p: impl AsRef<Path>,
 ) -> ParseResult<Program> {
     let path = p.as_ref();
+    let mut dir = path.to_path_buf();
+    remove_file(&mut dir);
+
+    let mut store = Store::new();
+    store.restrict_imports(import_root(&dir));
+    parse_program(tokens, path, store)
+}
+
+/// The canonical form of the directory imports are restricted to
+pub fn import_root(dir: &Path) -> PathBuf {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf())
+}
+
+fn parse_program(
+    tokens: impl IntoIterator<Item = Located<Token>>,
+    path: &Path,
+    store: Store,
+) -> ParseResult<Program> {
     let mut buff = path.to_path_buf();
 
     remove_file(&mut buff);
 
-    top_level(tokens, buff.as_path())
+    let mut program = top_level(tokens, buff.as_path(), store)?;
+    program.path = path.to_path_buf();
+
+    Ok(program)
 }
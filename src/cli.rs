@@ -0,0 +1,470 @@
+///
+/// Command line argument parsing for the gribc binary
+///
+use ast::{LintConfig, LintKind};
+use runtime::console::Console;
+use runtime::{GcMode, Limits, RuntimeConfig, DEFAULT_GC_THRESHOLD, DEFAULT_STACK_LIMIT};
+use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+usage: gribc [run] [options] <file> [args...]
+       gribc repl [options]
+       gribc debug [options] <file> [args...]
+       gribc dap [options]
+       gribc lsp
+       gribc check <file>
+       gribc lint [-A <lint>] [-W <lint>] <file>
+       gribc test [--json <path>] [--junit <path>] <path>
+       gribc fmt [--check | --write] <file>
+       gribc compile [-o <path>] <file>
+       gribc dump-ast [--analyzed] <file>
+       gribc tokens <file>
+       gribc std-manifest
+
+Use `-` as the file to read the program from stdin.  Running gribc
+without any arguments starts the REPL.  Files written by `compile`
+can be passed to run, check, and dump-ast in place of source code.
+
+commands:
+    run         execute a program (default)
+    repl        evaluate code interactively
+    debug       run a program, pausing at breakpoints and stepping through it
+    dap         serve the Debug Adapter Protocol over stdio for editors
+    lsp         serve the Language Server Protocol over stdio for editors
+    check       lex, parse, and analyze a program without running it
+    lint        check a program and warn about likely mistakes
+    test        run the `test_` procedures in the modules under a directory
+    fmt         print a program in the canonical style
+    compile     analyze a program and save it to be run without reparsing
+    dump-ast    print a program's syntax tree as JSON
+    tokens      print the tokens in a program
+    std-manifest
+                print the standard library's functions as JSON
+
+options:
+    --gc-threshold <n>    fewest allocations between garbage collections [default: 1000]
+    --gc-mode <mode>      `generational` or `full` garbage collection [default: generational]
+    --gc-stats            print garbage collector statistics when the program exits
+    --stack-limit <n>     most variables and nested calls on the Grib stack [default: 100000]
+    --stack-size <MiB>    native stack size of the interpreter [default: 256]
+    --max-steps <n>       stop the program after it executes n instructions
+    --time-limit <ms>     stop the program after it runs for this long
+    --max-heap <n>        most heap slots the program can use at once
+    --max-depth <n>       most function calls that can be nested
+    --sandbox             block the `syncio` and `process` packages, `exit`, `heapSnapshot`,
+                          and imports of modules outside the program's directory
+    --seed <n>            make the random functions return the same values every run
+    --analyzed            dump the tree after name resolution
+    -o, --output <path>   where compile writes the program [default: <file>.gribc]
+    --check               have fmt exit with 1 instead if the file isn't formatted
+    -w, --write           have fmt rewrite the file instead of printing it
+    -A, --allow <lint>    have lint skip a warning
+    -W, --warn <lint>     have lint report a warning that's off by default
+    --json <path>         have test write its results to a JSON file
+    --junit <path>        have test write its results to a JUnit XML file
+    -h, --help            print this message
+    -V, --version         print the version
+
+Arguments after the file are passed to the program.
+
+lints:
+    unused-variable       a `decl` variable is never used
+    unused-parameter      a parameter is never used [default: allowed]
+    unused-import         an imported name, or a whole import, is never used
+    shadowed-import       a declaration hides an imported name
+    unreachable-code      a statement follows a return, break, continue, or throw
+    unread-im             an `im` binding is never read
+
+Names starting with `_` are never reported as unused.
+
+exit codes:
+    0     success
+    1     runtime error, a failed test, or `fmt --check` found an unformatted file
+    64    invalid command line arguments
+    65    syntax or analysis error
+    66    the program could not be read
+    73    the compiled program or a test report could not be written";
+
+pub const DEFAULT_STACK_SIZE: usize = 256;
+
+pub const EXIT_RUNTIME: i32 = 1;
+pub const EXIT_USAGE: i32 = 64;
+pub const EXIT_COMPILE: i32 = 65;
+pub const EXIT_NO_INPUT: i32 = 66;
+pub const EXIT_CANT_CREATE: i32 = 73;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Run,
+    Repl,
+    Debug,
+    Dap,
+    Lsp,
+    Check,
+    Lint,
+    Test,
+    Fmt,
+    Compile,
+    DumpAst,
+    Tokens,
+}
+
+impl Command {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "run" => Some(Command::Run),
+            "repl" => Some(Command::Repl),
+            "debug" => Some(Command::Debug),
+            "dap" => Some(Command::Dap),
+            "lsp" => Some(Command::Lsp),
+            "check" => Some(Command::Check),
+            "lint" => Some(Command::Lint),
+            "test" => Some(Command::Test),
+            "fmt" => Some(Command::Fmt),
+            "compile" => Some(Command::Compile),
+            "dump-ast" => Some(Command::DumpAst),
+            "tokens" => Some(Command::Tokens),
+            _ => None,
+        }
+    }
+
+    /// Whether the command talks to the user or an editor instead of
+    /// running a file.  The debug adapter's client chooses what it runs
+    fn is_server(self) -> bool {
+        matches!(self, Command::Repl | Command::Dap | Command::Lsp)
+    }
+}
+
+/// Where the program's source is read from
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+    File(String),
+    Stdin,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub input: Input,
+    /// Arguments following the input file
+    pub args: Vec<String>,
+    pub gc_threshold: usize,
+    pub gc_mode: GcMode,
+    pub gc_stats: bool,
+    pub stack_limit: usize,
+    /// Native stack size in MiB
+    pub stack_size: usize,
+    pub limits: Limits,
+    pub sandbox: bool,
+    /// Seeds the random number generator
+    pub seed: Option<u64>,
+    pub analyzed: bool,
+    /// Where a compiled program is written
+    pub output: Option<String>,
+    /// Whether fmt only checks that the file is formatted
+    pub check: bool,
+    /// Whether fmt rewrites the file in place
+    pub write: bool,
+    /// Which warnings lint reports
+    pub lints: LintConfig,
+    /// Where test writes its results as JSON
+    pub json_report: Option<String>,
+    /// Where test writes its results as JUnit XML
+    pub junit_report: Option<String>,
+}
+
+impl Options {
+    /// Options for an interactive session, which reads from stdin
+    fn repl() -> Self {
+        Self {
+            command: Command::Repl,
+            input: Input::Stdin,
+            args: Vec::new(),
+            gc_threshold: DEFAULT_GC_THRESHOLD,
+            gc_mode: GcMode::Generational,
+            gc_stats: false,
+            stack_limit: DEFAULT_STACK_LIMIT,
+            stack_size: DEFAULT_STACK_SIZE,
+            limits: Limits::default(),
+            sandbox: false,
+            seed: None,
+            analyzed: false,
+            output: None,
+            check: false,
+            write: false,
+            lints: LintConfig::new(),
+            json_report: None,
+            junit_report: None,
+        }
+    }
+
+    /// The runtime the options ask for.  Discarded errors are only
+    /// reported when nothing else shows them to the user
+    pub fn runtime_config(&self, report_errors: bool) -> RuntimeConfig {
+        RuntimeConfig {
+            cleanup_after: self.gc_threshold,
+            gc_mode: self.gc_mode,
+            gc_stats: self.gc_stats,
+            stack_limit: self.stack_limit,
+            report_errors,
+            args: self.args.clone(),
+            limits: self.limits.clone(),
+            sandbox: self.sandbox,
+            console: Console::Std,
+            seed: self.seed,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cli {
+    Exec(Box<Options>),
+    /// Print the standard library manifest
+    Manifest,
+    Help,
+    Version,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CliError {
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue(String, String),
+    MissingInput,
+    UnexpectedArgument(String),
+    /// Two options that can't be given together
+    ConflictingOptions(String, String),
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            CliError::UnknownOption(opt) => write!(f, "unknown option `{}`", opt),
+            CliError::MissingValue(opt) => write!(f, "`{}` expects a value", opt),
+            CliError::InvalidValue(opt, val) => {
+                write!(f, "invalid value `{}` for `{}`", val, opt)
+            }
+            CliError::MissingInput => write!(f, "no input file"),
+            CliError::UnexpectedArgument(arg) => write!(f, "unexpected argument `{}`", arg),
+            CliError::ConflictingOptions(opt, other) => {
+                write!(f, "`{}` can't be used with `{}`", opt, other)
+            }
+        }
+    }
+}
+
+fn parse_gc_mode(opt: &str, value: Option<String>) -> Result<GcMode, CliError> {
+    match value.as_deref() {
+        Some("generational") => Ok(GcMode::Generational),
+        Some("full") => Ok(GcMode::Full),
+        Some(other) => Err(CliError::InvalidValue(opt.to_string(), other.to_string())),
+        None => Err(CliError::MissingValue(opt.to_string())),
+    }
+}
+
+fn parse_lint(opt: &str, value: Option<String>) -> Result<LintKind, CliError> {
+    let value = value.ok_or_else(|| CliError::MissingValue(opt.to_string()))?;
+    LintKind::from_str(&value).ok_or_else(|| CliError::InvalidValue(opt.to_string(), value))
+}
+
+fn parse_seed(opt: &str, value: Option<String>) -> Result<u64, CliError> {
+    let value = value.ok_or_else(|| CliError::MissingValue(opt.to_string()))?;
+    value
+        .parse::<u64>()
+        .map_err(|_| CliError::InvalidValue(opt.to_string(), value))
+}
+
+fn parse_count(opt: &str, value: Option<String>) -> Result<usize, CliError> {
+    let value = value.ok_or_else(|| CliError::MissingValue(opt.to_string()))?;
+    value
+        .parse::<usize>()
+        .ok()
+        .filter(|&n| n > 0)
+        .ok_or_else(|| CliError::InvalidValue(opt.to_string(), value))
+}
+
+/// Parses the arguments following the binary name.  Options must
+/// come before the input file; everything after it belongs to the program
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Cli, CliError> {
+    let mut args = args.into_iter().peekable();
+    if args.peek().is_none() {
+        return Ok(Cli::Exec(Box::new(Options::repl())));
+    }
+
+    if args.peek().map(String::as_str) == Some("std-manifest") {
+        args.next();
+        return match args.next() {
+            Some(arg) => Err(CliError::UnexpectedArgument(arg)),
+            None => Ok(Cli::Manifest),
+        };
+    }
+
+    let command = match args.peek().and_then(|a| Command::from_str(a)) {
+        Some(command) => {
+            args.next();
+            command
+        }
+        None => Command::Run,
+    };
+
+    let mut gc_threshold = DEFAULT_GC_THRESHOLD;
+    let mut gc_mode = GcMode::Generational;
+    let mut gc_stats = false;
+    let mut stack_limit = DEFAULT_STACK_LIMIT;
+    let mut stack_size = DEFAULT_STACK_SIZE;
+    let mut limits = Limits::default();
+    let mut sandbox = false;
+    let mut seed = None;
+    let mut analyzed = false;
+    let mut output = None;
+    let mut check = false;
+    let mut write = false;
+    let mut lints = LintConfig::new();
+    let mut json_report = None;
+    let mut junit_report = None;
+
+    while let Some(arg) = args.next() {
+        let input = match arg.as_str() {
+            "-h" | "--help" => return Ok(Cli::Help),
+            "-V" | "--version" => return Ok(Cli::Version),
+            "--gc-threshold" => {
+                gc_threshold = parse_count(&arg, args.next())?;
+                continue;
+            }
+            "--gc-mode" => {
+                gc_mode = parse_gc_mode(&arg, args.next())?;
+                continue;
+            }
+            "--gc-stats" => {
+                gc_stats = true;
+                continue;
+            }
+            "--stack-limit" => {
+                stack_limit = parse_count(&arg, args.next())?;
+                continue;
+            }
+            "--stack-size" => {
+                stack_size = parse_count(&arg, args.next())?;
+                continue;
+            }
+            "--max-steps" => {
+                limits.steps = Some(parse_count(&arg, args.next())? as u64);
+                continue;
+            }
+            "--time-limit" => {
+                let millis = parse_count(&arg, args.next())?;
+                limits.time = Some(Duration::from_millis(millis as u64));
+                continue;
+            }
+            "--max-heap" => {
+                limits.heap_slots = Some(parse_count(&arg, args.next())?);
+                continue;
+            }
+            "--max-depth" => {
+                limits.call_depth = Some(parse_count(&arg, args.next())?);
+                continue;
+            }
+            "--sandbox" => {
+                sandbox = true;
+                continue;
+            }
+            "--seed" => {
+                seed = Some(parse_seed(&arg, args.next())?);
+                continue;
+            }
+            "--analyzed" => {
+                analyzed = true;
+                continue;
+            }
+            "-o" | "--output" if command == Command::Compile => {
+                output = Some(args.next().ok_or(CliError::MissingValue(arg))?);
+                continue;
+            }
+            "--check" if command == Command::Fmt => {
+                if write {
+                    return Err(CliError::ConflictingOptions(arg, "--write".to_string()));
+                }
+                check = true;
+                continue;
+            }
+            "-w" | "--write" if command == Command::Fmt => {
+                if check {
+                    return Err(CliError::ConflictingOptions(arg, "--check".to_string()));
+                }
+                write = true;
+                continue;
+            }
+            "-A" | "--allow" if command == Command::Lint => {
+                lints.disable(parse_lint(&arg, args.next())?);
+                continue;
+            }
+            "-W" | "--warn" if command == Command::Lint => {
+                lints.enable(parse_lint(&arg, args.next())?);
+                continue;
+            }
+            "--json" if command == Command::Test => {
+                json_report = Some(args.next().ok_or(CliError::MissingValue(arg))?);
+                continue;
+            }
+            "--junit" if command == Command::Test => {
+                junit_report = Some(args.next().ok_or(CliError::MissingValue(arg))?);
+                continue;
+            }
+            opt if opt.starts_with('-') && opt != "-" => return Err(CliError::UnknownOption(arg)),
+            _ if command.is_server() => return Err(CliError::UnexpectedArgument(arg)),
+            // The debugger reads its commands from stdin
+            "-" if command == Command::Debug => return Err(CliError::UnexpectedArgument(arg)),
+            // There's no file to rewrite or directory to search
+            "-" if write || command == Command::Test => {
+                return Err(CliError::UnexpectedArgument(arg))
+            }
+            "-" => Input::Stdin,
+            _ => Input::File(arg),
+        };
+
+        let args = args.collect::<Vec<_>>();
+        let takes_args = command == Command::Run || command == Command::Debug;
+        if let (Some(extra), false) = (args.first(), takes_args) {
+            return Err(CliError::UnexpectedArgument(extra.clone()));
+        }
+
+        return Ok(Cli::Exec(Box::new(Options {
+            command,
+            input,
+            args,
+            gc_threshold,
+            gc_mode,
+            gc_stats,
+            stack_limit,
+            stack_size,
+            limits,
+            sandbox,
+            seed,
+            analyzed,
+            output,
+            check,
+            write,
+            lints,
+            json_report,
+            junit_report,
+        })));
+    }
+
+    if command.is_server() {
+        return Ok(Cli::Exec(Box::new(Options {
+            command,
+            gc_threshold,
+            gc_mode,
+            gc_stats,
+            stack_limit,
+            stack_size,
+            limits,
+            sandbox,
+            seed,
+            ..Options::repl()
+        })));
+    }
+
+    Err(CliError::MissingInput)
+}
@@ -0,0 +1,499 @@
+///
+/// Debug Adapter Protocol server, which lets editors launch and debug Grib programs
+///
+use ast::{self, node::Program};
+use diagnostic::Diagnostic;
+use framing;
+use lex;
+use runtime::debug::{Breakpoints, Debugger, Output, Paused, Resume, StopReason};
+use runtime::error::{RuntimeError, RuntimeErrorKind};
+use runtime::values::Value;
+use runtime::{self, RuntimeConfig};
+use serde_json::Value as Json;
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::mem;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Grib programs run on a single thread
+const THREAD_ID: i64 = 1;
+
+struct Request {
+    seq: i64,
+    command: String,
+    arguments: Json,
+}
+
+/// Messages to and from the client, framed with `Content-Length` headers
+struct Connection {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    seq: i64,
+}
+
+impl Connection {
+    fn new(input: Box<dyn BufRead>, output: Box<dyn Write>) -> Self {
+        Self {
+            input,
+            output,
+            seq: 0,
+        }
+    }
+
+    /// Reads messages until one is a request.  Anything else the client sends is ignored
+    fn next_request(&mut self) -> Option<Request> {
+        loop {
+            let message = framing::read(&mut self.input)?;
+            if message["type"] != "request" {
+                continue;
+            }
+            return Some(Request {
+                seq: message["seq"].as_i64().unwrap_or_default(),
+                command: message["command"].as_str().unwrap_or_default().to_string(),
+                arguments: message["arguments"].clone(),
+            });
+        }
+    }
+
+    fn send(&mut self, mut message: Json) {
+        self.seq += 1;
+        message["seq"] = self.seq.into();
+
+        // There's no one left to tell if the client has gone away
+        let _ = framing::write(&mut self.output, &message);
+    }
+
+    fn respond(&mut self, request: &Request, result: Result<Json, String>) {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "command": request.command,
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = message.into(),
+        }
+        self.send(response);
+    }
+
+    fn event(&mut self, event: &str, body: Json) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+}
+
+/// A program the client asked to run
+struct Launch {
+    program: Program,
+    args: Vec<String>,
+    stop_on_entry: bool,
+    no_debug: bool,
+}
+
+/// Reads and analyzes the program named in a launch request
+fn launch(arguments: &Json, sandbox: bool) -> Result<Launch, String> {
+    let path = arguments["program"]
+        .as_str()
+        .ok_or("launch needs the path of the program to run")?;
+    let path = fs::canonicalize(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    let source = fs::read_to_string(&path)
+        .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+
+    let program = match ast::is_compiled(&source) {
+        true => ast::read_compiled(&source)
+            .map_err(|e| format!("could not load {}: {}", path.display(), e))?,
+        false => analyze(&source, &path, sandbox)?,
+    };
+
+    let args = arguments["args"]
+        .as_array()
+        .map(|args| {
+            args.iter()
+                .filter_map(|arg| arg.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(Launch {
+        program,
+        args,
+        stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+        no_debug: arguments["noDebug"].as_bool().unwrap_or(false),
+    })
+}
+
+fn analyze(source: &str, path: &Path, sandbox: bool) -> Result<Program, String> {
+    let tokens =
+        lex::lex(source).map_err(|e| Diagnostic::from_lex(&e, path, source).to_string())?;
+    let parsed = match sandbox {
+        true => ast::ast_sandboxed(tokens, path),
+        false => ast::ast(tokens, path),
+    };
+    let mut tree = parsed.map_err(|e| Diagnostic::from_parse(&e, path, source).to_string())?;
+    ast::ref_check(&mut tree)
+        .map_err(|e| Diagnostic::from_walk(&e, &tree, path, source).to_string())?;
+    Ok(tree)
+}
+
+fn capabilities() -> Json {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsEvaluateForHovers": true,
+        "supportsTerminateRequest": true,
+    })
+}
+
+/// Handles the requests that don't need a paused program
+fn common(request: &Request, breakpoints: &mut Breakpoints) -> Result<Json, String> {
+    match request.command.as_str() {
+        "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+        "setBreakpoints" => {
+            let path = request.arguments["source"]["path"]
+                .as_str()
+                .ok_or("breakpoints need the path of their source")?;
+            let lines: Vec<_> = request.arguments["breakpoints"]
+                .as_array()
+                .map(|list| list.iter().filter_map(|b| b["line"].as_u64()).collect())
+                .unwrap_or_default();
+
+            breakpoints.clear(path);
+            for &line in &lines {
+                breakpoints.add(path, line as usize);
+            }
+            let verified = lines
+                .iter()
+                .map(|line| json!({ "verified": true, "line": line }))
+                .collect::<Vec<_>>();
+            Ok(json!({ "breakpoints": verified }))
+        }
+        "setExceptionBreakpoints" | "pause" => Ok(json!({})),
+        "stackTrace" => Ok(json!({ "stackFrames": [], "totalFrames": 0 })),
+        command => Err(format!("unsupported request `{}`", command)),
+    }
+}
+
+/// The children of an array or hash, shown when it's expanded
+fn children(value: &Value) -> Option<Vec<(String, Value)>> {
+    match value {
+        Value::Array(items) => Some(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| (i.to_string(), item.clone()))
+                .collect(),
+        ),
+        Value::Hash(entries) => Some(
+            entries
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Nil => "nil",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Hash(_) => "hash",
+        Value::Error(_) => "error",
+        Value::Opaque(kind) => kind,
+    }
+}
+
+/// Arrays and hashes are summarized, since their items are shown as children
+fn summary(value: &Value) -> String {
+    match value {
+        Value::Array(items) => format!("array({})", items.len()),
+        Value::Hash(entries) => format!("hash({})", entries.len()),
+        _ => value.to_string(),
+    }
+}
+
+/// Answers the client's requests whenever the program pauses
+struct Adapter {
+    connection: Rc<RefCell<Connection>>,
+    /// Whether the next pause is the one before the first statement
+    entry: bool,
+    /// The values that can be expanded, indexed by their variables reference minus one.
+    /// References are only valid while the program stays paused
+    references: Vec<Vec<(String, Value)>>,
+}
+
+impl Adapter {
+    fn reference(&mut self, children: Vec<(String, Value)>) -> usize {
+        self.references.push(children);
+        self.references.len()
+    }
+
+    fn variable(&mut self, name: &str, value: &Value) -> Json {
+        let reference = children(value).map_or(0, |c| self.reference(c));
+        json!({
+            "name": name,
+            "value": summary(value),
+            "type": kind(value),
+            "variablesReference": reference,
+        })
+    }
+
+    /// Finds the value of a variable followed by properties or indexes, like `point.x` or `list[0]`
+    fn evaluate(&self, paused: &mut Paused, expression: &str) -> Result<Value, String> {
+        let normalized = expression.replace('[', ".").replace(']', "");
+        let mut path = normalized
+            .split('.')
+            .map(|part| part.trim().trim_matches('"'));
+
+        let name = path.next().unwrap_or_default();
+        let value = match name {
+            "this" => paused.this().clone(),
+            _ => paused
+                .local(name)
+                .ok_or_else(|| format!("no variable named {} is in scope", name))?,
+        };
+
+        let mut value = paused.copy(&value);
+        for part in path {
+            value = match (value, part.parse::<usize>()) {
+                (Value::Array(mut items), Ok(index)) if index < items.len() => {
+                    items.swap_remove(index)
+                }
+                (Value::Hash(mut entries), _) => entries.remove(part).unwrap_or(Value::Nil),
+                _ => return Err(format!("can't find `{}` in {}", part, expression)),
+            };
+        }
+        Ok(value)
+    }
+
+    fn inspect(&mut self, paused: &mut Paused, request: &Request) -> Result<Json, String> {
+        let arguments = &request.arguments;
+        match request.command.as_str() {
+            "stackTrace" => {
+                let frames = paused
+                    .trace()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(id, frame)| {
+                        let mut json = json!({
+                            "id": id,
+                            "name": frame.name,
+                            "line": frame.location.as_ref().map_or(0, |l| l.get_row()),
+                            "column": frame.location.as_ref().map_or(0, |l| l.get_col() + 1),
+                        });
+                        if let Some(path) = &frame.path {
+                            let name = path.file_name().unwrap_or_default().to_string_lossy();
+                            json["source"] = json!({ "name": name, "path": path });
+                        }
+                        json
+                    })
+                    .collect::<Vec<_>>();
+                Ok(json!({ "totalFrames": frames.len(), "stackFrames": frames }))
+            }
+            "scopes" => {
+                // Only the variables of the innermost function can be read
+                if arguments["frameId"].as_u64().unwrap_or_default() != 0 {
+                    return Ok(json!({ "scopes": [] }));
+                }
+
+                let mut locals = Vec::new();
+                for (name, value) in paused.locals() {
+                    locals.push((name, paused.copy(&value)));
+                }
+                let this = paused.this().clone();
+                if !this.is_nil() {
+                    locals.push(("this".to_string(), paused.copy(&this)));
+                }
+
+                let reference = self.reference(locals);
+                let scope = json!({
+                    "name": "Locals",
+                    "presentationHint": "locals",
+                    "variablesReference": reference,
+                    "expensive": false,
+                });
+                Ok(json!({ "scopes": [scope] }))
+            }
+            "variables" => {
+                let reference = arguments["variablesReference"].as_u64().unwrap_or_default();
+                let children = (reference as usize)
+                    .checked_sub(1)
+                    .and_then(|index| self.references.get(index))
+                    .cloned()
+                    .unwrap_or_default();
+                let variables = children
+                    .iter()
+                    .map(|(name, value)| self.variable(name, value))
+                    .collect::<Vec<_>>();
+                Ok(json!({ "variables": variables }))
+            }
+            "evaluate" => {
+                let expression = arguments["expression"].as_str().unwrap_or_default();
+                let value = self.evaluate(paused, expression)?;
+                let reference = children(&value).map_or(0, |c| self.reference(c));
+                Ok(json!({
+                    "result": summary(&value),
+                    "type": kind(&value),
+                    "variablesReference": reference,
+                }))
+            }
+            _ => common(request, paused.breakpoints),
+        }
+    }
+}
+
+impl Debugger for Adapter {
+    fn paused(&mut self, paused: &mut Paused, reason: StopReason) -> Resume {
+        let reason = match (mem::take(&mut self.entry), reason) {
+            (true, _) => "entry",
+            (false, StopReason::Breakpoint) => "breakpoint",
+            (false, StopReason::Step) => "step",
+        };
+        self.references.clear();
+        self.connection.borrow_mut().event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        );
+
+        loop {
+            let request = match self.connection.borrow_mut().next_request() {
+                Some(request) => request,
+                None => return Resume::Stop,
+            };
+
+            let resume = match request.command.as_str() {
+                "continue" => Some(Resume::Continue),
+                "next" => Some(Resume::StepOver),
+                "stepIn" => Some(Resume::StepIn),
+                "stepOut" => Some(Resume::StepOut),
+                "disconnect" | "terminate" => Some(Resume::Stop),
+                _ => None,
+            };
+            let result = match resume {
+                Some(Resume::Continue) => Ok(json!({ "allThreadsContinued": true })),
+                Some(_) => Ok(json!({})),
+                None => self.inspect(paused, &request),
+            };
+
+            self.connection.borrow_mut().respond(&request, result);
+            if let Some(resume) = resume {
+                return resume;
+            }
+        }
+    }
+
+    fn output(&mut self, text: &str, output: Output) -> bool {
+        let category = match output {
+            Output::Stdout => "stdout",
+            Output::Stderr => "stderr",
+        };
+        self.connection
+            .borrow_mut()
+            .event("output", json!({ "category": category, "output": text }));
+        true
+    }
+}
+
+/// Serves the client on stdin and stdout.  The program's output is sent as events
+pub fn run(config: RuntimeConfig) -> i32 {
+    serve(Box::new(io::stdin().lock()), Box::new(io::stdout()), config)
+}
+
+/// Configures and runs a single program for the client, then waits for it to disconnect
+pub fn serve(input: Box<dyn BufRead>, output: Box<dyn Write>, mut config: RuntimeConfig) -> i32 {
+    let connection = Rc::new(RefCell::new(Connection::new(input, output)));
+    let mut breakpoints = Breakpoints::new();
+    let mut launched = None;
+    let mut configured = false;
+
+    // The program starts once it's launched and the client has set its breakpoints
+    let launched = loop {
+        if let (Some(_), true) = (&launched, configured) {
+            break launched.take().unwrap();
+        }
+
+        let mut connection = connection.borrow_mut();
+        let request = match connection.next_request() {
+            Some(request) => request,
+            None => return 0,
+        };
+
+        let result = match request.command.as_str() {
+            "initialize" => Ok(capabilities()),
+            "launch" => launch(&request.arguments, config.sandbox).map(|l| {
+                launched = Some(l);
+                json!({})
+            }),
+            "configurationDone" => {
+                configured = true;
+                Ok(json!({}))
+            }
+            "disconnect" | "terminate" => {
+                connection.respond(&request, Ok(json!({})));
+                return 0;
+            }
+            _ => common(&request, &mut breakpoints),
+        };
+        connection.respond(&request, result);
+        if request.command == "initialize" {
+            connection.event("initialized", json!({}));
+        }
+    };
+
+    if launched.no_debug {
+        breakpoints = Breakpoints::new();
+    }
+    let start = match launched.stop_on_entry && !launched.no_debug {
+        true => Resume::StepIn,
+        false => Resume::Continue,
+    };
+    let adapter = Adapter {
+        connection: connection.clone(),
+        entry: start == Resume::StepIn,
+        references: Vec::new(),
+    };
+
+    config.args = launched.args;
+    let result = runtime::debug(
+        &launched.program,
+        config,
+        Box::new(adapter),
+        breakpoints,
+        start,
+    );
+
+    let mut connection = connection.borrow_mut();
+    let exit_code = match result {
+        Ok(()) => 0,
+        Err(ref e) if e.kind == RuntimeErrorKind::Stopped => 1,
+        Err(RuntimeError {
+            kind: RuntimeErrorKind::Exit(status),
+            ..
+        }) => status,
+        Err(e) => {
+            let output = json!({ "category": "stderr", "output": e.to_string() });
+            connection.event("output", output);
+            1
+        }
+    };
+    connection.event("exited", json!({ "exitCode": exit_code }));
+    connection.event("terminated", json!({}));
+
+    while let Some(request) = connection.next_request() {
+        match request.command.as_str() {
+            "disconnect" | "terminate" => {
+                connection.respond(&request, Ok(json!({})));
+                break;
+            }
+            _ => {
+                let result = common(&request, &mut Breakpoints::new());
+                connection.respond(&request, result);
+            }
+        }
+    }
+    0
+}
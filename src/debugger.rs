@@ -0,0 +1,151 @@
+///
+/// Command line front end for stepping through programs
+///
+use runtime::debug::{Debugger, Paused, Resume, StopReason};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const PROMPT: &str = "(debug) ";
+
+pub const HELP: &str = "\
+commands:
+    break [<file>:]<line>   pause when the program reaches a line (b)
+    delete [<file>:]<line>  remove a breakpoint
+    breakpoints             list the breakpoints
+    continue                run until the next breakpoint (c)
+    step                    run one statement, entering functions (s)
+    next                    run one statement, stepping over functions (n)
+    finish                  run until the current function returns (out)
+    locals                  print the variables that are in scope
+    this                    print the value of `this`
+    print <name>            print a variable (p)
+    backtrace               print the functions being called (bt)
+    quit                    stop the program (q)
+    help                    print this message (h)";
+
+/// Reads commands from stdin whenever the program pauses
+pub struct Cli {
+    /// The lines of each file that's been shown, so they're only read once
+    sources: HashMap<PathBuf, Vec<String>>,
+    /// Where the program last paused, used as the file for breakpoints without one
+    current: PathBuf,
+}
+
+impl Cli {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            sources: HashMap::new(),
+            current: path.to_path_buf(),
+        }
+    }
+
+    fn source_line(&mut self, path: &Path, line: usize) -> Option<&str> {
+        let lines = self.sources.entry(path.to_path_buf()).or_insert_with(|| {
+            fs::read_to_string(path)
+                .map(|s| s.lines().map(str::to_string).collect())
+                .unwrap_or_default()
+        });
+        lines.get(line.checked_sub(1)?).map(String::as_str)
+    }
+
+    fn show(&mut self, paused: &Paused, reason: StopReason) {
+        let path = paused.path().to_path_buf();
+        let line = paused.location().get_row();
+        let reason = match reason {
+            StopReason::Breakpoint => "breakpoint",
+            StopReason::Step => "step",
+        };
+        println!("{} at {}:{}", reason, path.display(), line);
+        if let Some(source) = self.source_line(&path, line) {
+            println!("{:>5} | {}", line, source);
+        }
+        self.current = path;
+    }
+
+    /// Splits a breakpoint into its file and line
+    fn parse_breakpoint(&self, arg: &str) -> Option<(PathBuf, usize)> {
+        match arg.rsplit_once(':') {
+            Some((path, line)) => Some((path.into(), line.parse().ok()?)),
+            None => Some((self.current.clone(), arg.parse().ok()?)),
+        }
+    }
+
+    /// Carries out a command, returning how to resume if it continues the program
+    fn command(&mut self, paused: &mut Paused, line: &str) -> Option<Resume> {
+        let mut words = line.split_whitespace();
+        let command = words.next()?;
+        let arg = words.next();
+
+        match (command, arg) {
+            ("c" | "continue", _) => return Some(Resume::Continue),
+            ("s" | "step", _) => return Some(Resume::StepIn),
+            ("n" | "next", _) => return Some(Resume::StepOver),
+            ("out" | "finish", _) => return Some(Resume::StepOut),
+            ("q" | "quit", _) => return Some(Resume::Stop),
+            ("b" | "break", Some(arg)) => match self.parse_breakpoint(arg) {
+                Some((path, line)) => {
+                    paused.breakpoints.add(&path, line);
+                    println!("breakpoint set at {}:{}", path.display(), line);
+                }
+                None => println!("expected a breakpoint like `12` or `file.grib:12`"),
+            },
+            ("delete", Some(arg)) => match self.parse_breakpoint(arg) {
+                Some((path, line)) if paused.breakpoints.remove(&path, line) => {}
+                _ => println!("no breakpoint at {}", arg),
+            },
+            ("breakpoints", _) => {
+                for (path, line) in paused.breakpoints.iter() {
+                    println!("{}:{}", path.display(), line);
+                }
+            }
+            ("locals", _) => {
+                for (name, value) in paused.locals() {
+                    let value = paused.copy(&value);
+                    println!("{} = {}", name, value);
+                }
+            }
+            ("this", _) => {
+                let this = paused.this().clone();
+                println!("{}", paused.copy(&this));
+            }
+            ("p" | "print", Some(name)) => match paused.local(name) {
+                Some(value) => println!("{}", paused.copy(&value)),
+                None => println!("no variable named {} is in scope", name),
+            },
+            ("bt" | "backtrace", _) => {
+                for frame in paused.trace() {
+                    println!("    {}", frame);
+                }
+            }
+            ("h" | "help", _) => println!("{}", HELP),
+            _ => println!("unknown command `{}`, try `help`", line.trim()),
+        }
+        None
+    }
+}
+
+impl Debugger for Cli {
+    fn paused(&mut self, paused: &mut Paused, reason: StopReason) -> Resume {
+        self.show(paused, reason);
+
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+        let mut line = String::new();
+        loop {
+            print!("{}", PROMPT);
+            io::stdout().flush().ok();
+
+            line.clear();
+            match input.read_line(&mut line) {
+                // The program keeps running once there are no more commands
+                Ok(0) | Err(_) => return Resume::Continue,
+                Ok(_) => {}
+            }
+            if let Some(resume) = self.command(paused, &line) {
+                return resume;
+            }
+        }
+    }
+}
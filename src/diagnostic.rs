@@ -0,0 +1,319 @@
+///
+/// Human readable error reports for lexing, parsing, and analysis errors
+///
+use ast::node::Program;
+use ast::{Lint, LintKind, ModuleError, ModuleErrorBody, ParseError, WalkError, WalkErrorType};
+use lex::{LexError, LexErrorData};
+use location::Location;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The source line an error points to along with the
+/// columns that should be underlined
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snippet {
+    pub row: usize,
+    pub line: String,
+    pub start_col: usize,
+    pub end_col: usize,
+}
+
+impl Snippet {
+    /// Extracts the line containing `start` from the source text.
+    /// Spans that cover multiple lines are underlined to the end of the first line
+    pub fn new(source: &str, start: &Location, end: &Location) -> Option<Self> {
+        let row = start.get_row();
+        let line = source.lines().nth(row.checked_sub(1)?)?.to_string();
+        let line_len = line.chars().count();
+
+        let start_col = start.get_col().min(line_len);
+        let end_col = if end.get_row() == row {
+            end.get_col().min(line_len)
+        } else {
+            line_len
+        };
+
+        Some(Self {
+            row,
+            line,
+            start_col,
+            end_col: end_col.max(start_col + 1),
+        })
+    }
+
+    fn underline(&self) -> String {
+        let mut underline = String::new();
+
+        // Tabs are copied so that the carets line up with the source line
+        for c in self.line.chars().take(self.start_col) {
+            underline.push(if c == '\t' { '\t' } else { ' ' });
+        }
+        for _ in self.start_col..self.end_col {
+            underline.push('^');
+        }
+
+        underline
+    }
+}
+
+/// Whether a diagnostic stops the program from running
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    /// Reported by lints for code that runs but is likely a mistake
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub path: PathBuf,
+    pub location: Option<Location>,
+    pub snippet: Option<Snippet>,
+    /// Extra context, such as the chain of imports that led to an error
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    fn new(message: impl Into<String>, path: &Path) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            path: path.to_path_buf(),
+            location: None,
+            snippet: None,
+            notes: Vec::new(),
+        }
+    }
+
+    fn with_span(mut self, source: Option<&str>, start: &Location, end: &Location) -> Self {
+        self.snippet = source.and_then(|s| Snippet::new(s, start, end));
+        self.location = Some(start.clone());
+        self
+    }
+
+    fn note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    /// Diagnostic for an error encountered while lexing a file
+    pub fn from_lex(err: &LexError, path: &Path, source: &str) -> Self {
+        let message = match &err.data {
+            LexErrorData::UnexpectedEOF => "unexpected end of file".to_string(),
+            LexErrorData::UnexpectedChar(c) => format!("unexpected character `{}`", c),
+            LexErrorData::InvalidNumber(n) => format!("invalid number literal `{}`", n),
+        };
+
+        // Lexer locations are recorded after the offending character is consumed
+        let end = err.loc.clone();
+        let start = Location::at(end.get_row(), end.get_col().saturating_sub(1));
+
+        Diagnostic::new(message, path).with_span(Some(source), &start, &end)
+    }
+
+    /// Diagnostic for an error encountered while parsing a file.
+    /// Errors in imported modules are reported against the module's source
+    pub fn from_parse(err: &ParseError, path: &Path, source: &str) -> Self {
+        let source = Some(source);
+        let at = |message: &str, loc: &Location| {
+            Diagnostic::new(message, path).with_span(source, loc, loc)
+        };
+
+        match err {
+            ParseError::UnexpectedEOF => {
+                // Point at the last character in the file
+                let end = source
+                    .map(|s| Location::end_of(s.trim_end()))
+                    .unwrap_or_default();
+                let start = Location::at(end.get_row(), end.get_col().saturating_sub(1));
+                Diagnostic::new("unexpected end of input", path).with_span(source, &start, &end)
+            }
+            ParseError::UnexpectedToken(token) => Diagnostic::new(
+                format!("unexpected token `{}`", token.data),
+                path,
+            )
+            .with_span(source, &token.start, &token.end),
+            ParseError::IllegallyScopedImport(loc) => {
+                at("imports can only be made at the top level", loc)
+            }
+            ParseError::IllegalExpression(loc) => at(
+                "only function calls and assignments can be used as statements",
+                loc,
+            ),
+            ParseError::IllegalLeftExpression(loc) => {
+                at("invalid left-hand side of assignment", loc)
+            }
+            ParseError::FunctionNotAtTopLevel(loc) => {
+                at("procedures can only be defined at the top level", loc)
+            }
+            ParseError::MisplacedImport(loc) => {
+                at("imports must come before all other statements", loc)
+            }
+            ParseError::DuplicateParam(param) => Diagnostic::new(
+                format!("duplicate parameter `{}`", param.data),
+                path,
+            )
+            .with_span(source, &param.start, &param.end),
+            ParseError::ParamAfterSpread(param) => Diagnostic::new(
+                format!(
+                    "parameter `{}` cannot come after a spread parameter",
+                    param.data
+                ),
+                path,
+            )
+            .with_span(source, &param.start, &param.end),
+            ParseError::ModuleError(module) => Self::from_module(module, path, source),
+        }
+    }
+
+    fn from_module(err: &ModuleError, path: &Path, source: Option<&str>) -> Self {
+        let import = &err.path;
+        let module_path = import.data.as_path();
+        let imported_from = format!(
+            "imported from {}:{}:{}",
+            path.display(),
+            import.start.get_row(),
+            import.start.get_col() + 1
+        );
+
+        match &err.data {
+            ModuleErrorBody::PathNotFound => Diagnostic::new(
+                format!("could not read module {}", module_path.display()),
+                path,
+            )
+            .with_span(source, &import.start, &import.end),
+            ModuleErrorBody::CantResolveImport => Diagnostic::new(
+                format!("could not resolve module {}", module_path.display()),
+                path,
+            )
+            .with_span(source, &import.start, &import.end),
+            ModuleErrorBody::OutsideImportRoot => Diagnostic::new(
+                format!(
+                    "can't import {} in the sandbox, since it's outside the program's directory",
+                    module_path.display()
+                ),
+                path,
+            )
+            .with_span(source, &import.start, &import.end),
+            ModuleErrorBody::LexError(e) => {
+                let module_source = fs::read_to_string(module_path).unwrap_or_default();
+                Self::from_lex(e, module_path, &module_source).note(imported_from)
+            }
+            ModuleErrorBody::ParseError(e) => {
+                let module_source = fs::read_to_string(module_path).unwrap_or_default();
+                Self::from_parse(e, module_path, &module_source).note(imported_from)
+            }
+        }
+    }
+
+    /// Diagnostic for an error found during static analysis.
+    /// String table indices are resolved to their names
+    pub fn from_walk(err: &WalkError, program: &Program, path: &Path, source: &str) -> Self {
+        let name = |ind: &usize| {
+            program
+                .strings
+                .get(*ind)
+                .map(String::as_str)
+                .unwrap_or("<unknown>")
+        };
+
+        let message = match &err.kind {
+            WalkErrorType::ImmutableModification(s) => {
+                format!("cannot assign to immutable value `{}`", name(s))
+            }
+            WalkErrorType::IdentifierNotFound(s) => {
+                format!("cannot find `{}` in this scope", name(s))
+            }
+            WalkErrorType::InvalidRedefinition(s) => {
+                format!("`{}` is already defined in this scope", name(s))
+            }
+            WalkErrorType::InvalidBreak => "`break` outside of a loop".to_string(),
+            WalkErrorType::InvalidContinue => "`continue` outside of a loop".to_string(),
+            WalkErrorType::InvalidReturn => "`return` outside of a function".to_string(),
+            WalkErrorType::InvalidThis => {
+                "`this` can only be used inside lambdas and auto-properties".to_string()
+            }
+        };
+
+        match err.module.and_then(|ind| program.modules.get(ind)) {
+            Some(module) => {
+                let module_source = fs::read_to_string(&module.path).ok();
+                Diagnostic::new(message, &module.path).with_span(
+                    module_source.as_deref(),
+                    &err.start,
+                    &err.end,
+                )
+            }
+            None => Diagnostic::new(message, path).with_span(Some(source), &err.start, &err.end),
+        }
+    }
+
+    /// Warning for a lint found in the main program
+    pub fn from_lint(lint: &Lint, program: &Program, path: &Path, source: &str) -> Self {
+        let name = lint
+            .name
+            .and_then(|ind| program.strings.get(ind))
+            .map(String::as_str)
+            .unwrap_or("<unknown>");
+
+        let message = match (&lint.kind, lint.name) {
+            (LintKind::UnusedImport, None) => "nothing from this import is used".to_string(),
+            (LintKind::UnusedImport, Some(_)) => format!("unused import `{}`", name),
+            (LintKind::UnusedVariable, _) => format!("variable `{}` is never used", name),
+            (LintKind::UnusedParameter, _) => format!("parameter `{}` is never used", name),
+            (LintKind::UnreadIm, _) => format!("`{}` is never read", name),
+            (LintKind::ShadowedImport, _) => format!("`{}` shadows an import", name),
+            (LintKind::UnreachableCode, _) => "unreachable code".to_string(),
+        };
+
+        let mut diagnostic = Diagnostic::new(message, path)
+            .with_span(Some(source), &lint.start, &lint.end)
+            .note(format!("reported by the `{}` lint", lint.kind.str()));
+        diagnostic.severity = Severity::Warning;
+        diagnostic
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "{}: {}", self.severity, self.message)?;
+
+        match &self.location {
+            Some(loc) => writeln!(
+                f,
+                "  --> {}:{}:{}",
+                self.path.display(),
+                loc.get_row(),
+                loc.get_col() + 1
+            )?,
+            None => writeln!(f, "  --> {}", self.path.display())?,
+        }
+
+        if let Some(snippet) = &self.snippet {
+            let gutter = snippet.row.to_string();
+            let pad = " ".repeat(gutter.len());
+
+            writeln!(f, "{} |", pad)?;
+            writeln!(f, "{} | {}", gutter, snippet.line)?;
+            writeln!(f, "{} | {}", pad, snippet.underline())?;
+        }
+
+        for note in &self.notes {
+            writeln!(f, "note: {}", note)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+///
+/// JSON messages framed with `Content-Length` headers, which both the
+/// Debug Adapter Protocol and the Language Server Protocol use over stdio
+///
+use serde_json::{self, Value as Json};
+use std::io::{self, BufRead, Write};
+
+/// Reads the next message, or `None` once the stream is closed.
+/// A body that isn't valid JSON is read as null
+pub fn read(input: &mut dyn BufRead) -> Option<Json> {
+    let mut length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+
+        let header = line.trim_end();
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        } else if header.is_empty() && length.is_some() {
+            break;
+        }
+    }
+
+    let mut body = vec![0; length?];
+    input.read_exact(&mut body).ok()?;
+    Some(serde_json::from_slice(&body).unwrap_or(Json::Null))
+}
+
+pub fn write(output: &mut dyn Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
@@ -0,0 +1,149 @@
+///
+/// Runs Grib code on behalf of a Rust program
+///
+use ast::{self, node::Program, ReplScope};
+use diagnostic::Diagnostic;
+use lex;
+use runtime::error::RuntimeError;
+use runtime::host::{HostPackage, ReservedName};
+use runtime::values::{Callable, Value};
+use runtime::{RuntimeConfig, Session};
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    /// A source file couldn't be read
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// The code couldn't be lexed, parsed, or analyzed
+    Syntax(Box<Diagnostic>),
+    Runtime(Box<RuntimeError>),
+    /// No procedure with the given name has been loaded
+    UnknownProcedure(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, error } => write!(f, "could not read {}: {}", path.display(), error),
+            Error::Syntax(diagnostic) => write!(f, "{}", diagnostic),
+            Error::Runtime(error) => write!(f, "{}", error),
+            Error::UnknownProcedure(name) => write!(f, "no procedure named `{}`", name),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<RuntimeError> for Error {
+    fn from(error: RuntimeError) -> Self {
+        Error::Runtime(Box::new(error))
+    }
+}
+
+/// Loads and runs Grib code.  Everything that's loaded shares one
+/// scope and heap, so procedures, imports, and top level variables
+/// declared by one piece of code can be used by the ones after it
+pub struct Interpreter {
+    program: Program,
+    scope: ReplScope,
+    session: Session,
+}
+
+impl Interpreter {
+    pub fn new(config: RuntimeConfig) -> Self {
+        let mut program = Program::new();
+        program.path = "<embedded>".into();
+        // Sandboxed code can only import modules from the current directory
+        if config.sandbox {
+            let dir = env::current_dir().unwrap_or_default();
+            program.import_root = Some(ast::import_root(&dir));
+        }
+
+        Self {
+            program,
+            scope: ReplScope::new(),
+            session: Session::new(config),
+        }
+    }
+
+    /// Gets a package of native functions that code run afterwards can
+    /// import by name, creating it if it doesn't exist.  Packages in the
+    /// standard library can't be replaced
+    pub fn package(&mut self, name: &str) -> Result<&mut HostPackage, ReservedName> {
+        self.program.host.package(name)
+    }
+
+    /// Runs source code, returning the value of its last expression
+    /// statement.  Imports are resolved from the current directory
+    pub fn eval(&mut self, source: &str) -> Result<Value, Error> {
+        let dir = env::current_dir().unwrap_or_default();
+        self.load(source, &dir)
+    }
+
+    /// Runs a source file, resolving its imports from the directory it's in
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<Value, Error> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|error| Error::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        self.program.path = path.to_path_buf();
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        self.load(&source, dir)
+    }
+
+    fn load(&mut self, source: &str, dir: &Path) -> Result<Value, Error> {
+        let path = self.program.path.clone();
+        let tokens = lex::lex(source)
+            .map_err(|e| Error::Syntax(Box::new(Diagnostic::from_lex(&e, &path, source))))?;
+
+        match ast::parse_input(tokens, &mut self.program, dir) {
+            Ok(body) => self.program.body = body,
+            Err(e) => {
+                self.scope.discard(&mut self.program);
+                let diagnostic = Diagnostic::from_parse(&e, &path, source);
+                return Err(Error::Syntax(Box::new(diagnostic)));
+            }
+        }
+
+        if let Err(e) = self.scope.check(&mut self.program) {
+            let diagnostic = Diagnostic::from_walk(&e, &self.program, &path, source);
+            return Err(Error::Syntax(Box::new(diagnostic)));
+        }
+
+        self.session.eval(&self.program).map_err(|e| {
+            self.scope.rollback();
+            Error::Runtime(Box::new(e))
+        })
+    }
+
+    /// Calls a procedure declared by the code that has been loaded
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, Error> {
+        let program = &self.program;
+        let index = program
+            .functions
+            .iter()
+            .position(|f| program.strings[f.identifier.data] == name)
+            .ok_or_else(|| Error::UnknownProcedure(name.to_string()))?;
+
+        let function = Callable::Procedure {
+            module: None,
+            index,
+        };
+        Ok(self.session.call(program, &function, args)?)
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new(RuntimeConfig::default())
+    }
+}
@@ -36,6 +36,9 @@ macro_rules! keyword_map {
 
 type LexResult<A> = Result<A, LexError>;
 
+/// A comment as it was written, `@` and all
+pub type Comment = Located<String>;
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum LexErrorData {
     UnexpectedEOF,
@@ -93,9 +96,15 @@ fn next_guard<T: Iterator<Item = char>>(
 }
 
 pub fn lex(s: &str) -> LexResult<Vec<Located<Token>>> {
+    lex_with_comments(s).map(|(tokens, _)| tokens)
+}
+
+/// Lexes the source while keeping its comments, in the order they were written
+pub fn lex_with_comments(s: &str) -> LexResult<(Vec<Located<Token>>, Vec<Comment>)> {
     let mut chars = s.chars().peekable();
     let mut loc = Location::new();
     let mut tokens = vec![];
+    let mut comments = vec![];
 
     while let Some(c) = chars.next() {
         let start = loc.clone();
@@ -235,19 +244,24 @@ pub fn lex(s: &str) -> LexResult<Vec<Located<Token>>> {
                     }
                 }
                 '@' => {
+                    let mut text = c.to_string();
                     if nchar_if(&mut chars, '{', &mut loc).is_some() {
+                        text.push('{');
                         while let Some(c) = chars.next() {
                             loc.feed(c);
+                            text.push(c);
                             if c == '}' && nchar_if(&mut chars, '@', &mut loc).is_some() {
+                                text.push('@');
                                 break;
                             }
                         }
                     } else {
-                        for _ in chars.by_ref().take_while(|&c| {
+                        text.extend(chars.by_ref().take_while(|&c| {
                             loc.feed(c);
                             c != '\n'
-                        }) {}
+                        }));
                     }
+                    comments.push(Located::with_loc(text, start, loc.clone()));
                     continue;
                 }
                 _ if c.is_whitespace() => continue,
@@ -258,9 +272,10 @@ pub fn lex(s: &str) -> LexResult<Vec<Located<Token>>> {
         ));
     }
 
-    Ok(tokens)
+    Ok((tokens, comments))
 }
 
-fn valid_ident_char(c: char) -> bool {
+/// Whether the character can be part of an identifier
+pub fn valid_ident_char(c: char) -> bool {
     c.is_ascii_alphanumeric() || c == '_'
 }
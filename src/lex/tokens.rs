@@ -27,6 +27,9 @@ keyword_map!(Keyword {
     Public -> "public",
     From -> "from",
     This -> "this",
+    Try -> "try",
+    Catch -> "catch",
+    Throw -> "throw",
 });
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -77,3 +80,47 @@ impl Token {
         }
     }
 }
+
+impl Grouper {
+    pub fn open(&self) -> char {
+        match self {
+            Grouper::Bracket => '[',
+            Grouper::Parentheses => '(',
+            Grouper::Brace => '{',
+        }
+    }
+
+    pub fn close(&self) -> char {
+        match self {
+            Grouper::Bracket => ']',
+            Grouper::Parentheses => ')',
+            Grouper::Brace => '}',
+        }
+    }
+}
+
+/// Writes the token roughly as it would appear in source code
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::String(s) => write!(f, "{:?}", s),
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Identifier(i) => write!(f, "{}", i),
+            Token::BinaryOp(op) => write!(f, "{}", op.str()),
+            Token::UnaryOp(op) => write!(f, "{}", op.str()),
+            Token::AssignOp(op) => write!(f, "{}", op.str()),
+            Token::Arrow => write!(f, "->"),
+            Token::Period => write!(f, "."),
+            Token::Spread => write!(f, "..."),
+            Token::MutableHash => write!(f, "$"),
+            Token::Hash => write!(f, "#"),
+            Token::Keyword(k) => write!(f, "{}", k.str()),
+            Token::Bool(b) => write!(f, "{}", b),
+            Token::Comma => write!(f, ","),
+            Token::Semicolon => write!(f, ";"),
+            Token::Pipe => write!(f, "|"),
+            Token::OpenGroup(g) => write!(f, "{}", g.open()),
+            Token::CloseGroup(g) => write!(f, "{}", g.close()),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+//!
+//! The Grib interpreter.  Programs can be embedded in a Rust host
+//! through `Interpreter`, while the modules below expose each stage
+//! of the pipeline for tools that need more control
+//!
+#[macro_use]
+extern crate serde_derive;
+extern crate rand;
+extern crate serde;
+extern crate serde_json;
+
+pub mod ast;
+pub mod diagnostic;
+mod interpreter;
+pub mod lex;
+pub mod location;
+mod operators;
+pub mod runtime;
+pub mod util;
+
+pub use interpreter::{Error, Interpreter};
+pub use runtime::values::Value;
+pub use runtime::{GcMode, RuntimeConfig};
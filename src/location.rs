@@ -1,7 +1,8 @@
 use serde::{de::DeserializeOwned, Serialize};
 use std::fmt::Debug;
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Default)]
+/// Locations are ordered by row, then column
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Default)]
 pub struct Location {
     row: usize,
     column: usize,
@@ -12,6 +13,19 @@ impl Location {
         Self { row: 1, column: 0 }
     }
 
+    pub fn at(row: usize, column: usize) -> Self {
+        Self { row, column }
+    }
+
+    /// Location directly after the last character of the given text
+    pub fn end_of(text: &str) -> Self {
+        let mut loc = Self::new();
+        for c in text.chars() {
+            loc.feed(c);
+        }
+        loc
+    }
+
     pub fn feed(&mut self, c: char) {
         if c == '\n' {
             self.row += 1;
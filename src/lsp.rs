@@ -0,0 +1,567 @@
+///
+/// Language Server Protocol server, which gives editors diagnostics,
+/// definitions, hovers, and completions for Grib code
+///
+use ast::{
+    self,
+    node::{ImportKind, Module, NativePackage, Parameters, Procedure, Program},
+};
+use diagnostic::{Diagnostic, Severity};
+use framing;
+use lex::{self, valid_ident_char};
+use location::Location;
+use runtime::native_fn::NativeSignature;
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Kinds of completion items defined by the protocol
+const COMPLETION_FUNCTION: i64 = 3;
+const COMPLETION_MODULE: i64 = 9;
+
+/// An error code and message
+type RpcError = (i64, String);
+type RpcResult = Result<Json, RpcError>;
+
+/// An open file and what was learned the last time it was analyzed
+struct Document {
+    path: PathBuf,
+    text: String,
+    /// The last version of the file that parsed.  It's kept while the
+    /// file has errors so definitions and completions keep working
+    program: Option<Program>,
+}
+
+/// Lexes, parses, and analyzes a file.  The program is returned as long as
+/// it parsed, even if analysis found an error in it
+fn analyze(text: &str, path: &Path) -> (Option<Program>, Option<Diagnostic>) {
+    let tokens = match lex::lex(text) {
+        Ok(tokens) => tokens,
+        Err(e) => return (None, Some(Diagnostic::from_lex(&e, path, text))),
+    };
+    let mut program = match ast::ast(tokens, path) {
+        Ok(program) => program,
+        Err(e) => return (None, Some(Diagnostic::from_parse(&e, path, text))),
+    };
+
+    let error = ast::ref_check(&mut program)
+        .err()
+        .map(|e| Diagnostic::from_walk(&e, &program, path, text));
+    (Some(program), error)
+}
+
+fn position(row: usize, col: usize) -> Json {
+    json!({ "line": row.saturating_sub(1), "character": col })
+}
+
+fn range(start: &Location, end: &Location) -> Json {
+    json!({
+        "start": position(start.get_row(), start.get_col()),
+        "end": position(end.get_row(), end.get_col()),
+    })
+}
+
+/// Errors in imported modules are shown at the top of the file that imports them
+fn to_lsp(diagnostic: &Diagnostic, path: &Path) -> Json {
+    let mut message = diagnostic.message.clone();
+    let (start, end) = match (&diagnostic.snippet, &diagnostic.location) {
+        _ if diagnostic.path != path => {
+            message = format!("{} ({})", message, diagnostic.path.display());
+            ((1, 0), (1, 0))
+        }
+        (Some(snippet), _) => (
+            (snippet.row, snippet.start_col),
+            (snippet.row, snippet.end_col),
+        ),
+        (None, Some(loc)) => (
+            (loc.get_row(), loc.get_col()),
+            (loc.get_row(), loc.get_col() + 1),
+        ),
+        (None, None) => ((1, 0), (1, 0)),
+    };
+    for note in &diagnostic.notes {
+        message.push('\n');
+        message.push_str(note);
+    }
+
+    json!({
+        "range": { "start": position(start.0, start.1), "end": position(end.0, end.1) },
+        "severity": match diagnostic.severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+        },
+        "source": "gribc",
+        "message": message,
+    })
+}
+
+/// Only `file` URIs name files on disk.  Other documents are analyzed
+/// as if they were in the current directory
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = match uri.strip_prefix("file://") {
+        Some(path) => path,
+        None => return PathBuf::from(uri),
+    };
+
+    let mut bytes = Vec::new();
+    let mut chars = path.bytes();
+    while let Some(byte) = chars.next() {
+        let escaped = match byte {
+            b'%' => {
+                let hex = [chars.next().unwrap_or(b'0'), chars.next().unwrap_or(b'0')];
+                std::str::from_utf8(&hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            }
+            _ => None,
+        };
+        bytes.push(escaped.unwrap_or(byte));
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// An identifier, along with the one before it if they're
+/// joined by a dot, like `console.println`
+struct Word {
+    qualifier: Option<String>,
+    name: String,
+}
+
+/// Finds the identifier at a position.  With `prefix` set, only the
+/// part of the identifier before the position is returned
+fn word_at(text: &str, pos: &Json, prefix: bool) -> Option<Word> {
+    let line = pos["line"].as_u64()? as usize;
+    let line = text.lines().nth(line).unwrap_or_default();
+    let chars = line.chars().collect::<Vec<_>>();
+    let at = (pos["character"].as_u64()? as usize).min(chars.len());
+
+    let ident_start = |end: usize| {
+        (0..end)
+            .rev()
+            .take_while(|&i| valid_ident_char(chars[i]))
+            .last()
+            .unwrap_or(end)
+    };
+    let start = ident_start(at);
+    let end = match prefix {
+        true => at,
+        false => (at..chars.len())
+            .find(|&i| !valid_ident_char(chars[i]))
+            .unwrap_or(chars.len()),
+    };
+
+    let qualifier = match start.checked_sub(1) {
+        Some(dot) if chars[dot] == '.' => {
+            let qualifier = chars[ident_start(dot)..dot].iter().collect::<String>();
+            Some(qualifier).filter(|q| !q.is_empty())
+        }
+        _ => None,
+    };
+    Some(Word {
+        qualifier,
+        name: chars[start..end].iter().collect(),
+    })
+}
+
+/// Something a name in a program refers to
+enum Symbol<'a> {
+    Native(&'static str, &'static NativeSignature),
+    /// A procedure and the index of the module it's in
+    Procedure(Option<usize>, &'a Procedure),
+    /// A module imported as an object
+    Module(&'a Module),
+}
+
+fn name(program: &Program, index: usize) -> &str {
+    program.strings.get(index).map_or("", String::as_str)
+}
+
+fn signature(package: &NativePackage, name: &str) -> Option<&'static NativeSignature> {
+    package
+        .manifest()
+        .functions
+        .iter()
+        .find(|f| f.name == name || f.aliases.contains(&name))
+}
+
+/// Finds a function that a module exports
+fn member<'a>(program: &'a Program, module: &'a Module, member: &str) -> Option<Symbol<'a>> {
+    match module {
+        Module::Native(package) => {
+            signature(package, member).map(|sig| Symbol::Native(package.name(), sig))
+        }
+        Module::Custom(index) => program.modules[*index]
+            .pub_functions()
+            .find(|f| name(program, f.identifier.data) == member)
+            .map(|f| Symbol::Procedure(Some(*index), f)),
+        Module::Host(_) => None,
+    }
+}
+
+/// Finds what a name refers to at the top level of a program.
+/// Variables aren't tracked, only procedures and imports
+fn resolve<'a>(program: &'a Program, qualifier: Option<&str>, word: &str) -> Option<Symbol<'a>> {
+    if let Some(qualifier) = qualifier {
+        return program
+            .imports
+            .iter()
+            .find_map(|import| match &import.kind {
+                ImportKind::ModuleObject(id) if name(program, id.data) == qualifier => {
+                    member(program, &import.module, word)
+                }
+                _ => None,
+            });
+    }
+
+    if let Some(procedure) = program
+        .functions
+        .iter()
+        .find(|f| name(program, f.identifier.data) == word)
+    {
+        return Some(Symbol::Procedure(None, procedure));
+    }
+
+    program
+        .imports
+        .iter()
+        .find_map(|import| match &import.kind {
+            ImportKind::ModuleObject(id) if name(program, id.data) == word => {
+                Some(Symbol::Module(&import.module))
+            }
+            ImportKind::List(ids) if ids.iter().any(|id| name(program, id.data) == word) => {
+                member(program, &import.module, word)
+            }
+            ImportKind::All => member(program, &import.module, word),
+            _ => None,
+        })
+}
+
+fn params(program: &Program, params: &Parameters) -> String {
+    let mut list = params
+        .params
+        .iter()
+        .map(|p| name(program, p.name).to_string())
+        .collect::<Vec<_>>();
+    if let Some(vardic) = &params.vardic {
+        list.push(format!("...{}", name(program, vardic.name)));
+    }
+    list.join(" ")
+}
+
+fn native_signature(package: &str, sig: &NativeSignature) -> String {
+    let mut params = sig.params.to_vec().join(", ");
+    if sig.variadic {
+        params = format!("...{}", params);
+    }
+    format!("{}.{}({})", package, sig.name, params)
+}
+
+fn hover(program: &Program, symbol: &Symbol) -> String {
+    let (code, note) = match symbol {
+        Symbol::Native(package, sig) => {
+            let aliases = match sig.aliases {
+                [] => None,
+                aliases => Some(format!("Also imported as `{}`", aliases.join("`, `"))),
+            };
+            (native_signature(package, sig), aliases)
+        }
+        Symbol::Procedure(module, procedure) => {
+            let code = format!(
+                "proc {} |{}|",
+                name(program, procedure.identifier.data),
+                params(program, &procedure.param_list)
+            );
+            let defined =
+                module.map(|m| format!("Defined in {}", program.modules[m].path.display()));
+            (code, defined)
+        }
+        Symbol::Module(Module::Native(package)) => (format!("package {}", package.name()), None),
+        Symbol::Module(Module::Custom(m)) => {
+            let path = program.modules[*m].path.display().to_string();
+            (format!("module \"{}\"", path), None)
+        }
+        Symbol::Module(Module::Host(_)) => ("package".to_string(), None),
+    };
+
+    let mut hover = format!("```grib\n{}\n```", code);
+    if let Some(note) = note {
+        hover.push_str("\n\n");
+        hover.push_str(&note);
+    }
+    hover
+}
+
+fn completion(label: &str, kind: i64, detail: Option<String>) -> Json {
+    let mut item = json!({ "label": label, "kind": kind });
+    if let Some(detail) = detail {
+        item["detail"] = detail.into();
+    }
+    item
+}
+
+/// The functions a module exports
+fn members(program: &Program, module: &Module) -> Vec<Json> {
+    match module {
+        Module::Native(package) => package
+            .raw_names()
+            .iter()
+            .map(|f| {
+                let detail = signature(package, f).map(|sig| native_signature(package.name(), sig));
+                completion(f, COMPLETION_FUNCTION, detail)
+            })
+            .collect(),
+        Module::Custom(index) => program.modules[*index]
+            .pub_functions()
+            .map(|f| {
+                let name = name(program, f.identifier.data);
+                let detail = format!("proc {} |{}|", name, params(program, &f.param_list));
+                completion(name, COMPLETION_FUNCTION, Some(detail))
+            })
+            .collect(),
+        Module::Host(_) => Vec::new(),
+    }
+}
+
+/// Names that can be used at the top level of a program, or the members of
+/// a module object if the name being completed follows one
+fn completions(program: &Program, qualifier: Option<&str>) -> Vec<Json> {
+    if let Some(qualifier) = qualifier {
+        return program
+            .imports
+            .iter()
+            .filter(|import| match &import.kind {
+                ImportKind::ModuleObject(id) => name(program, id.data) == qualifier,
+                _ => false,
+            })
+            .flat_map(|import| members(program, &import.module))
+            .collect();
+    }
+
+    let mut items = Vec::new();
+    for import in &program.imports {
+        match &import.kind {
+            ImportKind::ModuleObject(id) => {
+                items.push(completion(name(program, id.data), COMPLETION_MODULE, None))
+            }
+            ImportKind::List(ids) => items.extend(
+                members(program, &import.module)
+                    .into_iter()
+                    .filter(|item| ids.iter().any(|id| item["label"] == name(program, id.data))),
+            ),
+            ImportKind::All => items.extend(members(program, &import.module)),
+        }
+    }
+    for procedure in &program.functions {
+        let name = name(program, procedure.identifier.data);
+        let detail = format!("proc {} |{}|", name, params(program, &procedure.param_list));
+        items.push(completion(name, COMPLETION_FUNCTION, Some(detail)));
+    }
+    items
+}
+
+struct Server {
+    documents: HashMap<String, Document>,
+    output: Box<dyn Write>,
+    shutdown: bool,
+}
+
+impl Server {
+    fn send(&mut self, message: Json) {
+        // There's no one left to tell if the client has gone away
+        let _ = framing::write(&mut self.output, &message);
+    }
+
+    fn respond(&mut self, id: Json, result: RpcResult) {
+        let mut response = json!({ "jsonrpc": "2.0", "id": id });
+        match result {
+            Ok(result) => response["result"] = result,
+            Err((code, message)) => response["error"] = json!({ "code": code, "message": message }),
+        }
+        self.send(response);
+    }
+
+    fn notify(&mut self, method: &str, params: Json) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    /// Analyzes a document and publishes what's wrong with it
+    fn update(&mut self, uri: &str, text: String) {
+        let path = uri_to_path(uri);
+        let (program, diagnostic) = analyze(&text, &path);
+        let diagnostics = diagnostic
+            .iter()
+            .map(|d| to_lsp(d, &path))
+            .collect::<Vec<_>>();
+
+        let document = self
+            .documents
+            .entry(uri.to_string())
+            .or_insert_with(|| Document {
+                path: path.clone(),
+                text: String::new(),
+                program: None,
+            });
+        document.text = text;
+        if program.is_some() {
+            document.program = program;
+        }
+
+        self.notify(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics }),
+        );
+    }
+
+    /// Finds the open document a request is about
+    fn document(&self, params: &Json) -> Result<&Document, RpcError> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        self.documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("{} isn't open", uri)))
+    }
+
+    /// The program in a document, and the name at the position a request is about
+    fn lookup(&self, params: &Json, prefix: bool) -> Result<Option<(&Program, Word)>, RpcError> {
+        let document = self.document(params)?;
+        let word = word_at(&document.text, &params["position"], prefix);
+        Ok(match (&document.program, word) {
+            (Some(program), Some(word)) => Some((program, word)),
+            _ => None,
+        })
+    }
+
+    fn handle(&mut self, method: &str, params: &Json) -> RpcResult {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": { "triggerCharacters": ["."] },
+                },
+                "serverInfo": { "name": "gribc", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/didOpen" => {
+                let document = &params["textDocument"];
+                let uri = document["uri"].as_str().unwrap_or_default();
+                let text = document["text"].as_str().unwrap_or_default();
+                self.update(uri, text.to_string());
+                Ok(Json::Null)
+            }
+            "textDocument/didChange" => {
+                // Documents are synced in full, so the last change has all of the text
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|c| c.last()?["text"].as_str()) {
+                    self.update(uri, text.to_string());
+                }
+                Ok(Json::Null)
+            }
+            "textDocument/didSave" => {
+                // The modules the document imports may have changed on disk
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                if let Some(text) = self.documents.get(uri).map(|d| d.text.clone()) {
+                    self.update(uri, text);
+                }
+                Ok(Json::Null)
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                self.documents.remove(uri);
+                let params = json!({ "uri": uri, "diagnostics": [] });
+                self.notify("textDocument/publishDiagnostics", params);
+                Ok(Json::Null)
+            }
+            "textDocument/hover" => {
+                let hover = self.lookup(params, false)?.and_then(|(program, word)| {
+                    let symbol = resolve(program, word.qualifier.as_deref(), &word.name)?;
+                    Some(hover(program, &symbol))
+                });
+                Ok(match hover {
+                    Some(hover) => json!({ "contents": { "kind": "markdown", "value": hover } }),
+                    None => Json::Null,
+                })
+            }
+            "textDocument/definition" => {
+                let path = self.document(params)?.path.clone();
+                let found =
+                    self.lookup(params, false)?.and_then(|(program, word)| {
+                        match resolve(program, word.qualifier.as_deref(), &word.name)? {
+                            Symbol::Procedure(module, procedure) => {
+                                let path = module.map_or(path, |m| program.modules[m].path.clone());
+                                let id = &procedure.identifier;
+                                Some((path, range(&id.start, &id.end)))
+                            }
+                            Symbol::Native(..) | Symbol::Module(_) => None,
+                        }
+                    });
+                Ok(match found {
+                    Some((path, range)) => json!({ "uri": path_to_uri(&path), "range": range }),
+                    None => Json::Null,
+                })
+            }
+            "textDocument/completion" => {
+                let items = self
+                    .lookup(params, true)?
+                    .map(|(program, word)| completions(program, word.qualifier.as_deref()))
+                    .unwrap_or_default();
+                Ok(json!(items))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unsupported method `{}`", method))),
+        }
+    }
+}
+
+/// Serves the client on stdin and stdout
+pub fn run() -> i32 {
+    serve(Box::new(io::stdin().lock()), Box::new(io::stdout()))
+}
+
+/// Answers requests until the client exits.  The exit code tells
+/// whether the client shut the server down before exiting
+pub fn serve(mut input: Box<dyn BufRead>, output: Box<dyn Write>) -> i32 {
+    let mut server = Server {
+        documents: HashMap::new(),
+        output,
+        shutdown: false,
+    };
+
+    while let Some(message) = framing::read(&mut input) {
+        let method = message["method"].as_str().unwrap_or_default();
+        if method == "exit" {
+            break;
+        }
+
+        let result = server.handle(method, &message["params"]);
+        // Notifications have no id and aren't answered, even if they aren't supported
+        if let (Some(id), false) = (message.get("id"), method.is_empty()) {
+            server.respond(id.clone(), result);
+        }
+    }
+
+    match server.shutdown {
+        true => 0,
+        false => 1,
+    }
+}
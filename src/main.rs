@@ -1,29 +1,29 @@
-#[macro_use]
-extern crate serde_derive;
-extern crate rand;
+extern crate gribc;
 extern crate serde;
+#[macro_use]
 extern crate serde_json;
 
-mod ast;
-mod lex;
-mod location;
-mod operators;
-mod runtime;
-mod util;
+mod cli;
+mod dap;
+mod debugger;
+mod framing;
+mod lsp;
+mod repl;
+mod test_runner;
 
+use cli::{Cli, Command, Input, Options};
+use diagnostic::Diagnostic;
+use gribc::{ast, diagnostic, lex, location, runtime};
+use runtime::debug::Resume;
+use runtime::error::{RuntimeError, RuntimeErrorKind};
 use std::env;
 use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process;
+use std::thread;
 
 macro_rules! err_guard {
-    ($next:expr) => {
-        match $next {
-            Ok(v) => v,
-            Err(e) => {
-                println!("{:?}", e);
-                panic!("{:?}", e);
-            }
-        }
-    };
     ($next:expr, $e:ident => $b:expr) => {
         match $next {
             Ok(v) => v,
@@ -33,28 +33,244 @@ macro_rules! err_guard {
 }
 
 fn main() {
-    std::env::set_var("RUST_BACKTRACE", "1");
-    let path = env::args()
-        .last()
-        .expect("Error: File not found in cli arguments");
+    let options = match cli::parse_args(env::args().skip(1)) {
+        Ok(Cli::Exec(options)) => *options,
+        Ok(Cli::Manifest) => {
+            let manifest = runtime::native_fn::std_manifest();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&manifest)
+                    .expect("The manifest is always serializable")
+            );
+            return;
+        }
+        Ok(Cli::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Ok(Cli::Version) => {
+            println!("gribc {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\nrun `gribc --help` for usage", e);
+            process::exit(cli::EXIT_USAGE);
+        }
+    };
+
+    // Native functions that call back into Grib recurse on the native
+    // stack, so the interpreter runs on a thread with a configurable stack size
+    let interpreter = thread::Builder::new()
+        .stack_size(options.stack_size << 20)
+        .spawn(move || exec(options));
+    let code = err_guard!(interpreter, e => {
+        eprintln!("error: could not start interpreter: {}", e);
+        process::exit(cli::EXIT_RUNTIME);
+    })
+    .join()
+    .unwrap_or(cli::EXIT_RUNTIME);
+
+    process::exit(code);
+}
+
+fn exec(options: Options) -> i32 {
+    if options.command == Command::Repl {
+        return repl::run(options.runtime_config(true));
+    }
+    if options.command == Command::Lsp {
+        return lsp::run();
+    }
+    if let (Command::Test, Input::File(path)) = (options.command, &options.input) {
+        return test_runner::run(Path::new(path), &options);
+    }
+    if options.command == Command::Dap {
+        // The program's arguments come from the client when it's launched
+        return dap::run(options.runtime_config(false));
+    }
+
+    let (path, source) = match &options.input {
+        Input::File(path) => (path.as_str(), fs::read_to_string(path)),
+        Input::Stdin => {
+            let mut source = String::new();
+            let read = io::stdin().read_to_string(&mut source).map(|_| source);
+            ("<stdin>", read)
+        }
+    };
+    let source = err_guard!(source, e => {
+        eprintln!("error: could not read {}: {}", path, e);
+        return cli::EXIT_NO_INPUT;
+    });
+    let file = Path::new(path);
+
+    if ast::is_compiled(&source) {
+        let tree = err_guard!(ast::read_compiled(&source), e => {
+            eprintln!("error: could not load {}: {}", path, e);
+            return cli::EXIT_COMPILE;
+        });
+        return match options.command {
+            Command::Tokens | Command::Compile | Command::Fmt | Command::Lint => {
+                eprintln!("error: {} is already compiled", path);
+                cli::EXIT_USAGE
+            }
+            _ => finish(&tree, options),
+        };
+    }
+
+    if options.command == Command::Fmt {
+        return fmt(&source, file, &options);
+    }
+
+    let tokens = err_guard!(lex::lex(source.as_str()),
+        e => return fail(Diagnostic::from_lex(&e, file, &source)));
+
+    if options.command == Command::Tokens {
+        for token in &tokens {
+            println!(
+                "{}:{}\t{}",
+                token.start.get_row(),
+                token.start.get_col() + 1,
+                token.data
+            );
+        }
+        return 0;
+    }
+
+    let parsed = match options.sandbox {
+        true => ast::ast_sandboxed(tokens, file),
+        false => ast::ast(tokens, file),
+    };
+    let mut tree = err_guard!(parsed,
+        e => return fail(Diagnostic::from_parse(&e, file, &source)));
+
+    if options.command == Command::DumpAst && !options.analyzed {
+        return dump(&tree);
+    }
+
+    if options.command == Command::Lint {
+        return lint(&mut tree, file, &source, &options);
+    }
+
+    err_guard!(ast::ref_check(&mut tree),
+        e => return fail(Diagnostic::from_walk(&e, &tree, file, &source)));
+
+    if options.command == Command::Compile {
+        return compile(&tree, &options);
+    }
+    finish(&tree, options)
+}
+
+/// Carries out the command on an analyzed program
+fn finish(tree: &ast::node::Program, options: Options) -> i32 {
+    match options.command {
+        Command::DumpAst => dump(tree),
+        Command::Check
+        | Command::Lint
+        | Command::Test
+        | Command::Tokens
+        | Command::Repl
+        | Command::Dap
+        | Command::Lsp
+        | Command::Fmt
+        | Command::Compile => 0,
+        Command::Run | Command::Debug => {
+            let path = tree.path.clone();
+            let debug = options.command == Command::Debug;
+            let config = options.runtime_config(true);
+            let result = match debug {
+                true => {
+                    let cli = debugger::Cli::new(&path);
+                    println!("paused before the first statement, run `help` for commands");
+                    let breakpoints = runtime::debug::Breakpoints::new();
+                    runtime::debug(tree, config, Box::new(cli), breakpoints, Resume::StepIn)
+                }
+                false => runtime::execute(tree, config),
+            };
+            match result {
+                Ok(()) => 0,
+                Err(RuntimeError {
+                    kind: RuntimeErrorKind::Exit(status),
+                    ..
+                }) => status,
+                Err(e) => {
+                    eprint!("{}", e);
+                    cli::EXIT_RUNTIME
+                }
+            }
+        }
+    }
+}
 
-    let source = err_guard!(fs::read_to_string(&path),
-        _e => panic!("Error: could not load file {}", path));
+fn compile(tree: &ast::node::Program, options: &Options) -> i32 {
+    let compiled = err_guard!(ast::write_compiled(tree), e => {
+        eprintln!("error: {}", e);
+        return cli::EXIT_COMPILE;
+    });
+    let output = match (&options.output, &options.input) {
+        (Some(path), _) if path != "-" => Path::new(path).to_path_buf(),
+        (None, Input::File(path)) => Path::new(path).with_extension("gribc"),
+        _ => {
+            print!("{}", compiled);
+            return 0;
+        }
+    };
+
+    err_guard!(fs::write(&output, compiled), e => {
+        eprintln!("error: could not write {}: {}", output.display(), e);
+        return cli::EXIT_CANT_CREATE;
+    });
+    0
+}
+
+/// Prints the program in the canonical style, or checks or rewrites its file
+fn fmt(source: &str, file: &Path, options: &Options) -> i32 {
+    let (tokens, comments) = err_guard!(lex::lex_with_comments(source),
+        e => return fail(Diagnostic::from_lex(&e, file, source)));
+    let tree = err_guard!(ast::ast(tokens.iter().cloned(), file),
+        e => return fail(Diagnostic::from_parse(&e, file, source)));
+    let formatted = ast::format::format(&tree, &tokens, comments, source);
+
+    if options.check {
+        if formatted == source {
+            return 0;
+        }
+        eprintln!("{} is not formatted", file.display());
+        return cli::EXIT_RUNTIME;
+    }
+    if !options.write {
+        print!("{}", formatted);
+        return 0;
+    }
+
+    err_guard!(fs::write(file, formatted), e => {
+        eprintln!("error: could not write {}: {}", file.display(), e);
+        return cli::EXIT_CANT_CREATE;
+    });
+    0
+}
 
-    let tokens = err_guard!(lex::lex(source.as_str()));
-    let mut tree = err_guard!(ast::ast(tokens.into_iter(), &path));
+/// Prints the warnings the enabled lints find.  Warnings don't fail the command
+fn lint(tree: &mut ast::node::Program, file: &Path, source: &str, options: &Options) -> i32 {
+    let lints = err_guard!(ast::lint(tree, &options.lints),
+        e => return fail(Diagnostic::from_walk(&e, tree, file, source)));
 
-    err_guard!(ast::ref_check(&mut tree));
-    //println!("{}", serde_json::to_string_pretty(&tree).unwrap());
+    for lint in &lints {
+        eprint!("{}", Diagnostic::from_lint(lint, tree, file, source));
+    }
+    0
+}
 
-    runtime::execute(
-        &tree,
-        runtime::RuntimeConfig {
-            cleanup_after: 1000,
-        },
+fn dump(tree: &ast::node::Program) -> i32 {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(tree).expect("Syntax trees are always serializable")
     );
+    0
+}
+
+fn fail(diagnostic: Diagnostic) -> i32 {
+    eprint!("{}", diagnostic);
+    cli::EXIT_COMPILE
 }
 
 #[cfg(test)]
 mod tests;
-//cargo rustc -- -C link-args=-Wl,-zstack-size=144016
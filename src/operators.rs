@@ -53,7 +53,39 @@ pub enum Binary {
     LogicalOr,
 }
 
+impl Assignment {
+    pub fn str(&self) -> &'static str {
+        match self {
+            Assignment::Assign => "=",
+            Assignment::AssignPlus => "+=",
+            Assignment::AssignMinus => "-=",
+            Assignment::AssignMult => "*=",
+            Assignment::AssignDiv => "/=",
+            Assignment::AssignMod => "%=",
+        }
+    }
+}
+
 impl Binary {
+    pub fn str(&self) -> &'static str {
+        use self::Binary::*;
+        match self {
+            Plus => "+",
+            Minus => "-",
+            Mult => "*",
+            Div => "/",
+            Mod => "%",
+            GreaterThan => ">",
+            LessThan => "<",
+            GreaterEq => ">=",
+            LessEq => "<=",
+            Equal => "==",
+            NotEqual => "!=",
+            LogicalAnd => "&&",
+            LogicalOr => "||",
+        }
+    }
+
     pub fn is_lazy(&self) -> bool {
         match self {
             Binary::LogicalAnd | Binary::LogicalOr => true,
@@ -68,6 +100,15 @@ pub enum Unary {
     LogicalNegation,
 }
 
+impl Unary {
+    pub fn str(&self) -> &'static str {
+        match self {
+            Unary::Negation => "~",
+            Unary::LogicalNegation => "!",
+        }
+    }
+}
+
 pub fn op_precedence(op: &Binary) -> Precedence {
     use self::Binary::*;
     match op {
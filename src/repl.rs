@@ -0,0 +1,171 @@
+///
+/// Interactive read-eval-print loop
+///
+use ast::{self, node::Program, ReplScope};
+use diagnostic::Diagnostic;
+use lex::{self, tokens::Token, LexErrorData};
+use location::Located;
+use runtime::error::{RuntimeError, RuntimeErrorKind};
+use runtime::{RuntimeConfig, Session};
+use std::env;
+use std::io::{self, BufRead, Write};
+
+const PROMPT: &str = "> ";
+const CONTINUE_PROMPT: &str = ". ";
+
+/// Whether the tokens have more opening groupers than closing ones
+fn unclosed(tokens: &[Located<Token>]) -> bool {
+    let mut depth = 0i64;
+    for token in tokens {
+        match token.data {
+            Token::OpenGroup(_) => depth += 1,
+            Token::CloseGroup(_) => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+pub struct Repl {
+    program: Program,
+    scope: ReplScope,
+    session: Session,
+}
+
+/// The result of feeding a line of input to the REPL
+pub enum Step {
+    /// The input so far is unfinished
+    Incomplete,
+    Done,
+    /// The program called `exit` with this status
+    Exit(i32),
+}
+
+impl Repl {
+    pub fn new(config: RuntimeConfig) -> Self {
+        let mut program = Program::new();
+        program.path = "<repl>".into();
+        if config.sandbox {
+            let dir = env::current_dir().unwrap_or_default();
+            program.import_root = Some(ast::import_root(&dir));
+        }
+
+        Self {
+            program,
+            scope: ReplScope::new(),
+            session: Session::new(config),
+        }
+    }
+
+    /// Evaluates the source, printing its result or any errors.
+    /// Unless `force` is set, nothing is evaluated if the source looks
+    /// like it continues onto the next line
+    pub fn eval(&mut self, source: &str, force: bool) -> Step {
+        let path = self.program.path.clone();
+
+        let mut tokens = match lex::lex(source) {
+            Ok(tokens) => tokens,
+            Err(ref e) if e.data == LexErrorData::UnexpectedEOF && !force => {
+                return Step::Incomplete
+            }
+            Err(e) => {
+                eprint!("{}", Diagnostic::from_lex(&e, &path, source));
+                return Step::Done;
+            }
+        };
+
+        if tokens.is_empty() {
+            return Step::Done;
+        }
+        if unclosed(&tokens) && !force {
+            return Step::Incomplete;
+        }
+
+        // The semicolon after the last statement is optional
+        if let Some(last) = tokens.last().filter(|t| t.data != Token::Semicolon) {
+            let end = last.end.clone();
+            tokens.push(Located::with_loc(Token::Semicolon, end.clone(), end));
+        }
+
+        let dir = env::current_dir().unwrap_or_default();
+        match ast::parse_input(tokens, &mut self.program, &dir) {
+            Ok(body) => self.program.body = body,
+            Err(e) => {
+                self.scope.discard(&mut self.program);
+                eprint!("{}", Diagnostic::from_parse(&e, &path, source));
+                return Step::Done;
+            }
+        }
+
+        if let Err(e) = self.scope.check(&mut self.program) {
+            eprint!(
+                "{}",
+                Diagnostic::from_walk(&e, &self.program, &path, source)
+            );
+            return Step::Done;
+        }
+
+        match self.session.run(&self.program) {
+            Ok(Some(result)) => println!("{}", result),
+            Ok(None) => {}
+            Err(RuntimeError {
+                kind: RuntimeErrorKind::Exit(status),
+                ..
+            }) => return Step::Exit(status),
+            Err(e) => {
+                self.scope.rollback();
+                eprint!("{}", e);
+            }
+        }
+
+        Step::Done
+    }
+}
+
+/// Reads lines from stdin until it is closed or the program exits, returning
+/// its exit code.  A blank line evaluates unfinished input as is
+pub fn run(config: RuntimeConfig) -> i32 {
+    let mut repl = Repl::new(config);
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut source = String::new();
+
+    println!(
+        "gribc {} REPL. Press Ctrl-D to exit.",
+        env!("CARGO_PKG_VERSION")
+    );
+
+    loop {
+        print!(
+            "{}",
+            if source.is_empty() {
+                PROMPT
+            } else {
+                CONTINUE_PROMPT
+            }
+        );
+        let _ = io::stdout().flush();
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+
+        let force = line.trim().is_empty();
+        source.push_str(&line);
+        source.push('\n');
+
+        match repl.eval(&source, force) {
+            Step::Incomplete => {}
+            Step::Done => source.clear(),
+            Step::Exit(status) => {
+                repl.session.close();
+                return status;
+            }
+        }
+    }
+
+    println!();
+    repl.session.close();
+    0
+}
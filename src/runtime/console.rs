@@ -0,0 +1,76 @@
+///
+/// Where the console package sends what a program prints
+///
+use runtime::debug::Output;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+/// A writer shared between the runtime and whoever reads the output
+pub type Sink = Arc<Mutex<dyn Write + Send>>;
+
+#[derive(Clone, Default)]
+pub enum Console {
+    /// The process's stdout and stderr
+    #[default]
+    Std,
+    /// One writer that receives both streams in the order they're written
+    Redirect(Sink),
+}
+
+impl Console {
+    /// Errors writing the output are ignored, like print! would
+    pub fn write(&self, text: &str, output: Output) {
+        let _ = match (self, output) {
+            (Console::Std, Output::Stdout) => io::stdout().write_all(text.as_bytes()),
+            (Console::Std, Output::Stderr) => io::stderr().write_all(text.as_bytes()),
+            (Console::Redirect(sink), _) => match sink.lock() {
+                Ok(mut sink) => sink.write_all(text.as_bytes()),
+                Err(_) => Ok(()),
+            },
+        };
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        match self {
+            Console::Std => io::stdout().flush(),
+            Console::Redirect(sink) => match sink.lock() {
+                Ok(mut sink) => sink.flush(),
+                Err(_) => Ok(()),
+            },
+        }
+    }
+}
+
+/// Keeps everything a program prints so it can be read once the program is done
+#[derive(Clone, Default)]
+pub struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Capture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A console that writes into this capture
+    pub fn console(&self) -> Console {
+        Console::Redirect(Arc::new(Mutex::new(self.clone())))
+    }
+
+    /// What's been printed so far.  Invalid UTF-8 is replaced
+    pub fn text(&self) -> String {
+        let bytes = self.0.lock().map(|b| b.clone()).unwrap_or_default();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Ok(mut bytes) = self.0.lock() {
+            bytes.extend_from_slice(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
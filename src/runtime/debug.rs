@@ -0,0 +1,274 @@
+///
+/// Pausing programs at breakpoints and stepping through them
+///
+use ast::node::Program;
+use location::Location;
+use runtime::error::{fatal, RuntimeErrorKind, StackFrame};
+use runtime::exec::{stack_trace, Chunk, Statement};
+use runtime::memory::Runtime;
+use runtime::values::{GribValue, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How a paused program continues
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resume {
+    /// Run until the next breakpoint
+    Continue,
+    /// Stop at the next statement, even if it's in a function being called
+    StepIn,
+    /// Stop at the next statement in the current function or the ones calling it
+    StepOver,
+    /// Stop once the current function returns
+    StepOut,
+    /// End the program
+    Stop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason {
+    Breakpoint,
+    Step,
+}
+
+/// Lines that pause the program when it reaches a statement on them
+#[derive(Clone, Debug, Default)]
+pub struct Breakpoints {
+    lines: HashMap<PathBuf, BTreeSet<usize>>,
+}
+
+/// Paths are canonicalized so they match the ones the program was loaded from
+fn normalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl Breakpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, path: impl AsRef<Path>, line: usize) {
+        let path = normalize(path.as_ref());
+        self.lines.entry(path).or_default().insert(line);
+    }
+
+    /// Returns whether there was a breakpoint to remove
+    pub fn remove(&mut self, path: impl AsRef<Path>, line: usize) -> bool {
+        let path = normalize(path.as_ref());
+        self.lines
+            .get_mut(&path)
+            .is_some_and(|lines| lines.remove(&line))
+    }
+
+    /// Removes every breakpoint in a file
+    pub fn clear(&mut self, path: impl AsRef<Path>) {
+        self.lines.remove(&normalize(path.as_ref()));
+    }
+
+    pub fn contains(&self, path: &Path, line: usize) -> bool {
+        self.lines
+            .get(path)
+            .is_some_and(|lines| lines.contains(&line))
+    }
+
+    /// Every breakpoint, ordered by line within each file
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, usize)> {
+        self.lines
+            .iter()
+            .flat_map(|(path, lines)| lines.iter().map(move |&line| (path.as_path(), line)))
+    }
+}
+
+/// Where the program printed some text
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    Stdout,
+    Stderr,
+}
+
+/// Decides what to do whenever a program running under it pauses
+pub trait Debugger {
+    fn paused(&mut self, paused: &mut Paused, reason: StopReason) -> Resume;
+
+    /// Receives text the program prints.  Debuggers that talk to their
+    /// client over stdio return true to keep it from being printed
+    fn output(&mut self, _text: &str, _output: Output) -> bool {
+        false
+    }
+}
+
+/// A statement that ran, identified by the chunk it's in
+#[derive(Clone, Copy, PartialEq)]
+struct Position {
+    chunk: *const Chunk,
+    statement: usize,
+    line: usize,
+    depth: usize,
+}
+
+/// The debugger attached to a runtime
+pub(in runtime) struct DebugState {
+    debugger: Box<dyn Debugger>,
+    breakpoints: Breakpoints,
+    resume: Resume,
+    /// The call depth when the program last paused
+    depth: usize,
+    last: Option<Position>,
+}
+
+impl DebugState {
+    pub fn new(debugger: Box<dyn Debugger>, breakpoints: Breakpoints, start: Resume) -> Self {
+        Self {
+            debugger,
+            breakpoints,
+            resume: start,
+            depth: 0,
+            last: None,
+        }
+    }
+
+    fn stop_reason(&self, path: &Path, at: Position) -> Option<StopReason> {
+        // Statements after the first on a line don't stop at its breakpoint again
+        let continues_line = self.last.is_some_and(|last| {
+            last.chunk == at.chunk
+                && last.depth == at.depth
+                && last.line == at.line
+                && last.statement < at.statement
+        });
+
+        if self.breakpoints.contains(path, at.line) && !continues_line {
+            return Some(StopReason::Breakpoint);
+        }
+
+        let step = match self.resume {
+            Resume::StepIn => true,
+            Resume::StepOver => at.depth <= self.depth,
+            Resume::StepOut => at.depth < self.depth,
+            Resume::Continue | Resume::Stop => false,
+        };
+        Some(StopReason::Step).filter(|_| step)
+    }
+}
+
+/// A program that's stopped before running a statement
+pub struct Paused<'a> {
+    runtime: &'a mut Runtime,
+    program: &'a Program,
+    statement: &'a Statement,
+    path: &'a Path,
+    this: &'a GribValue,
+    /// Breakpoints can be changed while the program is paused
+    pub breakpoints: &'a mut Breakpoints,
+}
+
+impl<'a> Paused<'a> {
+    pub fn location(&self) -> &Location {
+        &self.statement.location
+    }
+
+    /// The file the statement is in
+    pub fn path(&self) -> &Path {
+        self.path
+    }
+
+    /// The number of functions that are being called
+    pub fn depth(&self) -> usize {
+        self.runtime.frames.len()
+    }
+
+    /// The functions being called, innermost first
+    pub fn trace(&self) -> Vec<StackFrame> {
+        stack_trace(&self.runtime.frames, Some(self.location()), self.program)
+    }
+
+    /// The variables of the current function that are in scope, in the order
+    /// they were declared.  Captured variables and parameters come first
+    pub fn locals(&self) -> Vec<(String, GribValue)> {
+        let names = &self.statement.locals;
+        names
+            .iter()
+            .enumerate()
+            .map(|(index, &name)| {
+                let value = self.runtime.get_offset(names.len() - index);
+                let name = self.program.strings[name].clone();
+                (name, value.cloned().unwrap_or_default())
+            })
+            .collect()
+    }
+
+    /// Finds a variable that's in scope by name
+    pub fn local(&self, name: &str) -> Option<GribValue> {
+        self.locals()
+            .into_iter()
+            .rev()
+            .find(|(local, _)| local == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn this(&self) -> &GribValue {
+        self.this
+    }
+
+    /// Copies a value out of the runtime.  The getters of hashes are called
+    pub fn copy(&mut self, value: &GribValue) -> Value {
+        self.runtime.operands.push(value.clone());
+        let copied = Value::from_grib(value, self.runtime, self.program);
+        self.runtime.operands.pop();
+        copied
+    }
+}
+
+/// Gives the debugger a chance to pause before a statement runs
+pub(in runtime) fn statement(
+    runtime: &mut Runtime,
+    program: &Program,
+    chunk: &Chunk,
+    index: usize,
+    this: &GribValue,
+) {
+    let mut state = match runtime.debug.take() {
+        Some(state) => state,
+        None => return,
+    };
+
+    let statement = &chunk.statements[index];
+    let at = Position {
+        chunk,
+        statement: index,
+        line: statement.location.get_row(),
+        depth: runtime.frames.len(),
+    };
+    let reason = state.stop_reason(&chunk.path, at);
+    state.last = Some(at);
+
+    if let Some(reason) = reason {
+        let mut paused = Paused {
+            runtime,
+            program,
+            statement,
+            path: &chunk.path,
+            this,
+            breakpoints: &mut state.breakpoints,
+        };
+        state.resume = state.debugger.paused(&mut paused, reason);
+        state.depth = at.depth;
+    }
+
+    let stop = state.resume == Resume::Stop;
+    runtime.debug = Some(state);
+    if stop {
+        fatal(RuntimeErrorKind::Stopped);
+    }
+}
+
+/// Prints text for the program, unless the debugger captures it
+pub(in runtime) fn print(runtime: &mut Runtime, text: &str, output: Output) {
+    if let Some(state) = &mut runtime.debug {
+        if state.debugger.output(text, output) {
+            return;
+        }
+    }
+
+    runtime.console.write(text, output);
+}
@@ -0,0 +1,153 @@
+use location::Location;
+use std::fmt::{self, Display, Formatter};
+use std::panic;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Unrecoverable failures that abort the running program
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuntimeErrorKind {
+    StackOverflow,
+    InvalidOffset(usize),
+    /// A value was thrown outside of any try block
+    Uncaught(String),
+    /// A Rust panic that escaped the interpreter
+    Internal(String),
+    LimitExceeded(Limit),
+    /// A native function that isn't allowed in the sandbox was called
+    Sandboxed(String),
+    /// The debugger ended the program
+    Stopped,
+    /// A call to `assert` or `assertEq` failed
+    AssertionFailed(String),
+    /// The program called `exit` with this status
+    Exit(i32),
+}
+
+/// A resource limit that a program ran into, along with its value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Limit {
+    Steps(u64),
+    Time(Duration),
+    HeapSlots(usize),
+    CallDepth(usize),
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Limit::Steps(max) => write!(f, "executed more than {} steps", max),
+            Limit::Time(max) => write!(f, "ran for longer than {}ms", max.as_millis()),
+            Limit::HeapSlots(max) => write!(f, "used more than {} heap slots", max),
+            Limit::CallDepth(max) => write!(f, "nested more than {} calls", max),
+        }
+    }
+}
+
+impl Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            RuntimeErrorKind::StackOverflow => write!(f, "Grib stack overflow"),
+            RuntimeErrorKind::InvalidOffset(offset) => {
+                write!(f, "invalid stack offset {}", offset)
+            }
+            RuntimeErrorKind::Uncaught(value) => write!(f, "uncaught exception: {}", value),
+            RuntimeErrorKind::Internal(msg) => write!(f, "internal error: {}", msg),
+            RuntimeErrorKind::LimitExceeded(limit) => write!(f, "limit exceeded: {}", limit),
+            RuntimeErrorKind::Stopped => write!(f, "stopped by the debugger"),
+            RuntimeErrorKind::AssertionFailed(message) => {
+                write!(f, "assertion failed: {}", message)
+            }
+            RuntimeErrorKind::Sandboxed(name) => {
+                write!(f, "`{}` can't be called in the sandbox", name)
+            }
+            RuntimeErrorKind::Exit(status) => write!(f, "exited with status {}", status),
+        }
+    }
+}
+
+/// One line of a Grib stack trace
+#[derive(Clone, Debug, PartialEq)]
+pub struct StackFrame {
+    pub name: String,
+    /// `None` for native functions
+    pub path: Option<PathBuf>,
+    /// Where execution was inside of this frame
+    pub location: Option<Location>,
+}
+
+impl Display for StackFrame {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "at {}", self.name)?;
+        match (&self.path, &self.location) {
+            (Some(path), Some(loc)) => write!(
+                f,
+                " ({}:{}:{})",
+                path.display(),
+                loc.get_row(),
+                loc.get_col() + 1
+            ),
+            (Some(path), None) => write!(f, " ({})", path.display()),
+            (None, _) => write!(f, " (native)"),
+        }
+    }
+}
+
+/// Consecutive identical frames past this count are collapsed into one line
+const MAX_REPEATED_FRAMES: usize = 3;
+
+/// Prints a trace with the innermost frame first
+pub struct Trace<'a>(pub &'a [StackFrame]);
+
+impl<'a> Display for Trace<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut repeated = 0;
+        for (i, frame) in self.0.iter().enumerate() {
+            if i > 0 && self.0[i - 1] == *frame {
+                repeated += 1;
+            } else {
+                if repeated > MAX_REPEATED_FRAMES {
+                    writeln!(
+                        f,
+                        "    [previous frame repeated {} more times]",
+                        repeated - MAX_REPEATED_FRAMES
+                    )?;
+                }
+                repeated = 1;
+            }
+
+            if repeated <= MAX_REPEATED_FRAMES {
+                writeln!(f, "    {}", frame)?;
+            }
+        }
+
+        if repeated > MAX_REPEATED_FRAMES {
+            writeln!(
+                f,
+                "    [previous frame repeated {} more times]",
+                repeated - MAX_REPEATED_FRAMES
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    pub trace: Vec<StackFrame>,
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "runtime error: {}", self.kind)?;
+        write!(f, "{}", Trace(&self.trace))
+    }
+}
+
+/// Aborts execution. The error is caught by `execute`, which
+/// attaches the Grib stack trace
+pub fn fatal(kind: RuntimeErrorKind) -> ! {
+    panic::resume_unwind(Box::new(kind))
+}
@@ -1,6 +1,5 @@
 /// Structures related to getting and setting index and property values
-use super::evaluate_expression;
-use ast::node::{Assignable, Module, Program};
+use ast::node::{Module, Program};
 use runtime::{
     memory::{Gc, Runtime},
     values::{eval_setter, GribKey, GribString, GribValue, KnownIndex},
@@ -147,30 +146,6 @@ pub enum LiveAssignable {
 }
 
 impl LiveAssignable {
-    pub fn new(
-        assignable: &Assignable,
-        this: &GribValue,
-        runtime: &mut Runtime,
-        program: &Program,
-    ) -> Option<Self> {
-        match assignable {
-            Assignable::Identifier(_) => {
-                panic!("Identifier should not be present at this point in exeuction")
-            }
-            Assignable::Offset(off) => Self::Offset(*off).into(),
-            Assignable::IndexAccess { item, index } => {
-                let item_val = evaluate_expression(item, this, runtime, program);
-                let index_val = evaluate_expression(index, this, runtime, program);
-                LiveIndex::new(item_val, &index_val, runtime, program).map(LiveAssignable::Index)
-            }
-            Assignable::PropertyAccess { item, property } => {
-                let item_val = evaluate_expression(item, this, runtime, program);
-                LiveProperty::new(item_val, *property, &runtime.gc, program)
-                    .map(LiveAssignable::Property)
-            }
-        }
-    }
-
     pub fn get(&self, runtime: &mut Runtime, program: &Program) -> GribValue {
         match self {
             Self::Offset(offset) => runtime.get_offset(*offset).cloned().unwrap_or_default(),
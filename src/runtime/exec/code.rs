@@ -0,0 +1,181 @@
+///
+/// Bytecode produced from an analyzed program
+///
+use super::compile::compile;
+use ast::node::{AutoProp, Program};
+use location::Location;
+use operators::{Assignment, Binary, Unary};
+use runtime::values::GribValue;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// A single instruction.  Temporary values are kept on the runtime's
+/// operand stack, while variables stay on the Grib stack and are read
+/// through the offsets resolved during analysis
+#[derive(Clone, Debug)]
+pub enum Op {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    /// Allocates a copy of a string from the program's string table
+    String(usize),
+    Constant(usize),
+    This,
+    Load(usize),
+    /// Collects the given number of operands into an array
+    Array(usize),
+    Hash(usize),
+    Lambda(usize),
+    GetProp(usize),
+    GetIndex,
+    Unary(Unary),
+    /// A binary operator that evaluates both of its operands
+    Binary(Binary),
+    /// Converts the top operand into a boolean
+    Truthy,
+    AssignLocal {
+        op: Assignment,
+        offset: usize,
+    },
+    AssignIndex(Assignment),
+    AssignProp {
+        op: Assignment,
+        property: usize,
+    },
+    /// Calls the top operand with the arguments beneath it
+    Call {
+        args: usize,
+        site: usize,
+    },
+    /// Moves the top operand onto the Grib stack
+    Declare {
+        captured: bool,
+    },
+    PopLocals(usize),
+    Pop,
+    /// Discards the result of an expression statement,
+    /// reporting it if it's an error
+    Discard {
+        site: Option<usize>,
+    },
+    Jump(usize),
+    JumpIfFalse(usize),
+    JumpIfTrue(usize),
+    /// Installs a handler that catches values thrown before the matching
+    /// `ExitTry`.  The thrown value is pushed before jumping to the handler
+    EnterTry(usize),
+    ExitTry,
+    /// Unwinds the top operand to the nearest handler
+    Throw {
+        site: usize,
+    },
+    /// Returns the top operand, removing any variables declared since the chunk started
+    Return,
+    /// Returns the top operand, leaving declared variables on the stack
+    Exit,
+    /// Marks the start of a statement.  Only emitted for programs run under a debugger
+    Statement(usize),
+}
+
+/// How a value in a hash literal is created
+#[derive(Clone, Debug)]
+pub enum HashEntry {
+    /// The next operand
+    Value,
+    AutoProp(AutoProp),
+}
+
+#[derive(Clone, Debug)]
+pub struct HashTemplate {
+    pub mutable: bool,
+    pub entries: Vec<(usize, HashEntry)>,
+}
+
+impl HashTemplate {
+    /// The number of operands the hash is built from
+    pub fn values(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| matches!(entry, HashEntry::Value))
+            .count()
+    }
+}
+
+/// A statement the debugger can stop at
+#[derive(Clone, Debug)]
+pub struct Statement {
+    pub location: Location,
+    /// The names of the chunk's variables on the stack
+    /// when the statement runs, from the bottom up
+    pub locals: Vec<usize>,
+}
+
+/// The compiled body of a function or program
+#[derive(Clone, Debug, Default)]
+pub struct Chunk {
+    pub ops: Vec<Op>,
+    pub constants: Vec<GribValue>,
+    pub hashes: Vec<HashTemplate>,
+    /// Locations of function calls, used in stack traces
+    pub sites: Vec<Location>,
+    pub statements: Vec<Statement>,
+    /// The file the code is in
+    pub path: PathBuf,
+}
+
+/// Identifies the code of a function in the program
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CodeId {
+    Procedure { module: Option<usize>, index: usize },
+    Lambda(usize),
+    Getter(usize),
+    Setter(usize),
+}
+
+type Cache = Vec<Option<Rc<Chunk>>>;
+
+fn slot(cache: &mut Cache, index: usize) -> &mut Option<Rc<Chunk>> {
+    if cache.len() <= index {
+        cache.resize(index + 1, None);
+    }
+    &mut cache[index]
+}
+
+/// Functions are compiled the first time they are called
+#[derive(Default)]
+pub struct CodeCache {
+    /// Whether statements are marked for the debugger
+    pub debug: bool,
+    functions: Cache,
+    modules: Vec<Cache>,
+    lambdas: Cache,
+    getters: Cache,
+    setters: Cache,
+}
+
+impl CodeCache {
+    pub fn get(&mut self, id: CodeId, program: &Program) -> Rc<Chunk> {
+        let debug = self.debug;
+        let slot = match id {
+            CodeId::Procedure {
+                module: None,
+                index,
+            } => slot(&mut self.functions, index),
+            CodeId::Procedure {
+                module: Some(module),
+                index,
+            } => {
+                if self.modules.len() <= module {
+                    self.modules.resize(module + 1, Vec::new());
+                }
+                slot(&mut self.modules[module], index)
+            }
+            CodeId::Lambda(index) => slot(&mut self.lambdas, index),
+            CodeId::Getter(index) => slot(&mut self.getters, index),
+            CodeId::Setter(index) => slot(&mut self.setters, index),
+        };
+
+        slot.get_or_insert_with(|| Rc::new(compile(id, program, debug)))
+            .clone()
+    }
+}
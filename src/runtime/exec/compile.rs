@@ -0,0 +1,522 @@
+///
+/// Compiles analyzed syntax trees into bytecode
+///
+use super::code::*;
+use ast::node::*;
+use location::Location;
+use runtime::error::*;
+use std::fs;
+use std::path::Path;
+
+struct Loop {
+    /// The number of variables declared outside of the loop body
+    locals: usize,
+    /// The number of try blocks the loop is inside of
+    tries: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+struct Compiler<'a> {
+    program: &'a Program,
+    chunk: Chunk,
+    /// Variables declared in the chunk that are currently on the stack
+    locals: usize,
+    loops: Vec<Loop>,
+    /// The number of try blocks being compiled
+    tries: usize,
+    /// Whether statements are marked for the debugger
+    debug: bool,
+    /// The names of the chunk's variables that are on the stack
+    names: Vec<usize>,
+}
+
+fn param_names(params: &Parameters) -> impl Iterator<Item = usize> + '_ {
+    params.all_params().map(|param| param.name)
+}
+
+/// Compiles the body of a function.  Parameters and captured
+/// variables are already on the stack when the chunk runs
+pub fn compile(id: CodeId, program: &Program, debug: bool) -> Chunk {
+    let mut compiler = Compiler::new(program, debug);
+    let module = match id {
+        CodeId::Procedure { module, .. } => module,
+        CodeId::Lambda(index) => program.lambdas[index].module,
+        CodeId::Getter(index) => program.getters[index].module,
+        CodeId::Setter(index) => program.setters[index].module,
+    };
+    compiler.set_path(match module {
+        Some(module) => &program.modules[module].path,
+        None => &program.path,
+    });
+
+    let body = match id {
+        CodeId::Procedure { module, index } => {
+            let functions = match module {
+                Some(module) => &program.modules[module].functions,
+                None => &program.functions,
+            };
+            let function = &functions[index];
+            compiler.names.extend(param_names(&function.param_list));
+            compiler.stmts(&function.body);
+            None
+        }
+        CodeId::Lambda(index) => {
+            let lambda = &program.lambdas[index];
+            compiler.names.extend(&lambda.captured_names);
+            compiler.names.extend(param_names(&lambda.param_list));
+            Some(&lambda.body)
+        }
+        CodeId::Getter(index) => {
+            let getter = &program.getters[index];
+            compiler.names.extend(&getter.capture_names);
+            Some(&getter.block)
+        }
+        CodeId::Setter(index) => {
+            let setter = &program.setters[index];
+            compiler.names.extend(&setter.capture_names);
+            compiler.names.push(setter.param);
+            Some(&setter.block)
+        }
+    };
+
+    match body {
+        Some(LambdaBody::ImplicitReturn(expr)) => compiler.expr(expr),
+        Some(LambdaBody::Block(block)) => {
+            compiler.stmts(block);
+            compiler.emit(Op::Nil);
+        }
+        None => {
+            compiler.emit(Op::Nil);
+        }
+    };
+    compiler.emit(Op::Return);
+
+    compiler.chunk
+}
+
+/// Compiles the program's body, leaving its variables on the stack.  With
+/// `result` set, the value of the final statement is returned if it's an expression
+pub fn compile_main(program: &Program, result: bool, debug: bool) -> Chunk {
+    let mut compiler = Compiler::new(program, debug);
+    compiler.set_path(&program.path);
+
+    let body = &program.body;
+    match body.stmts.last() {
+        Some(Node::Expression(expr)) if result => {
+            let last = body.stmts.len() - 1;
+            compiler.stmts_to(body, last);
+            compiler.mark(body.location(last));
+            compiler.expr(expr);
+        }
+        _ => {
+            compiler.stmts(body);
+            compiler.emit(Op::Nil);
+        }
+    }
+    compiler.emit(Op::Exit);
+
+    compiler.chunk
+}
+
+impl<'a> Compiler<'a> {
+    fn new(program: &'a Program, debug: bool) -> Self {
+        Self {
+            program,
+            chunk: Chunk::default(),
+            locals: 0,
+            loops: Vec::new(),
+            tries: 0,
+            debug,
+            names: Vec::new(),
+        }
+    }
+
+    /// Records the file the code is in.  The debugger matches breakpoints against canonical paths
+    fn set_path(&mut self, path: &Path) {
+        self.chunk.path = match self.debug {
+            true => fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+            false => path.to_path_buf(),
+        };
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.chunk.ops.push(op);
+        self.chunk.ops.len() - 1
+    }
+
+    fn here(&self) -> usize {
+        self.chunk.ops.len()
+    }
+
+    /// Points the jump at the given index to the next instruction
+    fn patch(&mut self, jump: usize) {
+        let target = self.here();
+        match &mut self.chunk.ops[jump] {
+            Op::Jump(to) | Op::JumpIfFalse(to) | Op::JumpIfTrue(to) | Op::EnterTry(to) => {
+                *to = target
+            }
+            op => panic!("Attempted to patch non-jump instruction {:?}", op),
+        }
+    }
+
+    fn site(&mut self, location: &Location) -> usize {
+        self.chunk.sites.push(location.clone());
+        self.chunk.sites.len() - 1
+    }
+
+    fn pop_locals(&mut self, count: usize) {
+        if count > 0 {
+            self.emit(Op::PopLocals(count));
+        }
+    }
+
+    /// Removes the variables declared since there were `locals` of them
+    fn end_scope(&mut self, locals: usize) {
+        let declared = self.locals - locals;
+        self.pop_locals(declared);
+        self.names.truncate(self.names.len() - declared);
+        self.locals = locals;
+    }
+
+    /// Lets the debugger stop before the next instruction
+    fn mark(&mut self, location: Option<&Location>) {
+        if let (true, Some(location)) = (self.debug, location) {
+            self.chunk.statements.push(Statement {
+                location: location.clone(),
+                locals: self.names.clone(),
+            });
+            self.emit(Op::Statement(self.chunk.statements.len() - 1));
+        }
+    }
+
+    fn stmts(&mut self, block: &Block) {
+        self.stmts_to(block, block.stmts.len());
+    }
+
+    /// Compiles the statements of a block before `end`
+    fn stmts_to(&mut self, block: &Block, end: usize) {
+        for (index, node) in block.stmts[..end].iter().enumerate() {
+            self.mark(block.location(index));
+            self.stmt(node);
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        let locals = self.locals;
+        self.stmts(block);
+        self.end_scope(locals);
+    }
+
+    fn declare(&mut self, decl: &Declaration) {
+        for declarator in &decl.declarations {
+            self.expr(&declarator.value);
+            self.emit(Op::Declare {
+                captured: declarator.captured,
+            });
+            self.locals += 1;
+            self.names.push(declarator.identifier.data);
+        }
+    }
+
+    /// Compiles a loop body, leaving the breaks and continues to be patched
+    fn loop_body(&mut self, body: &Block) -> Loop {
+        self.loops.push(Loop {
+            locals: self.locals,
+            tries: self.tries,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        });
+        self.block(body);
+        self.loops.pop().expect("Loop was pushed")
+    }
+
+    fn stmt(&mut self, node: &Node) {
+        match node {
+            Node::Expression(expr) => {
+                self.expr(expr);
+                let site = match expr {
+                    Expression::FunctionCall { start, .. } => Some(self.site(start)),
+                    _ => None,
+                };
+                self.emit(Op::Discard { site });
+            }
+            Node::Block(block) => self.block(block),
+            Node::Declaration(decl) => self.declare(decl),
+            Node::ControlFlow(flow) => match &flow.kind {
+                BreakType::Return(expr) => {
+                    self.expr(expr);
+                    self.emit(Op::Return);
+                }
+                BreakType::Throw(expr) => {
+                    self.expr(expr);
+                    let site = self.site(&flow.start);
+                    self.emit(Op::Throw { site });
+                }
+                BreakType::Break | BreakType::Continue => {
+                    let outer = self
+                        .loops
+                        .last()
+                        .expect("Analysis only allows control breaks in loops");
+                    let (locals, tries) = (outer.locals, outer.tries);
+                    self.pop_locals(self.locals - locals);
+                    for _ in tries..self.tries {
+                        self.emit(Op::ExitTry);
+                    }
+
+                    let jump = self.emit(Op::Jump(0));
+                    let inner = self.loops.last_mut().unwrap();
+                    if let BreakType::Break = flow.kind {
+                        inner.breaks.push(jump);
+                    } else {
+                        inner.continues.push(jump);
+                    }
+                }
+            },
+            Node::LogicChain {
+                if_block,
+                elseifs,
+                else_block,
+            } => {
+                let mut ends = Vec::new();
+                for pair in std::iter::once(if_block).chain(elseifs) {
+                    self.expr(&pair.condition);
+                    let skip = self.emit(Op::JumpIfFalse(0));
+                    self.block(&pair.block);
+                    ends.push(self.emit(Op::Jump(0)));
+                    self.patch(skip);
+                }
+
+                if let Some(block) = else_block {
+                    self.block(block);
+                }
+
+                for end in ends {
+                    self.patch(end);
+                }
+            }
+            Node::Try { body, catch } => {
+                let handler = self.emit(Op::EnterTry(0));
+                self.tries += 1;
+                self.block(body);
+                self.tries -= 1;
+                self.emit(Op::ExitTry);
+                let end = self.emit(Op::Jump(0));
+
+                // The handler starts with the thrown value on the operand stack
+                self.patch(handler);
+                let locals = self.locals;
+                match &catch.param {
+                    Some(param) => {
+                        self.emit(Op::Declare {
+                            captured: param.captured,
+                        });
+                        self.locals += 1;
+                        self.names.push(param.name);
+                    }
+                    None => {
+                        self.emit(Op::Pop);
+                    }
+                }
+                self.stmts(&catch.block);
+                self.end_scope(locals);
+
+                self.patch(end);
+            }
+            Node::While(pair) => {
+                let start = self.here();
+                self.expr(&pair.condition);
+                let exit = self.emit(Op::JumpIfFalse(0));
+
+                let body = self.loop_body(&pair.block);
+                self.emit(Op::Jump(start));
+
+                self.patch(exit);
+                for jump in body.breaks {
+                    self.patch(jump);
+                }
+                for jump in body.continues {
+                    self.chunk.ops[jump] = Op::Jump(start);
+                }
+            }
+            Node::For {
+                declaration,
+                condition,
+                increment,
+                body,
+            } => {
+                let locals = self.locals;
+                if let Some(decl) = declaration {
+                    self.declare(decl);
+                }
+
+                let start = self.here();
+                let exit = condition.as_ref().map(|cond| {
+                    self.expr(cond);
+                    self.emit(Op::JumpIfFalse(0))
+                });
+
+                let body = self.loop_body(body);
+                for jump in body.continues {
+                    self.patch(jump);
+                }
+                if let Some(incr) = increment {
+                    self.expr(incr);
+                    self.emit(Op::Pop);
+                }
+                self.emit(Op::Jump(start));
+
+                if let Some(exit) = exit {
+                    self.patch(exit);
+                }
+                for jump in body.breaks {
+                    self.patch(jump);
+                }
+
+                self.end_scope(locals);
+            }
+        }
+    }
+
+    fn hash(&mut self, hash: &Hash, mutable: bool) {
+        let mut entries = Vec::with_capacity(hash.len());
+        for (label, value) in hash.iter() {
+            let entry = match value {
+                ObjectValue::Expression(expr) => {
+                    self.expr(expr);
+                    HashEntry::Value
+                }
+                ObjectValue::AutoProp(prop) => HashEntry::AutoProp(prop.clone()),
+            };
+            entries.push((*label, entry));
+        }
+
+        self.chunk.hashes.push(HashTemplate { mutable, entries });
+        let template = self.chunk.hashes.len() - 1;
+        self.emit(Op::Hash(template));
+    }
+
+    fn expr(&mut self, expression: &Expression) {
+        use self::Expression::*;
+        match expression {
+            Bool(b) => {
+                self.emit(Op::Bool(*b));
+            }
+            Nil => {
+                self.emit(Op::Nil);
+            }
+            This { .. } => {
+                self.emit(Op::This);
+            }
+            Number(f) => {
+                self.emit(Op::Number(*f));
+            }
+            String(s) => {
+                self.emit(Op::String(*s));
+            }
+            Hash(h) => self.hash(h, false),
+            MutableHash(h) => self.hash(h, true),
+            ArrayCreation(items) => {
+                for item in items {
+                    self.expr(item);
+                }
+                self.emit(Op::Array(items.len()));
+            }
+            Identifier(ident) => fatal(RuntimeErrorKind::Internal(format!(
+                "unresolved identifier `{}`",
+                self.program.strings[ident.data]
+            ))),
+            PropertyAccess { item, property } => {
+                self.expr(item);
+                self.emit(Op::GetProp(*property));
+            }
+            IndexAccess { item, index } => {
+                self.expr(item);
+                self.expr(index);
+                self.emit(Op::GetIndex);
+            }
+            Unary { op, expr } => {
+                self.expr(expr);
+                self.emit(Op::Unary(op.clone()));
+            }
+            Binary { op, left, right } if op.is_lazy() => {
+                // Both operators produce a boolean and skip
+                // their right side once the result is known
+                let and = *op == ::operators::Binary::LogicalAnd;
+                self.expr(left);
+                let short = self.emit(if and {
+                    Op::JumpIfFalse(0)
+                } else {
+                    Op::JumpIfTrue(0)
+                });
+                self.expr(right);
+                self.emit(Op::Truthy);
+                let end = self.emit(Op::Jump(0));
+
+                self.patch(short);
+                self.emit(Op::Bool(!and));
+                self.patch(end);
+            }
+            Binary { op, left, right } => {
+                self.expr(left);
+                self.expr(right);
+                self.emit(Op::Binary(op.clone()));
+            }
+            Assignment { op, left, right } => {
+                self.expr(right);
+                let op = op.clone();
+                match left {
+                    Assignable::Identifier(ident) => fatal(RuntimeErrorKind::Internal(format!(
+                        "unresolved identifier `{}`",
+                        self.program.strings[ident.data]
+                    ))),
+                    Assignable::Offset(offset) => {
+                        self.emit(Op::AssignLocal {
+                            op,
+                            offset: *offset,
+                        });
+                    }
+                    Assignable::IndexAccess { item, index } => {
+                        self.expr(item);
+                        self.expr(index);
+                        self.emit(Op::AssignIndex(op));
+                    }
+                    Assignable::PropertyAccess { item, property } => {
+                        self.expr(item);
+                        self.emit(Op::AssignProp {
+                            op,
+                            property: *property,
+                        });
+                    }
+                }
+            }
+            FunctionCall {
+                function,
+                args,
+                start,
+                ..
+            } => {
+                for arg in args {
+                    self.expr(arg);
+                }
+                self.expr(function);
+                let site = self.site(start);
+                self.emit(Op::Call {
+                    args: args.len(),
+                    site,
+                });
+            }
+            Lambda(index) => {
+                self.emit(Op::Lambda(*index));
+            }
+            Value(RuntimeValue::Static(value)) => {
+                self.chunk.constants.push(value.clone().into());
+                let constant = self.chunk.constants.len() - 1;
+                self.emit(Op::Constant(constant));
+            }
+            Value(RuntimeValue::StackOffset(offset)) => {
+                self.emit(Op::Load(*offset));
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use ast::node::{HostFunction, NativeFunction, Program};
+use location::Location;
+use runtime::error::StackFrame;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug)]
+pub enum FrameKind {
+    Procedure { module: Option<usize>, index: usize },
+    Lambda(usize),
+    Getter(usize),
+    Setter(usize),
+    Native(NativeFunction),
+    Host(HostFunction),
+}
+
+/// Record of an active function call
+#[derive(Clone, Debug)]
+pub struct CallFrame {
+    pub kind: FrameKind,
+    /// Location of the call expression in the caller's body
+    pub call_site: Option<Location>,
+}
+
+impl CallFrame {
+    pub fn new(kind: FrameKind, call_site: Option<&Location>) -> Self {
+        Self {
+            kind,
+            call_site: call_site.cloned(),
+        }
+    }
+
+    fn name(&self, program: &Program) -> String {
+        match &self.kind {
+            FrameKind::Procedure { module, index } => {
+                let fnc = match module {
+                    Some(m) => &program.modules[*m].functions[*index],
+                    None => &program.functions[*index],
+                };
+                program
+                    .strings
+                    .get(fnc.identifier.data)
+                    .cloned()
+                    .unwrap_or_default()
+            }
+            FrameKind::Lambda(_) => "<lambda>".to_string(),
+            FrameKind::Getter(_) => "<getter>".to_string(),
+            FrameKind::Setter(_) => "<setter>".to_string(),
+            FrameKind::Native(n) => format!("{}.{}", n.mod_name(), n.fn_name()),
+            FrameKind::Host(f) => {
+                let (package, name) = program.host.name_of(*f);
+                format!("{}.{}", package, name)
+            }
+        }
+    }
+
+    fn path(&self, program: &Program) -> Option<PathBuf> {
+        let module = match &self.kind {
+            FrameKind::Procedure { module, .. } => *module,
+            FrameKind::Lambda(i) => program.lambdas[*i].module,
+            FrameKind::Getter(i) => program.getters[*i].module,
+            FrameKind::Setter(i) => program.setters[*i].module,
+            FrameKind::Native(_) | FrameKind::Host(_) => return None,
+        };
+
+        Some(match module {
+            Some(m) => program.modules[m].path.clone(),
+            None => program.path.clone(),
+        })
+    }
+}
+
+/// Builds a stack trace from the active frames, innermost first.
+/// `location` is the position of execution inside the innermost frame
+pub fn stack_trace(
+    frames: &[CallFrame],
+    location: Option<&Location>,
+    program: &Program,
+) -> Vec<StackFrame> {
+    let mut trace = Vec::with_capacity(frames.len() + 1);
+    let mut location = location.cloned();
+
+    for frame in frames.iter().rev() {
+        trace.push(StackFrame {
+            name: frame.name(program),
+            path: frame.path(program),
+            location: location.take(),
+        });
+        location = frame.call_site.clone();
+    }
+
+    trace.push(StackFrame {
+        name: "<main>".to_string(),
+        path: Some(program.path.clone()),
+        location,
+    });
+
+    trace
+}
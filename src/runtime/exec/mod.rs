@@ -1,373 +1,223 @@
 mod access;
+mod code;
+mod compile;
+mod frame;
 mod operator;
+mod vm;
 
-use self::access::*;
-use self::operator::*;
+pub use self::code::{Chunk, CodeCache, CodeId, Statement};
+pub use self::frame::*;
+pub use self::vm::run_code;
 
+use self::compile::compile_main;
 use ast::node::*;
-use location::Located;
+use location::Location;
+use runtime::debug::{Breakpoints, DebugState, Debugger, Resume};
+use runtime::error::*;
 use runtime::memory::*;
 use runtime::values::*;
-
-pub fn execute(program: &Program, config: RuntimeConfig) {
-    let mut runtime = Runtime::new(config);
-    run_block(&program.body, &GribValue::Nil, &mut runtime, program);
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+/// Unwinds the interpreter from a throw statement to the nearest catch
+/// block.  The thrown value is kept in the runtime so it stays rooted
+struct Thrown {
+    location: Location,
 }
 
-#[derive(Debug)]
-pub enum ControlFlow {
-    Return(GribValue),
-    Break,
-    Continue,
+fn throw(value: GribValue, location: Location, runtime: &mut Runtime) -> ! {
+    runtime.thrown = Some(value);
+    panic::resume_unwind(Box::new(Thrown { location }))
 }
 
-impl ControlFlow {
-    pub fn new(
-        node: &FlowBreak,
-        this: &GribValue,
-        runtime: &mut Runtime,
-        program: &Program,
-    ) -> Self {
-        match &node.kind {
-            BreakType::Break => ControlFlow::Break,
-            BreakType::Continue => ControlFlow::Continue,
-            BreakType::Return(e) => {
-                ControlFlow::Return(evaluate_expression(e, this, runtime, program))
-            }
+/// Takes the value out of a throw.  Stack overflows are caught as error
+/// values, while any other failure continues to unwind
+fn catch(payload: Box<dyn Any + Send>, runtime: &mut Runtime) -> GribValue {
+    let payload = match payload.downcast::<Thrown>() {
+        Ok(_) => return runtime.thrown.take().unwrap_or_default(),
+        Err(payload) => payload,
+    };
+    match payload.downcast::<RuntimeErrorKind>() {
+        Ok(kind) if *kind == RuntimeErrorKind::StackOverflow => {
+            GribValue::err("Grib stack overflow")
         }
+        Ok(kind) => panic::resume_unwind(kind),
+        Err(payload) => panic::resume_unwind(payload),
     }
 }
 
-impl Default for ControlFlow {
-    fn default() -> Self {
-        Self::Return(GribValue::Nil)
-    }
+pub fn execute(program: &Program, config: RuntimeConfig) -> Result<(), RuntimeError> {
+    let mut runtime = Runtime::new(config);
+    let main = Rc::new(compile_main(program, false, false));
+    let result = catch_fatal(&mut runtime, program, |runtime| {
+        vm::run(main, &GribValue::Nil, runtime, program);
+    });
+    runtime.exit_report();
+    result
 }
 
-impl From<ControlFlow> for GribValue {
-    fn from(f: ControlFlow) -> Self {
-        if let ControlFlow::Return(val) = f {
-            val
-        } else {
-            GribValue::Nil
-        }
-    }
+/// Runs a program under a debugger, which is asked what to do whenever
+/// the program reaches a breakpoint or finishes a step
+pub fn debug(
+    program: &Program,
+    config: RuntimeConfig,
+    debugger: Box<dyn Debugger>,
+    breakpoints: Breakpoints,
+    start: Resume,
+) -> Result<(), RuntimeError> {
+    let mut runtime = Runtime::new(config);
+    runtime.code.debug = true;
+    runtime.debug = Some(DebugState::new(debugger, breakpoints, start));
+
+    let main = Rc::new(compile_main(program, false, true));
+    let result = catch_fatal(&mut runtime, program, |runtime| {
+        vm::run(main, &GribValue::Nil, runtime, program);
+    });
+    runtime.exit_report();
+    result
 }
 
-macro_rules! control_guard {
-    ($name:ident, $control:expr) => {{
-        let _t = $control;
-        if _t.is_some() {
-            $name = _t;
-            break;
-        }
-    }};
-}
-macro_rules! return_break {
-    ($name:ident, $control:expr) => {{
-        $name = $control.into();
-        break;
-    }};
-}
-macro_rules! check_flow {
-    ($name:ident, $control:expr) => {{
-        let _t = $control;
-        match &_t {
-            Some(ControlFlow::Return(_)) => return_break!($name, _t),
-            Some(ControlFlow::Break) => {
-                break;
-            }
-            Some(ControlFlow::Continue) | None => {}
-        }
-    }};
+/// Runs programs one input at a time, keeping the stack and
+/// heap alive in between.  Used by the REPL
+pub struct Session {
+    runtime: Runtime,
 }
 
-fn declare(decl: &Declaration, this: &GribValue, runtime: &mut Runtime, program: &Program) {
-    for declaration in &decl.declarations {
-        let value = evaluate_expression(&declaration.value, this, runtime, program);
-        if declaration.captured {
-            runtime.add_stack_captured(value);
-        } else {
-            runtime.stack.add(value);
+impl Session {
+    pub fn new(config: RuntimeConfig) -> Self {
+        Self {
+            runtime: Runtime::new(config),
         }
     }
-}
 
-pub fn run_block(
-    block: &Block,
-    this: &GribValue,
-    runtime: &mut Runtime,
-    program: &Program,
-) -> Option<ControlFlow> {
-    let mut result = None;
-    for node in block.iter() {
-        match &node {
-            Node::Block(block) => {
-                control_guard!(result, run_block(block, this, runtime, program));
-            }
-            Node::ControlFlow(flow) => {
-                let ret = ControlFlow::new(flow, this, runtime, program);
-                runtime.stack.pop_stack(flow.allocations);
-                return_break!(result, ret);
-            }
-            Node::Declaration(decl) => declare(decl, this, runtime, program),
-            Node::Expression(expression) => {
-                evaluate_expression(expression, this, runtime, program);
-            }
-            Node::LogicChain {
-                if_block,
-                elseifs,
-                else_block,
-            } => {
-                let first_cond = evaluate_expression(&if_block.condition, this, runtime, program);
-                if first_cond.truthy(program, &runtime.gc) {
-                    let res = run_block(&if_block.block, this, runtime, program);
-                    control_guard!(result, res);
-                } else {
-                    let mut run_else = true;
-                    for ConditionBodyPair { condition, block } in elseifs {
-                        let cond = evaluate_expression(&condition, this, runtime, program);
-                        if cond.truthy(program, &runtime.gc) {
-                            run_else = false;
-                            control_guard!(result, run_block(&block, this, runtime, program));
-                            break;
-                        }
-                    }
-
-                    if let Some(block) = else_block.as_ref().filter(|_| run_else) {
-                        control_guard!(result, run_block(&block, this, runtime, program));
-                    }
-                }
-            }
-            Node::While(pair) => {
-                let mut local_result = None;
+    /// Runs the program's body.  If the last statement is an expression
+    /// that doesn't evaluate to nil, its value is returned as a string.
+    /// Anything the body pushed onto the stack is removed if it fails
+    pub fn run(&mut self, program: &Program) -> Result<Option<String>, RuntimeError> {
+        let main = Rc::new(compile_main(program, true, self.runtime.code.debug));
+        self.guarded(program, |runtime| {
+            let val = vm::run(main, &GribValue::Nil, runtime, program);
+            Some(val)
+                .filter(|val| !val.is_nil())
+                .map(|val| val.display(program, runtime).into_owned())
+        })
+    }
 
-                while evaluate_expression(&pair.condition, this, runtime, program)
-                    .truthy(program, &runtime.gc)
-                {
-                    let val = run_block(&pair.block, this, runtime, program);
-                    check_flow!(local_result, val);
-                }
+    /// Runs the program's body like `run`, copying the value
+    /// of the last expression statement out of the runtime
+    pub fn eval(&mut self, program: &Program) -> Result<Value, RuntimeError> {
+        let main = Rc::new(compile_main(program, true, self.runtime.code.debug));
+        self.guarded(program, |runtime| {
+            let val = vm::run(main, &GribValue::Nil, runtime, program);
+            copy_rooted(val, runtime, program)
+        })
+    }
 
-                control_guard!(result, local_result);
+    /// Calls a function with arguments copied into the runtime
+    pub fn call(
+        &mut self,
+        program: &Program,
+        function: &Callable,
+        args: &[Value],
+    ) -> Result<Value, RuntimeError> {
+        self.guarded(program, |runtime| {
+            let base = runtime.operands.len();
+            for arg in args {
+                let arg = arg.to_grib(runtime, program);
+                runtime.operands.push(arg);
             }
-            Node::For {
-                declaration,
-                condition,
-                increment,
-                body,
-            } => {
-                let mut params = 0;
-                if let Some(d) = declaration {
-                    declare(d, this, runtime, program);
-                    params = d.declarations.len();
-                }
-
-                let mut local_result = None;
-                while condition
-                    .as_ref()
-                    .map(|c| evaluate_expression(&c, this, runtime, program))
-                    .map(|g| g.truthy(program, &runtime.gc))
-                    .unwrap_or(true)
-                {
-                    let flow = run_block(body, this, runtime, program);
-                    check_flow!(local_result, flow);
 
-                    if let Some(incr_expr) = increment {
-                        evaluate_expression(incr_expr, this, runtime, program);
-                    }
-                }
+            let args = runtime.operands[base..].to_vec();
+            let val = function.call(program, runtime, args);
+            runtime.operands.truncate(base);
+            copy_rooted(val, runtime, program)
+        })
+    }
 
-                runtime.stack.pop_stack(params);
-                control_guard!(result, local_result);
-            }
+    /// Runs `f`, resetting the runtime's stacks if it fails
+    fn guarded<T>(
+        &mut self,
+        program: &Program,
+        f: impl FnOnce(&mut Runtime) -> T,
+    ) -> Result<T, RuntimeError> {
+        let stack_len = self.runtime.stack.len();
+        let result = catch_fatal(&mut self.runtime, program, f);
+
+        if result.is_err() {
+            let pushed = self.runtime.stack.len().saturating_sub(stack_len);
+            self.runtime.stack.pop_stack(pushed);
+            self.runtime.operands.clear();
+            self.runtime.frames.clear();
+            self.runtime.thrown = None;
         }
-    }
 
-    // Don't pop block allocations if we've already popped them off
-    // while evaluating the control flow
-    if result.is_none() {
-        runtime.stack.pop_stack(block.allocations);
+        result
     }
 
-    result
-}
-
-fn evaluate_hash(
-    hash: &Hash,
-    mutable: bool,
-    this: &GribValue,
-    runtime: &mut Runtime,
-    program: &Program,
-) -> GribValue {
-    let ptr = runtime.reserve_slot();
-    let mut values = HashValue::new(mutable);
-
-    for (label, val) in hash.iter() {
-        let key = values.key(GribString::Stored(*label), program, &runtime.gc);
-        values.init_value(
-            key,
-            match val {
-                ObjectValue::Expression(e) => evaluate_expression(e, this, runtime, program).into(),
-                ObjectValue::AutoProp(prop) => {
-                    let get = prop.get.as_ref().map(|p| match p {
-                        AutoPropValue::String(_s) => {
-                            panic!("STRING AUTOPROP SHOULD NOT BE FOUND DURING RUNTIME")
-                        }
-                        AutoPropValue::Value(RuntimeValue::Static(static_val)) => {
-                            AccessFunc::Static(static_val.clone().into())
-                        }
-                        AutoPropValue::Value(RuntimeValue::StackOffset(offset)) => runtime
-                            .stack
-                            .offset_slot(*offset)
-                            .map(|p| match p {
-                                StackSlot::Captured(ind) => AccessFunc::Captured(*ind),
-                                StackSlot::Value(val) => AccessFunc::Static(val.clone()),
-                            })
-                            .expect("FAILED TO READ OFFSET"),
-                        AutoPropValue::Lambda(ind) => AccessFunc::Callable {
-                            index: *ind,
-                            stack: runtime.capture_stack(&program.getters[*ind].capture),
-                        },
-                    });
-
-                    let set = prop.set.as_ref().map(|p| match p {
-                        AutoPropValue::Value(RuntimeValue::Static(_))
-                        | AutoPropValue::String(_) => panic!("Invalid setter found during runtime"),
-                        AutoPropValue::Value(RuntimeValue::StackOffset(offset)) => {
-                            if let Some(StackSlot::Captured(ind)) =
-                                runtime.stack.offset_slot(*offset)
-                            {
-                                AccessFunc::Captured(*ind)
-                            } else {
-                                panic!(
-                                    "FAILED TO CAPTURE ACCESS SETTER OFFSET {} | Stack: {:?}",
-                                    offset, runtime.stack
-                                );
-                            }
-                        }
-                        AutoPropValue::Lambda(ind) => AccessFunc::Callable {
-                            index: *ind,
-                            stack: runtime.capture_stack(&program.setters[*ind].capture),
-                        },
-                    });
-
-                    HashPropertyValue::AutoProp { get, set }
-                }
-            },
-        )
+    /// Ends the session, printing anything that's reported on exit
+    pub fn close(self) {
+        self.runtime.exit_report();
     }
-
-    runtime.gc.set_heap_val_at(HeapValue::Hash(values), ptr);
-
-    GribValue::HeapValue(ptr)
-}
-
-pub fn evaluate_lambda(
-    body: &LambdaBody,
-    this: &GribValue,
-    runtime: &mut Runtime,
-    program: &Program,
-) -> GribValue {
-    let res = match body {
-        LambdaBody::Block(block) => match run_block(block, this, runtime, program) {
-            Some(ControlFlow::Return(value)) => value,
-            _ => GribValue::Nil,
-        },
-        LambdaBody::ImplicitReturn(expr) => evaluate_expression(&expr, this, runtime, program),
-    };
-
-    res
 }
 
-fn eval_list(
-    items: &Vec<Expression>,
-    this: &GribValue,
-    runtime: &mut Runtime,
-    program: &Program,
-) -> Vec<GribValue> {
-    items
-        .iter()
-        .map(|e| evaluate_expression(e, this, runtime, program))
-        .collect()
+/// Copies a value out of the runtime, keeping it on the
+/// operand stack in case its getters trigger a collection
+fn copy_rooted(val: GribValue, runtime: &mut Runtime, program: &Program) -> Value {
+    runtime.operands.push(val.clone());
+    let copied = Value::from_grib(&val, runtime, program);
+    runtime.operands.pop();
+    copied
 }
 
-pub fn evaluate_expression(
-    expression: &Expression,
-    this: &GribValue,
+/// Runs `f`, converting fatal errors into a `RuntimeError` with a stack trace
+fn catch_fatal<T>(
     runtime: &mut Runtime,
     program: &Program,
-) -> GribValue {
-    use self::Expression::*;
-    match expression {
-        Bool(b) => GribValue::Bool(*b),
-        Nil => GribValue::Nil,
-        This { .. } => this.clone(),
-        Number(f) => GribValue::Number(*f),
-        String(s) => runtime.alloc_str(program.strings[*s].clone()).into(),
-        Hash(h) => evaluate_hash(h, false, this, runtime, program),
-        MutableHash(h) => evaluate_hash(h, true, this, runtime, program),
-        ArrayCreation(expressions) => {
-            let array = eval_list(expressions, this, runtime, program);
-            GribValue::HeapValue(runtime.alloc_heap(HeapValue::Array(array)))
-        }
-        Identifier(Located { data, .. }) => panic!(
-            "Invalid identifier found during runtime ({})",
-            program.strings[*data]
-        ),
-        PropertyAccess { item, property } => {
-            let value = evaluate_expression(item.as_ref(), this, runtime, program);
-            LiveProperty::new(value, *property, &runtime.gc, program)
-                .map(|prop| prop.get(runtime, program))
-                .unwrap_or_default()
-        }
-        IndexAccess { item, index } => {
-            let item = evaluate_expression(item.as_ref(), this, runtime, program);
-            let index = evaluate_expression(index.as_ref(), this, runtime, program);
-            LiveIndex::new(item, &index, runtime, program)
-                .map(|ind| ind.get(runtime, program))
-                .unwrap_or_default()
-        }
-        Unary { op, expr } => {
-            let val = evaluate_expression(expr, this, runtime, program);
-            unary_expr(op, &val, &runtime.gc, program)
-        }
-        Binary { op, left, right } => {
-            let left_val = evaluate_expression(left, this, runtime, program);
-            binary_expr(op, &left_val, right.as_ref(), this, runtime, program)
-        }
-        Assignment { op, left, right } => {
-            let val = evaluate_expression(right, this, runtime, program);
-            assignment_expr(op, left, val, this, runtime, program)
-        }
-        FunctionCall { function, args } => {
-            let values = eval_list(args, this, runtime, program);
-            let fn_val = evaluate_expression(function, this, runtime, program);
-            if let GribValue::Callable(f) = fn_val {
-                f.call(program, runtime, values)
-            } else {
-                GribValue::Nil
+    f: impl FnOnce(&mut Runtime) -> T,
+) -> Result<T, RuntimeError> {
+    runtime.start_limits();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| f(runtime)));
+    runtime.nested_runs = 0;
+
+    result.map_err(|payload| {
+        let payload = match payload.downcast::<Thrown>() {
+            Ok(thrown) => {
+                let value = runtime.thrown.take().unwrap_or_default();
+                let kind = RuntimeErrorKind::Uncaught(value.display(program, runtime).into_owned());
+                return RuntimeError {
+                    kind,
+                    trace: stack_trace(&runtime.frames, Some(&thrown.location), program),
+                };
             }
+            Err(payload) => payload,
+        };
+
+        let kind = match payload.downcast::<RuntimeErrorKind>() {
+            Ok(kind) => *kind,
+            Err(payload) => RuntimeErrorKind::Internal(
+                payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default(),
+            ),
+        };
+
+        RuntimeError {
+            kind,
+            trace: stack_trace(&runtime.frames, None, program),
         }
-        Lambda(index) => GribValue::Callable(Callable::Lambda {
-            binding: None,
-            stack: runtime.capture_stack(&program.lambdas[*index].captured),
-            index: *index,
-        }),
-        Value(val) => match val {
-            RuntimeValue::Static(static_val) => static_val.clone().into(),
-            RuntimeValue::StackOffset(offset) => {
-                if let Some(val) = runtime.get_offset(*offset) {
-                    val.clone()
-                } else {
-                    panic!(
-                        "Couldn't load offset {} \nStack: {:?}\nLen: {}",
-                        offset,
-                        runtime.stack,
-                        runtime.stack.len(),
-                    );
-                }
-            }
-        },
+    })
+}
+
+/// Reports an error value that is discarded without being handled
+fn report_error(value: &GribValue, at: Option<&Location>, runtime: &Runtime, program: &Program) {
+    if let GribValue::Error(inner) = value {
+        eprintln!(
+            "warning: uncaught error: {}",
+            inner.as_str(program, runtime)
+        );
+        eprint!("{}", Trace(&stack_trace(&runtime.frames, at, program)));
     }
 }
@@ -1,7 +1,7 @@
-use super::LiveAssignable;
+use super::access::LiveAssignable;
 use ast::node::Program;
 use operators::{Assignment, Binary, Unary};
-use runtime::{exec::*, memory::*, values::*};
+use runtime::{memory::*, values::*};
 
 fn add_values(
     left: &GribValue,
@@ -70,48 +70,73 @@ fn mod_values(left: &GribValue, right: &GribValue, program: &Program, gc: &Gc) -
     GribValue::Number(left.cast_num(program, gc) % right.cast_num(program, gc))
 }
 
+/// Arithmetic and comparisons between two numbers, which skip coercion
+pub fn number_expr(op: &Binary, left: f64, right: f64) -> GribValue {
+    use self::Binary::*;
+    match op {
+        Plus => GribValue::Number(left + right),
+        Minus => GribValue::Number(left - right),
+        Mult => GribValue::Number(left * right),
+        Div => GribValue::Number(left / right),
+        Mod => GribValue::Number(left % right),
+        Equal => GribValue::Bool(left == right),
+        NotEqual => GribValue::Bool(left != right),
+        LessThan => GribValue::Bool(left < right),
+        GreaterThan => GribValue::Bool(left > right),
+        LessEq => GribValue::Bool(left <= right),
+        GreaterEq => GribValue::Bool(left >= right),
+        LogicalAnd | LogicalOr => panic!("Unreachable arm"),
+    }
+}
+
+/// Applies an operator that evaluates both of its operands.  The lazy
+/// logical operators are compiled into jumps instead
 pub fn binary_expr(
     op: &Binary,
     left: &GribValue,
-    right: &Expression,
-    this: &GribValue,
+    right: &GribValue,
     runtime: &mut Runtime,
     program: &Program,
 ) -> GribValue {
     use self::Binary::*;
-    if op.is_lazy() {
-        GribValue::Bool(if let &LogicalAnd = op {
-            left.truthy(program, &runtime.gc)
-                && evaluate_expression(right, this, runtime, program).truthy(program, &runtime.gc)
-        } else {
-            // LogicalOr
-            left.truthy(program, &runtime.gc)
-                || evaluate_expression(right, this, runtime, program).truthy(program, &runtime.gc)
-        })
-    } else {
-        let right_expr = evaluate_expression(right, this, runtime, program);
-        match op {
-            Plus => add_values(left, &right_expr, program, runtime),
-            Minus => sub_values(left, &right_expr, program, &runtime.gc),
-            Mult => mult_values(left, &right_expr, program, runtime),
-            Div => div_values(left, &right_expr, program, &runtime.gc),
-            Mod => mod_values(left, &right_expr, program, &runtime.gc),
-            LogicalAnd | LogicalOr => panic!("Unreachable arm"),
-            Equal | NotEqual => GribValue::Bool(
-                left.exact_equals(&right_expr, program, &runtime.gc) == (op == &Equal),
-            ),
-            GreaterThan | LessEq | LessThan | GreaterEq => left
-                .coerced_cmp(&right_expr, program, runtime)
-                .map(|c| match op {
-                    LessThan => c.is_lt(),
-                    GreaterThan => c.is_gt(),
-                    LessEq => c.is_le(),
-                    GreaterEq => c.is_ge(),
-                    _ => panic!("Unreachable arm"),
-                })
-                .unwrap_or(false)
-                .into(),
+    if let (GribValue::Number(l), GribValue::Number(r)) = (left, right) {
+        return number_expr(op, *l, *r);
+    }
+
+    match op {
+        Plus => add_values(left, right, program, runtime),
+        Minus => sub_values(left, right, program, &runtime.gc),
+        Mult => mult_values(left, right, program, runtime),
+        Div => div_values(left, right, program, &runtime.gc),
+        Mod => mod_values(left, right, program, &runtime.gc),
+        LogicalAnd | LogicalOr => panic!("Unreachable arm"),
+        Equal | NotEqual => {
+            GribValue::Bool(left.exact_equals(right, program, &runtime.gc) == (op == &Equal))
         }
+        GreaterThan | LessEq | LessThan | GreaterEq => left
+            .coerced_cmp(right, program, runtime)
+            .map(|c| match op {
+                LessThan => c.is_lt(),
+                GreaterThan => c.is_gt(),
+                LessEq => c.is_le(),
+                GreaterEq => c.is_ge(),
+                _ => panic!("Unreachable arm"),
+            })
+            .unwrap_or(false)
+            .into(),
+    }
+}
+
+/// Compound assignments between two numbers, which skip coercion.
+/// Plain assignments don't combine their operands, so they're `None`
+pub fn number_assignment(op: &Assignment, left: f64, right: f64) -> Option<f64> {
+    match op {
+        Assignment::Assign => None,
+        Assignment::AssignPlus => Some(left + right),
+        Assignment::AssignMinus => Some(left - right),
+        Assignment::AssignMult => Some(left * right),
+        Assignment::AssignDiv => Some(left / right),
+        Assignment::AssignMod => Some(left % right),
     }
 }
 
@@ -124,65 +149,63 @@ pub fn unary_expr(op: &Unary, val: &GribValue, gc: &Gc, program: &Program) -> Gr
 
 pub fn assignment_expr(
     op: &Assignment,
-    left: &Assignable,
+    live: Option<LiveAssignable>,
     right: GribValue,
-    this: &GribValue,
     runtime: &mut Runtime,
     program: &Program,
 ) -> GribValue {
-    LiveAssignable::new(left, this, runtime, program)
-        .map(|live| {
-            let mut val = || live.get(runtime, program);
-            let res = match op {
-                Assignment::Assign => right.clone(),
-                Assignment::AssignDiv => div_values(&val(), &right, program, &runtime.gc),
-                Assignment::AssignMinus => sub_values(&val(), &right, program, &runtime.gc),
-                Assignment::AssignMod => mod_values(&val(), &right, program, &runtime.gc),
-                Assignment::AssignMult => {
-                    let val = val();
-
-                    // Check for array
-                    // If so, repeat array R times
-                    right
-                        .cast_ind(program, &runtime.gc)
-                        .and_then(|right| {
-                            runtime.gc.try_get_array_mut(val.clone()).map(|array| {
-                                if right == 0 {
-                                    array.clear();
-                                    return val.clone();
-                                }
-
-                                let r = right * array.len();
-                                let count = right.checked_sub(1).unwrap_or(0);
-
-                                array.reserve(r);
-                                for _ in 0..count {
-                                    let mut copy = array.clone();
-                                    array.append(&mut copy);
-                                }
-
-                                val.clone()
-                            })
-                        })
-                        .unwrap_or_else(|| mult_values(&val, &right, program, runtime))
-                }
-                Assignment::AssignPlus => {
-                    let val = val();
-
-                    // Check for array
-                    // If so, repeat array R times
-                    runtime
-                        .gc
-                        .try_get_array_mut(val.clone())
-                        .map(|array| {
-                            array.push(right.clone());
+    live.map(|live| {
+        let mut val = || live.get(runtime, program);
+        let res = match op {
+            Assignment::Assign => right.clone(),
+            Assignment::AssignDiv => div_values(&val(), &right, program, &runtime.gc),
+            Assignment::AssignMinus => sub_values(&val(), &right, program, &runtime.gc),
+            Assignment::AssignMod => mod_values(&val(), &right, program, &runtime.gc),
+            Assignment::AssignMult => {
+                let val = val();
+
+                // Check for array
+                // If so, repeat array R times
+                right
+                    .cast_ind(program, &runtime.gc)
+                    .and_then(|right| {
+                        runtime.gc.try_get_array_mut(val.clone()).map(|array| {
+                            if right == 0 {
+                                array.clear();
+                                return val.clone();
+                            }
+
+                            let r = right * array.len();
+                            let count = right.checked_sub(1).unwrap_or(0);
+
+                            array.reserve(r);
+                            for _ in 0..count {
+                                let mut copy = array.clone();
+                                array.append(&mut copy);
+                            }
+
                             val.clone()
                         })
-                        .unwrap_or_else(|| add_values(&val, &right, program, runtime))
-                }
-            };
+                    })
+                    .unwrap_or_else(|| mult_values(&val, &right, program, runtime))
+            }
+            Assignment::AssignPlus => {
+                let val = val();
+
+                // Check for array
+                // If so, repeat array R times
+                runtime
+                    .gc
+                    .try_get_array_mut(val.clone())
+                    .map(|array| {
+                        array.push(right.clone());
+                        val.clone()
+                    })
+                    .unwrap_or_else(|| add_values(&val, &right, program, runtime))
+            }
+        };
 
-            live.set(runtime, program, res)
-        })
-        .unwrap_or(right)
+        live.set(runtime, program, res)
+    })
+    .unwrap_or(right)
 }
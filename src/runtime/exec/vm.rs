@@ -0,0 +1,500 @@
+///
+/// Executes compiled bytecode
+///
+use super::access::*;
+use super::code::*;
+use super::operator::*;
+use super::{catch, report_error, throw};
+use ast::node::*;
+use operators::Assignment;
+use runtime::debug;
+use runtime::error::*;
+use runtime::exec::CallFrame;
+use runtime::memory::*;
+use runtime::values::*;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+/// Runs the compiled code of a function, compiling it if it hasn't run before
+pub fn run_code(
+    id: CodeId,
+    this: &GribValue,
+    runtime: &mut Runtime,
+    program: &Program,
+) -> GribValue {
+    let chunk = runtime.code.get(id, program);
+    run(chunk, this, runtime, program)
+}
+
+fn pop(runtime: &mut Runtime) -> GribValue {
+    runtime.operands.pop().unwrap_or_default()
+}
+
+fn top(runtime: &Runtime) -> GribValue {
+    runtime.operands.last().cloned().unwrap_or_default()
+}
+
+/// The operand `depth` places below the top one
+fn peek(runtime: &Runtime, depth: usize) -> &GribValue {
+    &runtime.operands[runtime.operands.len() - 1 - depth]
+}
+
+/// Replaces the operands the result was computed from, which is at least
+/// one.  Operands stay in place while they're used so the garbage collector sees them
+fn replace(runtime: &mut Runtime, count: usize, result: GribValue) {
+    let first = runtime.operands.len() - count;
+    runtime.operands.truncate(first + 1);
+    runtime.operands[first] = result;
+}
+
+/// Assigns the top operand to a variable that isn't captured, leaving the
+/// operand as the result.  Captured variables and operands that need
+/// coercion aren't handled, in which case nothing is changed
+fn assign_local(op: &Assignment, offset: usize, runtime: &mut Runtime) -> bool {
+    let right = match runtime.operands.last_mut() {
+        Some(right) => right,
+        None => return false,
+    };
+    let left = match runtime.stack.offset_slot_mut(offset) {
+        Some(StackSlot::Value(left)) => left,
+        _ => return false,
+    };
+
+    match (left, &*right) {
+        (left, right) if *op == Assignment::Assign => *left = right.clone(),
+        (GribValue::Number(left), GribValue::Number(r)) => match number_assignment(op, *left, *r) {
+            Some(result) => {
+                *left = result;
+                *right = GribValue::Number(result);
+            }
+            None => return false,
+        },
+        _ => return false,
+    }
+    true
+}
+
+fn make_hash(template: &HashTemplate, runtime: &mut Runtime, program: &Program) -> GribValue {
+    let count = template.values();
+    let first = runtime.operands.len() - count;
+    let mut values = HashValue::new(template.mutable);
+    let mut next = first;
+
+    for (label, entry) in &template.entries {
+        let key = values.key(GribString::Stored(*label), program, &runtime.gc);
+        let value = match entry {
+            HashEntry::Value => {
+                next += 1;
+                runtime.operands[next - 1].clone().into()
+            }
+            HashEntry::AutoProp(prop) => {
+                let get = prop.get.as_ref().map(|p| match p {
+                    AutoPropValue::String(_) => fatal(RuntimeErrorKind::Internal(
+                        "unresolved string autoprop".to_string(),
+                    )),
+                    AutoPropValue::Value(RuntimeValue::Static(static_val)) => {
+                        AccessFunc::Static(static_val.clone().into())
+                    }
+                    AutoPropValue::Value(RuntimeValue::StackOffset(offset)) => runtime
+                        .stack
+                        .offset_slot(*offset)
+                        .map(|p| match p {
+                            StackSlot::Captured(ind) => AccessFunc::Captured(*ind),
+                            StackSlot::Value(val) => AccessFunc::Static(val.clone()),
+                        })
+                        .unwrap_or_else(|| fatal(RuntimeErrorKind::InvalidOffset(*offset))),
+                    AutoPropValue::Lambda(ind) => AccessFunc::Callable {
+                        index: *ind,
+                        stack: capture_rooted(&program.getters[*ind].capture, runtime),
+                    },
+                });
+
+                let set = prop.set.as_ref().map(|p| match p {
+                    AutoPropValue::Value(RuntimeValue::Static(_)) | AutoPropValue::String(_) => {
+                        fatal(RuntimeErrorKind::Internal(
+                            "invalid autoprop setter".to_string(),
+                        ))
+                    }
+                    AutoPropValue::Value(RuntimeValue::StackOffset(offset)) => {
+                        if let Some(StackSlot::Captured(ind)) = runtime.stack.offset_slot(*offset) {
+                            AccessFunc::Captured(*ind)
+                        } else {
+                            fatal(RuntimeErrorKind::InvalidOffset(*offset))
+                        }
+                    }
+                    AutoPropValue::Lambda(ind) => AccessFunc::Callable {
+                        index: *ind,
+                        stack: capture_rooted(&program.setters[*ind].capture, runtime),
+                    },
+                });
+
+                HashPropertyValue::AutoProp { get, set }
+            }
+        };
+        values.init_value(key, value);
+    }
+
+    let ptr = runtime.alloc_heap(HeapValue::Hash(values));
+    runtime.operands.truncate(first);
+    GribValue::HeapValue(ptr)
+}
+
+/// Captures part of the stack, keeping it on the operand
+/// stack until the hash that refers to it is allocated
+fn capture_rooted(to_capture: &Vec<usize>, runtime: &mut Runtime) -> Option<usize> {
+    let stack = runtime.capture_stack(to_capture);
+    if let Some(ptr) = stack {
+        runtime.operands.push(GribValue::HeapValue(ptr));
+    }
+    stack
+}
+
+/// The most chunks that can run on the native stack at once.  Native
+/// functions and accessors that call Grib functions start a new one
+const MAX_NESTED_RUNS: usize = 1000;
+
+/// Where a try block's handler starts and the state to restore when it catches
+struct Handler {
+    target: usize,
+    /// The index of the frame the try block is in
+    depth: usize,
+    stack: usize,
+    operands: usize,
+    frames: usize,
+}
+
+/// A Grib function running in the interpreter loop
+struct Frame {
+    chunk: Rc<Chunk>,
+    pc: usize,
+    this: GribValue,
+    /// The lengths of the Grib and operand stacks when the function was called
+    stack: usize,
+    operands: usize,
+    /// The number of the caller's operands that the return value replaces
+    call: usize,
+}
+
+/// Calls between Grib functions are handled by pushing frames
+/// rather than by recursing, so they don't use the native stack
+struct Vm {
+    frames: Vec<Frame>,
+    handlers: Vec<Handler>,
+}
+
+/// Runs a chunk until it returns
+pub fn run(
+    chunk: Rc<Chunk>,
+    this: &GribValue,
+    runtime: &mut Runtime,
+    program: &Program,
+) -> GribValue {
+    let nested = runtime.nested_runs;
+    if nested >= MAX_NESTED_RUNS {
+        fatal(RuntimeErrorKind::StackOverflow);
+    }
+    runtime.nested_runs += 1;
+
+    let mut vm = Vm {
+        frames: vec![Frame {
+            chunk,
+            pc: 0,
+            this: this.clone(),
+            stack: runtime.stack.len(),
+            operands: runtime.operands.len(),
+            call: 0,
+        }],
+        handlers: Vec::new(),
+    };
+
+    loop {
+        // The loop only runs under a guard while a try block is active
+        let result = if vm.handlers.is_empty() {
+            vm.run_ops(runtime, program)
+        } else {
+            let guarded = panic::catch_unwind(AssertUnwindSafe(|| vm.run_ops(runtime, program)));
+            match guarded {
+                Ok(result) => result,
+                Err(payload) => {
+                    // The try blocks may have finished since the guard was entered
+                    if vm.handlers.is_empty() {
+                        panic::resume_unwind(payload);
+                    }
+                    let value = catch(payload, runtime);
+                    runtime.nested_runs = nested + 1;
+                    vm.unwind(value, runtime);
+                    None
+                }
+            }
+        };
+
+        if let Some(value) = result {
+            runtime.nested_runs = nested;
+            return value;
+        }
+    }
+}
+
+impl Vm {
+    /// Jumps to the innermost handler, removing everything left behind by the code that threw
+    fn unwind(&mut self, value: GribValue, runtime: &mut Runtime) {
+        let handler = self.handlers.pop().expect("A handler is active");
+        self.frames.truncate(handler.depth + 1);
+        self.frames[handler.depth].pc = handler.target;
+
+        let declared = runtime.stack.len().saturating_sub(handler.stack);
+        runtime.stack.pop_stack(declared);
+        runtime.operands.truncate(handler.operands);
+        runtime.frames.truncate(handler.frames);
+        runtime.operands.push(value);
+    }
+
+    /// Executes instructions until the first frame returns.  Stops
+    /// early with `None` when the first try block is entered
+    #[inline]
+    fn run_ops(&mut self, runtime: &mut Runtime, program: &Program) -> Option<GribValue> {
+        let frame = self.frames.last().expect("A frame is running");
+        let mut chunk = frame.chunk.clone();
+        let mut this = frame.this.clone();
+        let mut pc = frame.pc;
+        // Instructions are counted against the limits in batches.  A program
+        // can only run for long by jumping back or calling, so the batch is
+        // handed over before backward jumps, calls, and leaving the loop
+        let mut executed = 0;
+
+        loop {
+            executed += 1;
+            let op = &chunk.ops[pc];
+            pc += 1;
+
+            match op {
+                Op::Nil => runtime.operands.push(GribValue::Nil),
+                Op::Bool(b) => runtime.operands.push(GribValue::Bool(*b)),
+                Op::Number(n) => runtime.operands.push(GribValue::Number(*n)),
+                Op::String(s) => {
+                    let string = runtime.alloc_str(program.strings[*s].clone());
+                    runtime.operands.push(GribValue::String(string));
+                }
+                Op::Constant(c) => runtime.operands.push(chunk.constants[*c].clone()),
+                Op::This => runtime.operands.push(this.clone()),
+                Op::Load(offset) => {
+                    let value = match runtime.get_offset(*offset) {
+                        Some(value) => value.clone(),
+                        None => fatal(RuntimeErrorKind::InvalidOffset(*offset)),
+                    };
+                    runtime.operands.push(value);
+                }
+                Op::Array(count) => {
+                    let ptr = runtime.alloc_heap(HeapValue::Array(Vec::new()));
+                    let len = runtime.operands.len();
+                    let items = runtime.operands.split_off(len - count);
+                    runtime.gc.set_heap_val_at(HeapValue::Array(items), ptr);
+                    runtime.operands.push(GribValue::HeapValue(ptr));
+                }
+                Op::Hash(template) => {
+                    let hash = make_hash(&chunk.hashes[*template], runtime, program);
+                    runtime.operands.push(hash);
+                }
+                Op::Lambda(index) => {
+                    let stack = runtime.capture_stack(&program.lambdas[*index].captured);
+                    runtime.operands.push(GribValue::Callable(Callable::Lambda {
+                        binding: None,
+                        stack,
+                        index: *index,
+                    }));
+                }
+                Op::GetProp(property) => {
+                    let item = top(runtime);
+                    let value = LiveProperty::new(item, *property, &runtime.gc, program)
+                        .map(|prop| prop.get(runtime, program))
+                        .unwrap_or_default();
+                    replace(runtime, 1, value);
+                }
+                Op::GetIndex => {
+                    let index = pop(runtime);
+                    let item = top(runtime);
+                    let value = LiveIndex::new(item, &index, runtime, program)
+                        .map(|ind| ind.get(runtime, program))
+                        .unwrap_or_default();
+                    replace(runtime, 1, value);
+                }
+                Op::Unary(op) => {
+                    let value = unary_expr(op, peek(runtime, 0), &runtime.gc, program);
+                    replace(runtime, 1, value);
+                }
+                Op::Binary(op) => {
+                    let value = match (peek(runtime, 1), peek(runtime, 0)) {
+                        (GribValue::Number(left), GribValue::Number(right)) => {
+                            number_expr(op, *left, *right)
+                        }
+                        (left, right) => {
+                            let (left, right) = (left.clone(), right.clone());
+                            binary_expr(op, &left, &right, runtime, program)
+                        }
+                    };
+                    replace(runtime, 2, value);
+                }
+                Op::Truthy => {
+                    let value = peek(runtime, 0).truthy(program, &runtime.gc);
+                    replace(runtime, 1, GribValue::Bool(value));
+                }
+                Op::AssignLocal { op, offset } => {
+                    if !assign_local(op, *offset, runtime) {
+                        let right = top(runtime);
+                        let live = Some(LiveAssignable::Offset(*offset));
+                        let value = assignment_expr(op, live, right, runtime, program);
+                        replace(runtime, 1, value);
+                    }
+                }
+                Op::AssignIndex(op) => {
+                    let len = runtime.operands.len();
+                    let right = runtime.operands[len - 3].clone();
+                    let item = runtime.operands[len - 2].clone();
+                    let index = &runtime.operands[len - 1].clone();
+
+                    let live =
+                        LiveIndex::new(item, index, runtime, program).map(LiveAssignable::Index);
+                    let value = assignment_expr(op, live, right, runtime, program);
+                    replace(runtime, 3, value);
+                }
+                Op::AssignProp { op, property } => {
+                    let len = runtime.operands.len();
+                    let right = runtime.operands[len - 2].clone();
+                    let item = runtime.operands[len - 1].clone();
+
+                    let live = LiveProperty::new(item, *property, &runtime.gc, program)
+                        .map(LiveAssignable::Property);
+                    let value = assignment_expr(op, live, right, runtime, program);
+                    replace(runtime, 2, value);
+                }
+                Op::Call { args, site } => {
+                    runtime.step(executed);
+                    executed = 0;
+                    let (args, site) = (*args, *site);
+                    let len = runtime.operands.len();
+                    let first = len - args - 1;
+                    let function = top(runtime);
+                    let values = runtime.operands[first..len - 1].to_vec();
+
+                    match function {
+                        GribValue::Callable(f) if !f.is_native() => {
+                            let frame = CallFrame::new(f.frame_kind(), Some(&chunk.sites[site]));
+                            runtime.push_frame(frame);
+                            let stack = runtime.stack.len();
+                            let (id, callee_this, _) = f.enter(program, runtime, values);
+                            let callee = runtime.code.get(id, program);
+
+                            self.frames.last_mut().expect("A frame is running").pc = pc;
+                            self.frames.push(Frame {
+                                chunk: callee.clone(),
+                                pc: 0,
+                                this: callee_this.clone(),
+                                stack,
+                                operands: runtime.operands.len(),
+                                call: args + 1,
+                            });
+                            chunk = callee;
+                            this = callee_this;
+                            pc = 0;
+                        }
+                        GribValue::Callable(f) => {
+                            let value =
+                                f.call_at(program, runtime, values, Some(&chunk.sites[site]));
+                            replace(runtime, args + 1, value);
+                        }
+                        _ => replace(runtime, args + 1, GribValue::Nil),
+                    }
+                }
+                Op::Declare { captured } => {
+                    let value = top(runtime);
+                    if *captured {
+                        runtime.add_stack_captured(value);
+                    } else {
+                        runtime.stack.add(value);
+                    }
+                    runtime.operands.pop();
+                }
+                Op::PopLocals(count) => runtime.stack.pop_stack(*count),
+                Op::Pop => {
+                    runtime.operands.pop();
+                }
+                Op::Discard { site } => {
+                    let value = pop(runtime);
+                    if runtime.report_errors && value.is_err() {
+                        let at = site.map(|site| &chunk.sites[site]);
+                        report_error(&value, at, runtime, program);
+                    }
+                }
+                Op::Jump(target) => {
+                    if *target < pc {
+                        runtime.step(executed);
+                        executed = 0;
+                    }
+                    pc = *target;
+                }
+                Op::JumpIfFalse(target) => {
+                    if !pop(runtime).truthy(program, &runtime.gc) {
+                        pc = *target;
+                    }
+                }
+                Op::JumpIfTrue(target) => {
+                    if pop(runtime).truthy(program, &runtime.gc) {
+                        pc = *target;
+                    }
+                }
+                Op::EnterTry(target) => {
+                    self.handlers.push(Handler {
+                        target: *target,
+                        depth: self.frames.len() - 1,
+                        stack: runtime.stack.len(),
+                        operands: runtime.operands.len(),
+                        frames: runtime.frames.len(),
+                    });
+                    if self.handlers.len() == 1 {
+                        self.frames.last_mut().expect("A frame is running").pc = pc;
+                        runtime.step(executed);
+                        return None;
+                    }
+                }
+                Op::ExitTry => {
+                    self.handlers.pop();
+                }
+                Op::Throw { site } => {
+                    let value = pop(runtime);
+                    throw(value, chunk.sites[*site].clone(), runtime)
+                }
+                Op::Return => {
+                    let value = pop(runtime);
+                    let frame = self.frames.pop().expect("A frame is running");
+                    runtime.operands.truncate(frame.operands);
+                    let declared = runtime.stack.len().saturating_sub(frame.stack);
+                    runtime.stack.pop_stack(declared);
+
+                    // Try blocks that were returned out of
+                    let depth = self.frames.len();
+                    while self.handlers.last().is_some_and(|h| h.depth >= depth) {
+                        self.handlers.pop();
+                    }
+
+                    let caller = match self.frames.last() {
+                        Some(caller) => caller,
+                        None => {
+                            runtime.step(executed);
+                            return Some(value);
+                        }
+                    };
+                    runtime.frames.pop();
+                    replace(runtime, frame.call, value);
+                    chunk = caller.chunk.clone();
+                    this = caller.this.clone();
+                    pc = caller.pc;
+                }
+                Op::Exit => {
+                    runtime.step(executed);
+                    return Some(pop(runtime));
+                }
+                Op::Statement(index) => debug::statement(runtime, program, &chunk, *index, &this),
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+///
+/// Packages of native functions registered by a program embedding Grib
+///
+use ast::node::Program;
+use runtime::memory::Runtime;
+use runtime::native_fn::NativePackage;
+use runtime::values::GribValue;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::sync::Arc;
+
+/// A function implemented by the host program.  It's shared between
+/// copies of the program, which can be sent to other threads.  The
+/// program is passed so string arguments can be read with `GribValue::as_str`
+pub type HostFn = Arc<dyn Fn(&Program, &mut Runtime, Vec<GribValue>) -> GribValue + Send + Sync>;
+
+/// Identifies a function in a `HostRegistry`
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct HostFunction {
+    pub package: usize,
+    pub index: usize,
+}
+
+/// Host packages can't take the name of a package in the standard library
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReservedName(pub String);
+
+impl Display for ReservedName {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "`{}` is a package in the standard library", self.0)
+    }
+}
+
+impl std::error::Error for ReservedName {}
+
+#[derive(Clone)]
+struct Entry {
+    name: String,
+    function: HostFn,
+    /// Sandboxed programs can't call the function
+    escapes_sandbox: bool,
+}
+
+/// A package that can be imported by name, like the ones in the standard library
+#[derive(Clone)]
+pub struct HostPackage {
+    pub name: String,
+    functions: Vec<Entry>,
+}
+
+impl HostPackage {
+    /// Adds a function to the package, replacing any with the same name
+    pub fn function<F>(&mut self, name: &str, function: F) -> &mut Self
+    where
+        F: Fn(&Program, &mut Runtime, Vec<GribValue>) -> GribValue + Send + Sync + 'static,
+    {
+        self.insert(name, Arc::new(function), false)
+    }
+
+    /// Adds a function that reaches outside of the interpreter, like the ones
+    /// in `syncio` do.  Sandboxed programs are stopped if they call it
+    pub fn escaping_function<F>(&mut self, name: &str, function: F) -> &mut Self
+    where
+        F: Fn(&Program, &mut Runtime, Vec<GribValue>) -> GribValue + Send + Sync + 'static,
+    {
+        self.insert(name, Arc::new(function), true)
+    }
+
+    fn insert(&mut self, name: &str, function: HostFn, escapes_sandbox: bool) -> &mut Self {
+        let entry = Entry {
+            name: name.to_string(),
+            function,
+            escapes_sandbox,
+        };
+        match self.functions.iter_mut().find(|e| e.name == name) {
+            Some(existing) => *existing = entry,
+            None => self.functions.push(entry),
+        }
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.functions.iter().map(|e| e.name.as_str())
+    }
+}
+
+/// The packages registered by the host.  Packages and functions are only
+/// ever added, so the indexes stored in parsed programs stay valid
+#[derive(Clone, Default)]
+pub struct HostRegistry {
+    packages: Vec<HostPackage>,
+}
+
+impl HostRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the package with the given name, creating it if it doesn't exist
+    pub fn package(&mut self, name: &str) -> Result<&mut HostPackage, ReservedName> {
+        if NativePackage::from_str(name).is_some() {
+            return Err(ReservedName(name.to_string()));
+        }
+
+        let index = match self.find(name) {
+            Some(index) => index,
+            None => {
+                self.packages.push(HostPackage {
+                    name: name.to_string(),
+                    functions: Vec::new(),
+                });
+                self.packages.len() - 1
+            }
+        };
+        Ok(&mut self.packages[index])
+    }
+
+    /// Finds the index of a package by name
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.packages.iter().position(|p| p.name == name)
+    }
+
+    pub fn get(&self, package: usize) -> Option<&HostPackage> {
+        self.packages.get(package)
+    }
+
+    pub fn len(&self) -> usize {
+        self.packages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    pub fn function(&self, package: usize, name: &str) -> Option<HostFunction> {
+        let index = self
+            .packages
+            .get(package)?
+            .names()
+            .position(|n| n == name)?;
+        Some(HostFunction { package, index })
+    }
+
+    /// Returns the package and function name of a host function
+    pub fn name_of(&self, function: HostFunction) -> (&str, &str) {
+        let package = &self.packages[function.package];
+        (&package.name, &package.functions[function.index].name)
+    }
+
+    /// Whether the function was registered with `HostPackage::escaping_function`
+    pub fn escapes_sandbox(&self, function: HostFunction) -> bool {
+        self.packages[function.package].functions[function.index].escapes_sandbox
+    }
+
+    pub fn call(
+        &self,
+        function: HostFunction,
+        program: &Program,
+        runtime: &mut Runtime,
+        args: Vec<GribValue>,
+    ) -> GribValue {
+        let entry = &self.packages[function.package].functions[function.index];
+        (entry.function)(program, runtime, args)
+    }
+}
+
+impl Debug for HostRegistry {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.packages
+                    .iter()
+                    .map(|p| (&p.name, p.names().collect::<Vec<_>>())),
+            )
+            .finish()
+    }
+}
+
+/// Registries are compared by the functions they contain, since closures can't be
+impl PartialEq for HostRegistry {
+    fn eq(&self, other: &Self) -> bool {
+        self.packages.len() == other.packages.len()
+            && self
+                .packages
+                .iter()
+                .zip(&other.packages)
+                .all(|(a, b)| a.name == b.name && a.names().eq(b.names()))
+    }
+}
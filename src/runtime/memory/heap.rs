@@ -1,4 +1,5 @@
 use ast::node::Program;
+use rand::rngs::StdRng;
 use runtime::memory::slot::*;
 use runtime::values::*;
 
@@ -6,11 +7,26 @@ use runtime::values::*;
 pub struct Gc {
     //(in runtime::memory)
     pub heap: Vec<MarkedSlot>,
+    /// Old slots written to since the last collection.  They may
+    /// refer to young slots, so minor collections trace from them
+    pub(in runtime::memory) remembered: Vec<usize>,
 }
 
 impl Gc {
     pub fn new() -> Self {
-        Gc { heap: Vec::new() }
+        Gc {
+            heap: Vec::new(),
+            remembered: Vec::new(),
+        }
+    }
+
+    /// Records writes to old slots.  Every mutable
+    /// reference into the heap has to pass through here
+    fn write_barrier(&mut self, ptr: usize) {
+        if let Some(slot) = self.heap.get_mut(ptr).filter(|s| s.old && !s.remembered) {
+            slot.remembered = true;
+            self.remembered.push(ptr);
+        }
     }
 
     pub fn get_captured(&'_ self, index: usize) -> Option<&'_ GribValue> {
@@ -32,6 +48,7 @@ impl Gc {
         &'a mut self,
         ptr: usize,
     ) -> Option<&'a mut HeapSlot> {
+        self.write_barrier(ptr);
         self.heap
             .get_mut(ptr)
             .and_then(|marked| marked.value.as_mut())
@@ -63,6 +80,7 @@ impl Gc {
     }
 
     pub fn set_heap_val_at(&mut self, value: HeapValue, ptr: usize) {
+        self.write_barrier(ptr);
         self.heap[ptr].value = MemSlot::Value(value).into();
     }
 
@@ -96,6 +114,28 @@ impl Gc {
         }
     }
 
+    pub fn try_get_socket(&'_ self, val: impl Into<GribValue>) -> Option<&'_ Socket> {
+        if let Some(HeapValue::Socket(ref s)) = val.into().ptr().and_then(|ptr| self.heap_val(ptr))
+        {
+            Some(s)
+        } else {
+            None
+        }
+    }
+
+    pub fn try_get_generator_mut(
+        &'_ mut self,
+        val: impl Into<GribValue>,
+    ) -> Option<&'_ mut StdRng> {
+        if let Some(HeapValue::Generator(ref mut rng)) =
+            val.into().ptr().and_then(move |ptr| self.heap_val_mut(ptr))
+        {
+            Some(rng.as_mut())
+        } else {
+            None
+        }
+    }
+
     pub fn try_get_hash(&'_ self, val: impl Into<GribValue>) -> Option<&'_ HashValue> {
         if let Some(HeapValue::Hash(ref h)) = val.into().ptr().and_then(|ptr| self.heap_val(ptr)) {
             Some(h)
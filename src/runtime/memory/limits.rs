@@ -0,0 +1,117 @@
+use super::slot::HeapSlot;
+use super::Runtime;
+use runtime::error::{fatal, Limit, RuntimeErrorKind};
+use runtime::host::{HostFunction, HostRegistry};
+use runtime::native_fn::NativeFunction;
+use std::time::{Duration, Instant};
+
+/// How many steps run between checks of the clock
+const TIME_CHECK_INTERVAL: u64 = 1024;
+
+/// Bounds on the resources a program can use.  Exceeding one stops the
+/// program with an error that can't be caught.  `None` means unlimited
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Limits {
+    /// The most instructions the interpreter can execute
+    pub steps: Option<u64>,
+    /// The longest the program can run for
+    pub time: Option<Duration>,
+    /// The most heap slots that can hold values at once
+    pub heap_slots: Option<usize>,
+    /// The most function calls that can be nested
+    pub call_depth: Option<usize>,
+}
+
+impl Runtime {
+    /// Resets the step count and clock.  A session's limits apply to each input separately
+    pub(in runtime) fn start_limits(&mut self) {
+        self.steps = 0;
+        self.deadline = self.limits.time.map(|time| Instant::now() + time);
+        self.schedule_check();
+    }
+
+    /// Counts instructions that have been executed
+    #[inline]
+    pub(in runtime) fn step(&mut self, count: u64) {
+        self.steps += count;
+        if self.steps >= self.next_check {
+            self.check_limits();
+        }
+    }
+
+    fn check_limits(&mut self) {
+        if let Some(max) = self.limits.steps.filter(|&max| self.steps > max) {
+            fatal(RuntimeErrorKind::LimitExceeded(Limit::Steps(max)));
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.time_exceeded();
+        }
+        self.schedule_check();
+    }
+
+    /// How long native functions that block can wait before the time limit
+    /// runs out, or None if there's no time limit
+    pub(in runtime) fn remaining_time(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Stops the program for running past its time limit
+    pub(in runtime) fn time_exceeded(&self) -> ! {
+        let time = self.limits.time.unwrap_or_default();
+        fatal(RuntimeErrorKind::LimitExceeded(Limit::Time(time)));
+    }
+
+    /// Finds the step at which the limits next have to be checked
+    fn schedule_check(&mut self) {
+        let steps = self.limits.steps.map_or(u64::MAX, |max| max + 1);
+        let time = match self.deadline {
+            Some(_) => self.steps + TIME_CHECK_INTERVAL,
+            None => u64::MAX,
+        };
+        self.next_check = steps.min(time);
+    }
+
+    /// Makes room for an allocation within the heap limit, collecting
+    /// garbage first in case enough of the heap is unreachable
+    pub(in runtime::memory) fn check_heap(&mut self, pending: Option<&HeapSlot>) {
+        let max = match self.limits.heap_slots {
+            Some(max) if self.live_slots() >= max => max,
+            _ => return,
+        };
+
+        self.collect(false, pending);
+        if self.live_slots() >= max {
+            fatal(RuntimeErrorKind::LimitExceeded(Limit::HeapSlots(max)));
+        }
+    }
+
+    pub(in runtime::memory) fn check_call_depth(&self) {
+        if let Some(max) = self
+            .limits
+            .call_depth
+            .filter(|&max| self.frames.len() >= max)
+        {
+            fatal(RuntimeErrorKind::LimitExceeded(Limit::CallDepth(max)));
+        }
+    }
+
+    /// Stops the program if it calls a function that isn't allowed in the sandbox
+    pub(in runtime) fn check_sandbox(&self, function: &NativeFunction) {
+        if self.sandboxed && function.escapes_sandbox() {
+            let name = format!("{}.{}", function.mod_name(), function.fn_name());
+            fatal(RuntimeErrorKind::Sandboxed(name));
+        }
+    }
+
+    /// Stops the program if it calls a host function that escapes the sandbox
+    pub(in runtime) fn check_host_sandbox(&self, host: &HostRegistry, function: HostFunction) {
+        if self.sandboxed && host.escapes_sandbox(function) {
+            let (package, name) = host.name_of(function);
+            fatal(RuntimeErrorKind::Sandboxed(format!("{}.{}", package, name)));
+        }
+    }
+}
@@ -2,95 +2,132 @@ use super::slot::*;
 use super::*;
 use runtime::values::{AccessFunc, Callable, GribValue, HashPropertyValue, HeapValue};
 
-pub fn mark_heap(gc: &mut Gc, ind: usize) {
-    use self::{HeapValue::*, MemSlot::*};
+/// Pushes the heap slots that a value refers to onto `out`
+pub fn value_edges(value: &GribValue, out: &mut Vec<usize>) {
+    match value {
+        GribValue::HeapValue(ptr) | GribValue::String(GribString::Heap(ptr)) => out.push(*ptr),
+        GribValue::Callable(Callable::Lambda { binding, stack, .. }) => {
+            out.extend(binding.iter().chain(stack));
+        }
+        GribValue::Callable(Callable::Method { receiver, .. }) => out.push(*receiver),
+        GribValue::Error(inner) => value_edges(inner, out),
+        _ => {}
+    }
+}
 
-    let Markable { marked, ref value } = &mut gc.heap[ind];
+pub fn stack_edges(slot: &StackSlot, out: &mut Vec<usize>) {
+    match slot {
+        StackSlot::Captured(ind) => out.push(*ind),
+        StackSlot::Value(val) => value_edges(val, out),
+    }
+}
+
+fn access_edges(func: &AccessFunc, out: &mut Vec<usize>) {
+    match func {
+        AccessFunc::Captured(ind) => out.push(*ind),
+        AccessFunc::Callable { stack, .. } => out.extend(stack),
+        AccessFunc::Static(val) => value_edges(val, out),
+    }
+}
 
-    // Don't bother with marked/empty slots
-    if let Some(value) = value.as_ref().filter(|_| !*marked) {
-        let mut marked_stack = Vec::new();
-        let mut marked_heap = Vec::new();
-        let mut marked_func = Vec::new();
+/// Pushes the heap slots that the contents of a slot refer to onto `out`
+pub fn slot_edges(slot: &HeapSlot, out: &mut Vec<usize>) {
+    use self::{HeapValue::*, MemSlot::*};
 
-        match value {
-            Captured(val) => marked_stack.push(val.clone()),
-            Value(Array(arr)) => {
-                for i in arr.iter() {
-                    marked_stack.push(i.clone());
-                }
+    match slot {
+        Captured(val) => value_edges(val, out),
+        Value(Array(arr)) => {
+            for val in arr {
+                value_edges(val, out);
             }
-            Value(CapturedStack(stack)) => {
-                for val in stack {
-                    match val {
-                        StackSlot::Captured(index) => marked_heap.push(*index),
-                        StackSlot::Value(val) => marked_stack.push(val.clone()),
-                    }
-                }
+        }
+        Value(CapturedStack(stack)) => {
+            for slot in stack {
+                stack_edges(slot, out);
             }
-            Value(Hash(hash)) => {
-                for (key, value) in hash.iter() {
-                    marked_stack.push(key.clone().into());
-                    match value {
-                        HashPropertyValue::Value(val) => marked_stack.push(val.clone()),
-                        HashPropertyValue::AutoProp { get, set } => {
-                            if let Some(f1) = get {
-                                marked_func.push(f1.clone());
-                            }
-                            if let Some(f2) = set {
-                                marked_func.push(f2.clone());
-                            }
+        }
+        Value(Hash(hash)) => {
+            for (key, value) in hash.iter() {
+                if let GribString::Heap(ptr) = key {
+                    out.push(*ptr);
+                }
+                match value {
+                    HashPropertyValue::Value(val) => value_edges(val, out),
+                    HashPropertyValue::AutoProp { get, set } => {
+                        for func in get.iter().chain(set) {
+                            access_edges(func, out);
                         }
                     }
                 }
             }
-            Value(String(_)) => {}
         }
+        Value(String(_)) | Value(Socket(_)) | Value(Generator(_)) => {}
+    }
+}
 
-        *marked = true;
+/// Marks every slot reachable from the roots it's given.  Slots waiting
+/// to be traced are kept on a worklist rather than the native stack, so
+/// long chains of objects can't overflow it
+pub struct Marker {
+    pending: Vec<usize>,
+    /// Minor collections only trace the young generation
+    minor: bool,
+}
 
-        for value in marked_stack {
-            mark_stack(gc, &value);
+impl Marker {
+    pub fn new(minor: bool) -> Self {
+        Self {
+            pending: Vec::new(),
+            minor,
         }
+    }
 
-        for value in marked_heap {
-            mark_heap(gc, value);
-        }
+    pub fn root(&mut self, value: &GribValue) {
+        value_edges(value, &mut self.pending);
+    }
 
-        for value in marked_func {
-            match value {
-                AccessFunc::Captured(ind) => mark_heap(gc, ind),
-                AccessFunc::Callable { stack, .. } => {
-                    if let Some(ind) = stack {
-                        mark_heap(gc, ind);
-                    }
-                }
-                AccessFunc::Static(val) => mark_stack(gc, &val),
-            }
-        }
+    pub fn root_slot(&mut self, slot: &StackSlot) {
+        stack_edges(slot, &mut self.pending);
+    }
+
+    /// Treats everything the contents of a slot refer to as roots
+    /// without marking the slot itself
+    pub fn root_contents(&mut self, slot: &HeapSlot) {
+        slot_edges(slot, &mut self.pending);
     }
-}
 
-pub fn mark_stack(gc: &mut Gc, obj: &GribValue) {
-    match obj {
-        GribValue::HeapValue(heap) | GribValue::String(GribString::Heap(heap)) => {
-            mark_heap(gc, *heap)
+    pub fn run(mut self, gc: &mut Gc) {
+        while let Some(ind) = self.pending.pop() {
+            let slot = match gc.heap.get_mut(ind) {
+                Some(slot) if !slot.marked && !(self.minor && slot.old) => slot,
+                _ => continue,
+            };
+
+            // Don't bother with empty slots
+            if let Some(value) = &slot.value {
+                slot.marked = true;
+                slot_edges(value, &mut self.pending);
+            }
         }
-        GribValue::Callable(callable) => mark_function(gc, callable),
-        _ => {}
     }
 }
 
-fn mark_function(gc: &mut Gc, fnc: &Callable) {
-    match fnc {
-        Callable::Lambda { binding, stack, .. } => {
-            if let Some(ind) = binding {
-                mark_heap(gc, *ind);
-            }
-            if let Some(ind) = stack {
-                mark_heap(gc, *ind);
+impl Gc {
+    /// Finds the slots reachable from the given ones without marking them,
+    /// which are the slots that a full collection would keep
+    pub fn reachable(&self, mut pending: Vec<usize>) -> Vec<bool> {
+        let mut reached = vec![false; self.heap.len()];
+        while let Some(ind) = pending.pop() {
+            let value = match self.heap.get(ind) {
+                Some(slot) if !reached[ind] => &slot.value,
+                _ => continue,
+            };
+
+            if let Some(value) = value {
+                reached[ind] = true;
+                slot_edges(value, &mut pending);
             }
         }
-        _ => {}
+        reached
     }
 }
@@ -1,59 +1,297 @@
 pub(in runtime::memory) mod heap;
+mod limits;
 mod mark;
 pub(in runtime::memory) mod slot;
+mod snapshot;
 pub(in runtime::memory) mod stack;
+mod stats;
 
 pub use self::heap::Gc;
+pub use self::limits::Limits;
 pub use self::slot::StackSlot;
+pub use self::snapshot::HeapSnapshot;
 pub use self::stack::Stack;
+pub use self::stats::{GcReport, GcStats, HeapCensus};
 
 use self::mark::*;
+use self::snapshot::{RootKind, SnapshotRoot};
 use ast::node::{Param, Parameters};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use runtime::console::Console;
+use runtime::debug::DebugState;
+use runtime::error::{fatal, RuntimeErrorKind};
+use runtime::exec::{CallFrame, CodeCache};
 use runtime::memory::slot::*;
 use runtime::values::{GribString, GribValue, HeapValue};
+use std::time::Instant;
+
+/// The most variables and nested calls a program can have by default
+pub const DEFAULT_STACK_LIMIT: usize = 100_000;
+/// The fewest allocations between garbage collections by default
+pub const DEFAULT_GC_THRESHOLD: usize = 1000;
+
+/// How the garbage collector divides up its work
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GcMode {
+    /// Every collection traces and sweeps the whole heap
+    Full,
+    /// Frequent minor collections sweep only the slots allocated since the
+    /// last one, promoting survivors to an old generation that's swept by
+    /// occasional full collections
+    Generational,
+}
 
 pub struct RuntimeConfig {
+    /// The fewest allocations between collections.  In generational mode
+    /// this is the size of the young generation
     pub cleanup_after: usize,
+    pub gc_mode: GcMode,
+    /// Print garbage collector statistics when the program exits
+    pub gc_stats: bool,
+    /// The most variables the Grib stack can hold, which
+    /// is also the most calls that can be nested
+    pub stack_limit: usize,
+    /// Print a stack trace when an error value is discarded by an expression statement
+    pub report_errors: bool,
+    /// Command line arguments passed to the program
+    pub args: Vec<String>,
+    pub limits: Limits,
+    /// Keep the program from touching files, the network, or the
+    /// interpreter's process, or reading its arguments and environment.
+    /// Calls to the native functions that could are stopped with an error
+    pub sandbox: bool,
+    /// Where the console package writes
+    pub console: Console,
+    /// Makes the random functions return the same values every run
+    pub seed: Option<u64>,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            cleanup_after: DEFAULT_GC_THRESHOLD,
+            gc_mode: GcMode::Generational,
+            gc_stats: false,
+            stack_limit: DEFAULT_STACK_LIMIT,
+            report_errors: false,
+            args: Vec::new(),
+            limits: Limits::default(),
+            sandbox: false,
+            console: Console::Std,
+            seed: None,
+        }
+    }
 }
 
 pub struct Runtime {
     pub gc: Gc,
     pub stack: Stack,
+    pub frames: Vec<CallFrame>,
+    /// Temporary values used by the bytecode interpreter
+    pub operands: Vec<GribValue>,
+    /// A value that is being thrown to a catch block
+    pub(in runtime) thrown: Option<GribValue>,
+    /// The number of chunks running on the native stack.  Only native
+    /// functions and accessors that call back into Grib add to it
+    pub(in runtime) nested_runs: usize,
+    pub(in runtime) code: CodeCache,
     free_pointers: Vec<usize>,
-    allocations: usize,
-    max_allocations: usize,
+    /// Slots allocated since the last collection in generational mode
+    young: Vec<usize>,
+    gc_mode: GcMode,
+    min_collection: usize,
+    /// The live slot count that triggers the next full collection
+    next_collection: usize,
+    pub gc_stats: GcStats,
+    print_gc_stats: bool,
+    pub(in runtime) report_errors: bool,
+    pub args: Vec<String>,
+    limits: Limits,
+    /// Instructions executed since the limits were started
+    steps: u64,
+    /// The step count at which the limits are checked next
+    next_check: u64,
+    deadline: Option<Instant>,
+    sandboxed: bool,
+    pub(in runtime) debug: Option<DebugState>,
+    pub(in runtime) console: Console,
+    pub(in runtime) rng: StdRng,
 }
 
 impl Runtime {
     pub fn new(config: RuntimeConfig) -> Self {
         Self {
             gc: Gc::new(),
-            stack: Stack::new(),
+            stack: Stack::new(config.stack_limit),
+            frames: Vec::new(),
+            operands: Vec::new(),
+            thrown: None,
+            nested_runs: 0,
+            code: CodeCache::default(),
             free_pointers: Vec::new(),
-            allocations: 0,
-            max_allocations: config.cleanup_after,
+            young: Vec::new(),
+            gc_mode: config.gc_mode,
+            min_collection: config.cleanup_after,
+            next_collection: config.cleanup_after,
+            gc_stats: GcStats::default(),
+            print_gc_stats: config.gc_stats,
+            report_errors: config.report_errors,
+            args: config.args,
+            limits: config.limits,
+            steps: 0,
+            next_check: u64::MAX,
+            deadline: None,
+            sandboxed: config.sandbox,
+            debug: None,
+            console: config.console,
+            rng: match config.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
         }
     }
 
+    /// Runs a full collection over the whole heap
     pub fn clean(&mut self) {
+        self.collect(false, None);
+    }
+
+    /// The number of heap slots holding values
+    pub fn live_slots(&self) -> usize {
+        self.gc.heap.len() - self.free_pointers.len()
+    }
+
+    /// Marks everything reachable from the stack along with `pending`, a
+    /// value that's about to be allocated, then frees what wasn't reached.
+    /// Minor collections only free young slots and promote the rest
+    fn collect(&mut self, minor: bool, pending: Option<&HeapSlot>) {
+        let start = Instant::now();
+        let free = self.free_pointers.len();
+        let mut marker = Marker::new(minor);
         for slot in self.stack.iter() {
-            match slot {
-                StackSlot::Captured(ind) => mark_heap(&mut self.gc, *ind),
-                StackSlot::Value(val) => mark_stack(&mut self.gc, val),
+            marker.root_slot(slot);
+        }
+        for val in self.operands.iter().chain(&self.thrown) {
+            marker.root(val);
+        }
+        if let Some(slot) = pending {
+            marker.root_contents(slot);
+        }
+        if minor {
+            for &ind in &self.gc.remembered {
+                if let Some(slot) = &self.gc.heap[ind].value {
+                    marker.root_contents(slot);
+                }
             }
         }
+        marker.run(&mut self.gc);
 
-        let len = self.gc.heap.len();
-        for index in 0..len {
-            if !self.gc.heap[index].marked {
+        for ind in self.gc.remembered.drain(..) {
+            self.gc.heap[ind].remembered = false;
+        }
+
+        let generational = self.gc_mode == GcMode::Generational;
+        let young = std::mem::take(&mut self.young);
+        let swept = if minor {
+            young
+        } else {
+            (0..self.gc.heap.len()).collect()
+        };
+
+        for index in swept {
+            let slot = &mut self.gc.heap[index];
+            // Empty slots are either already free or reserved
+            if slot.value.is_none() {
+                continue;
+            }
+
+            if slot.marked {
+                slot.marked = false;
+                slot.old = generational;
+            } else {
                 self.gc.remove(index);
                 self.free_pointers.push(index);
             }
+        }
 
-            self.gc.heap[index].marked = false;
+        if !minor {
+            let live = self.live_slots();
+            self.next_collection = live + live.max(self.min_collection);
+        }
+
+        let freed = self.free_pointers.len() - free;
+        self.gc_stats.record(minor, freed, start.elapsed());
+    }
+
+    pub fn gc_report(&self) -> GcReport<'_> {
+        GcReport {
+            stats: &self.gc_stats,
+            census: self.census(),
         }
     }
 
+    /// Counts what's in the heap, telling live slots apart from
+    /// garbage by tracing from the same roots as a collection
+    pub fn census(&self) -> HeapCensus {
+        let mut roots = Vec::new();
+        for slot in self.stack.iter() {
+            stack_edges(slot, &mut roots);
+        }
+        for val in self.operands.iter().chain(&self.thrown) {
+            value_edges(val, &mut roots);
+        }
+        self.gc.census(&self.gc.reachable(roots))
+    }
+
+    /// Captures the heap along with the roots on the stacks the runtime keeps
+    pub fn heap_snapshot(&self) -> HeapSnapshot {
+        let stack = self
+            .stack
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| SnapshotRoot::stack(index, slot));
+        let operands = self
+            .operands
+            .iter()
+            .enumerate()
+            .map(|(index, val)| SnapshotRoot::value(RootKind::Operand, index, val));
+        let thrown = self
+            .thrown
+            .iter()
+            .map(|val| SnapshotRoot::value(RootKind::Thrown, 0, val));
+
+        self.gc.snapshot(stack.chain(operands).chain(thrown))
+    }
+
+    /// Prints the garbage collector's statistics if they were asked for
+    pub fn exit_report(&self) {
+        if self.print_gc_stats {
+            eprint!("{}", self.gc_report());
+        }
+    }
+
+    /// Collects garbage if enough has been allocated.  Full collections
+    /// wait for the heap to double in size since the last one
+    fn maybe_collect(&mut self, pending: Option<&HeapSlot>) {
+        if self.gc_mode == GcMode::Generational && self.young.len() >= self.min_collection {
+            self.collect(true, pending);
+        }
+        if self.live_slots() >= self.next_collection {
+            self.collect(false, pending);
+        }
+    }
+
+    /// Records a function call, failing with a stack overflow
+    /// once calls are nested as deeply as the stack limit allows
+    pub fn push_frame(&mut self, frame: CallFrame) {
+        self.check_call_depth();
+        if self.frames.len() >= self.stack.limit() {
+            fatal(RuntimeErrorKind::StackOverflow);
+        }
+        self.frames.push(frame);
+    }
+
     pub fn get_offset(&'_ self, offset: usize) -> Option<&'_ GribValue> {
         match self.stack.offset_slot(offset) {
             Some(StackSlot::Value(value)) => Some(value),
@@ -70,31 +308,25 @@ impl Runtime {
         }
     }
 
-    fn allocation_overflow(&self) -> bool {
-        self.allocations > self.max_allocations
-    }
-
     fn alloc(&mut self, value: impl Into<Option<HeapSlot>>) -> usize {
-        let value = Markable {
-            value: value.into(),
-            marked: false,
-        };
+        let value = value.into();
+        self.maybe_collect(value.as_ref());
+        self.check_heap(value.as_ref());
+        let value = Markable::new(value);
 
-        if self.allocation_overflow() {
-            self.clean();
-            self.allocations = 0;
-        }
-
-        self.allocations += 1;
-
-        if let Some(index) = self.free_pointers.pop() {
+        let index = if let Some(index) = self.free_pointers.pop() {
             self.gc.heap[index] = value;
             index
         } else {
             let index = self.gc.heap.len();
             self.gc.heap.push(value);
             index
+        };
+
+        if self.gc_mode == GcMode::Generational {
+            self.young.push(index);
         }
+        index
     }
 
     pub fn alloc_heap(&mut self, value: HeapValue) -> usize {
@@ -124,6 +356,23 @@ impl Runtime {
         }
     }
 
+    /// Allocates an array of strings.  The array is allocated first and kept
+    /// on the operand stack so each string is reachable once it's allocated
+    pub fn alloc_str_array(&mut self, strings: impl IntoIterator<Item = String>) -> GribValue {
+        let ptr = self.alloc_heap(HeapValue::Array(Vec::new()));
+        self.operands.push(GribValue::HeapValue(ptr));
+
+        for s in strings {
+            let string = GribValue::String(self.alloc_str(s));
+            if let Some(arr) = self.gc.try_get_array_mut(ptr) {
+                arr.push(string);
+            }
+        }
+
+        self.operands.pop();
+        GribValue::HeapValue(ptr)
+    }
+
     pub fn capture_stack(&mut self, to_capture: &Vec<usize>) -> Option<usize> {
         if to_capture.is_empty() {
             return None;
@@ -131,11 +380,10 @@ impl Runtime {
 
         let mut heap_stack = Vec::with_capacity(to_capture.len());
         for &offset in to_capture {
-            let slot = self
-                .stack
-                .offset_slot(offset)
-                .expect("INVALID OFFSET")
-                .clone();
+            let slot = match self.stack.offset_slot(offset) {
+                Some(slot) => slot.clone(),
+                None => fatal(RuntimeErrorKind::InvalidOffset(offset)),
+            };
             heap_stack.push(slot);
         }
 
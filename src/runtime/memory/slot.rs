@@ -4,6 +4,21 @@ use runtime::values::{GribValue, HeapValue};
 pub struct Markable<T> {
     pub value: T,
     pub marked: bool,
+    /// Whether the slot has survived a generational collection
+    pub old: bool,
+    /// Whether the slot is in the remembered set
+    pub remembered: bool,
+}
+
+impl<T> Markable<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            marked: false,
+            old: false,
+            remembered: false,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
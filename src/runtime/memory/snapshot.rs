@@ -0,0 +1,122 @@
+use super::heap::Gc;
+use super::mark::{slot_edges, stack_edges, value_edges};
+use super::slot::*;
+use runtime::values::{GribValue, HeapValue};
+use std::mem;
+
+/// Where a root of the heap was found
+#[derive(Clone, Copy, Debug, Serialize, PartialEq)]
+pub enum RootKind {
+    /// A variable on the Grib stack
+    Stack,
+    /// A temporary value used by the bytecode interpreter
+    Operand,
+    /// A value being thrown to a catch block
+    Thrown,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct SnapshotRoot {
+    pub kind: RootKind,
+    /// The root's position in the stack it came from
+    pub index: usize,
+    pub references: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct SnapshotSlot {
+    pub index: usize,
+    pub kind: &'static str,
+    /// Roughly how many bytes the slot retains by itself
+    pub size: usize,
+    /// Whether the slot is in the old generation
+    pub old: bool,
+    /// The slots that this one keeps alive
+    pub references: Vec<usize>,
+}
+
+/// Every live slot in the heap along with the edges the garbage collector
+/// follows between them, which is enough to find what retains a slot
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct HeapSnapshot {
+    pub roots: Vec<SnapshotRoot>,
+    pub slots: Vec<SnapshotSlot>,
+}
+
+impl SnapshotRoot {
+    pub fn stack(index: usize, slot: &StackSlot) -> Self {
+        let mut references = Vec::new();
+        stack_edges(slot, &mut references);
+        Self {
+            kind: RootKind::Stack,
+            index,
+            references,
+        }
+    }
+
+    pub fn value(kind: RootKind, index: usize, value: &GribValue) -> Self {
+        let mut references = Vec::new();
+        value_edges(value, &mut references);
+        Self {
+            kind,
+            index,
+            references,
+        }
+    }
+}
+
+fn slot_kind(slot: &HeapSlot) -> &'static str {
+    match slot {
+        MemSlot::Captured(_) => "captured",
+        MemSlot::Value(HeapValue::Array(_)) => "array",
+        MemSlot::Value(HeapValue::Hash(_)) => "hash",
+        MemSlot::Value(HeapValue::String(_)) => "string",
+        MemSlot::Value(HeapValue::CapturedStack(_)) => "capturedStack",
+        MemSlot::Value(HeapValue::Socket(_)) => "socket",
+        MemSlot::Value(HeapValue::Generator(_)) => "generator",
+    }
+}
+
+impl Gc {
+    /// Records every slot that can be reached from the roots, leaving out
+    /// garbage that hasn't been collected yet.  Roots without
+    /// references to the heap are left out too
+    pub fn snapshot(&self, roots: impl IntoIterator<Item = SnapshotRoot>) -> HeapSnapshot {
+        let roots = roots
+            .into_iter()
+            .filter(|root| !root.references.is_empty())
+            .collect::<Vec<_>>();
+        let edges = roots
+            .iter()
+            .flat_map(|root| root.references.iter().copied());
+        let reachable = self.reachable(edges.collect());
+
+        let slots = self
+            .heap
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| reachable[index])
+            .filter_map(|(index, marked)| {
+                let slot = marked.value.as_ref()?;
+                let mut references = Vec::new();
+                slot_edges(slot, &mut references);
+
+                let size = mem::size_of::<MarkedSlot>()
+                    + match slot {
+                        MemSlot::Value(value) => value.size(),
+                        MemSlot::Captured(_) => 0,
+                    };
+
+                Some(SnapshotSlot {
+                    index,
+                    kind: slot_kind(slot),
+                    size,
+                    old: marked.old,
+                    references,
+                })
+            })
+            .collect();
+
+        HeapSnapshot { roots, slots }
+    }
+}
@@ -1,49 +1,46 @@
+use runtime::error::{fatal, RuntimeErrorKind};
 use runtime::memory::slot::*;
 use std::fmt::{Debug, Error as DebugError, Formatter};
-const STACK_SIZE: usize = 5000;
 
+/// Holds the variables of every active function.  Grows as
+/// needed until it reaches its limit, which is a stack overflow
 pub struct Stack {
-    stack_size: usize,
-    stack: [Option<StackSlot>; STACK_SIZE],
+    limit: usize,
+    stack: Vec<StackSlot>,
 }
 
-const EMPTY_STACK_SLOT: Option<StackSlot> = None;
-
 impl Stack {
-    pub fn new() -> Self {
+    pub fn new(limit: usize) -> Self {
         Self {
-            stack_size: 0,
-            stack: [EMPTY_STACK_SLOT; STACK_SIZE],
+            limit,
+            stack: Vec::new(),
         }
     }
 
     pub fn len(&self) -> usize {
-        self.stack_size
+        self.stack.len()
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
     }
 
     pub fn add(&mut self, value: impl Into<StackSlot>) -> usize {
-        let value = value.into();
-        if self.stack_size < STACK_SIZE {
-            self.stack[self.stack_size] = Some(value);
-            let ptr = self.stack_size;
-            self.stack_size += 1;
-            ptr
+        if self.stack.len() < self.limit {
+            self.stack.push(value.into());
+            self.stack.len() - 1
         } else {
-            panic!("Grib stack overflow");
+            fatal(RuntimeErrorKind::StackOverflow);
         }
     }
 
     pub fn pop(&mut self) {
-        if self.stack_size > 0 {
-            self.stack_size -= 1;
-            self.stack[self.stack_size] = None;
-        }
+        self.stack.pop();
     }
 
     pub fn pop_stack(&mut self, count: usize) {
-        for _ in 0..count {
-            self.pop();
-        }
+        let len = self.stack.len().saturating_sub(count);
+        self.stack.truncate(len);
     }
 
     fn offset_calc(&self, offset: usize) -> Option<usize> {
@@ -51,22 +48,16 @@ impl Stack {
     }
 
     pub fn offset_slot(&'_ self, offset: usize) -> Option<&'_ StackSlot> {
-        self.offset_calc(offset)
-            .and_then(|ind| self.stack.get(ind))
-            .and_then(|o| o.as_ref())
+        self.offset_calc(offset).and_then(|ind| self.stack.get(ind))
     }
 
     pub fn offset_slot_mut(&'_ mut self, offset: usize) -> Option<&'_ mut StackSlot> {
         self.offset_calc(offset)
             .and_then(move |ind| self.stack.get_mut(ind))
-            .and_then(|o| o.as_mut())
     }
 
     pub fn iter<'a>(&'a self) -> impl Iterator<Item = &'a StackSlot> {
-        self.stack
-            .iter()
-            .take(self.stack_size)
-            .filter_map(|s| s.as_ref())
+        self.stack.iter()
     }
 }
 
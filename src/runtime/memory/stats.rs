@@ -0,0 +1,159 @@
+use super::heap::Gc;
+use super::slot::*;
+use runtime::values::HeapValue;
+use std::fmt::{self, Display, Formatter};
+use std::mem;
+use std::time::Duration;
+
+/// Running totals kept by the garbage collector
+#[derive(Clone, Debug, Default)]
+pub struct GcStats {
+    pub minor_collections: usize,
+    pub full_collections: usize,
+    /// Slots freed by every collection so far
+    pub freed: usize,
+    pub total_pause: Duration,
+    pub max_pause: Duration,
+    pub last_pause: Duration,
+}
+
+impl GcStats {
+    pub fn collections(&self) -> usize {
+        self.minor_collections + self.full_collections
+    }
+
+    pub fn record(&mut self, minor: bool, freed: usize, pause: Duration) {
+        if minor {
+            self.minor_collections += 1;
+        } else {
+            self.full_collections += 1;
+        }
+        self.freed += freed;
+        self.total_pause += pause;
+        self.max_pause = self.max_pause.max(pause);
+        self.last_pause = pause;
+    }
+}
+
+/// The live slots in the heap, counted by the kind of value they hold.
+/// Slots are live if the program can still reach them, so garbage that
+/// hasn't been collected yet is counted separately
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeapCensus {
+    pub arrays: usize,
+    pub hashes: usize,
+    pub strings: usize,
+    pub captured_stacks: usize,
+    pub sockets: usize,
+    pub generators: usize,
+    /// Variables that were captured by a closure
+    pub captured: usize,
+    /// Roughly how many bytes the live slots retain
+    pub bytes: usize,
+    /// Slots holding values that the next full collection will free
+    pub garbage: usize,
+    /// Slots in the heap, including free ones
+    pub capacity: usize,
+}
+
+impl HeapCensus {
+    pub fn live(&self) -> usize {
+        self.arrays
+            + self.hashes
+            + self.strings
+            + self.captured_stacks
+            + self.sockets
+            + self.generators
+            + self.captured
+    }
+}
+
+impl Gc {
+    /// Counts the slots that `reachable` is set for, along with the garbage
+    pub fn census(&self, reachable: &[bool]) -> HeapCensus {
+        let mut census = HeapCensus {
+            capacity: self.heap.len(),
+            bytes: self.heap.len() * mem::size_of::<MarkedSlot>(),
+            ..HeapCensus::default()
+        };
+
+        for (index, slot) in self.heap.iter().enumerate() {
+            let slot = match &slot.value {
+                Some(slot) if reachable[index] => slot,
+                Some(_) => {
+                    census.garbage += 1;
+                    continue;
+                }
+                None => continue,
+            };
+            let value = match slot {
+                MemSlot::Captured(_) => {
+                    census.captured += 1;
+                    continue;
+                }
+                MemSlot::Value(value) => value,
+            };
+
+            census.bytes += value.size();
+            match value {
+                HeapValue::Array(_) => census.arrays += 1,
+                HeapValue::Hash(_) => census.hashes += 1,
+                HeapValue::String(_) => census.strings += 1,
+                HeapValue::CapturedStack(_) => census.captured_stacks += 1,
+                HeapValue::Socket(_) => census.sockets += 1,
+                HeapValue::Generator(_) => census.generators += 1,
+            }
+        }
+
+        census
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.
+}
+
+/// What `--gc-stats` prints when a program exits
+pub struct GcReport<'a> {
+    pub stats: &'a GcStats,
+    pub census: HeapCensus,
+}
+
+impl Display for GcReport<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let (stats, census) = (self.stats, &self.census);
+        writeln!(
+            f,
+            "gc: {} collections ({} minor, {} full) freed {} slots",
+            stats.collections(),
+            stats.minor_collections,
+            stats.full_collections,
+            stats.freed
+        )?;
+        writeln!(
+            f,
+            "gc: paused {:.3}ms in total, {:.3}ms at most",
+            millis(stats.total_pause),
+            millis(stats.max_pause)
+        )?;
+        writeln!(
+            f,
+            "gc: {} of {} slots live retaining ~{} bytes, {} garbage",
+            census.live(),
+            census.capacity,
+            census.bytes,
+            census.garbage
+        )?;
+        writeln!(
+            f,
+            "gc: {} arrays, {} hashes, {} strings, {} captured stacks, {} sockets, {} generators, {} captured variables",
+            census.arrays,
+            census.hashes,
+            census.strings,
+            census.captured_stacks,
+            census.sockets,
+            census.generators,
+            census.captured
+        )
+    }
+}
@@ -1,7 +1,13 @@
+pub mod console;
+pub mod debug;
+pub mod error;
 pub(in runtime) mod exec;
+pub mod host;
 pub(in runtime) mod memory;
 pub mod native_fn;
 pub mod values;
 
-pub use self::exec::execute;
-pub use self::memory::RuntimeConfig;
+pub use self::exec::{debug, execute, Session};
+pub use self::memory::{
+    GcMode, Limits, Runtime, RuntimeConfig, DEFAULT_GC_THRESHOLD, DEFAULT_STACK_LIMIT,
+};
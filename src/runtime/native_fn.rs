@@ -1,20 +1,56 @@
 use ast::node::Program;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use runtime::debug::{self, Output};
+use runtime::error::{fatal, RuntimeErrorKind};
 use runtime::memory::Runtime;
-use runtime::values::{Callable, GribKey, GribString, GribValue, HeapValue, KnownIndex};
+use runtime::values::{
+    timed_out, Callable, GribKey, GribString, GribValue, HashValue, HeapValue, KnownIndex, Socket,
+    Value, READ_SIZE,
+};
 use std::borrow::Borrow;
 use std::collections::HashSet;
 use std::{
-    fs,
-    io::{self, Read, Write},
+    env, fs,
+    io::{self, Write},
     path::Path,
+    sync::mpsc,
+    thread,
 };
 
+/// Describes a native function's name and parameters
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct NativeSignature {
+    pub name: &'static str,
+    /// Other names the function can be imported by
+    pub aliases: &'static [&'static str],
+    pub params: &'static [&'static str],
+    /// Whether all arguments are collected into the last parameter
+    pub variadic: bool,
+}
+
+/// The functions in a native package
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct PackageManifest {
+    pub name: &'static str,
+    pub functions: &'static [NativeSignature],
+}
+
 macro_rules! native_obj {
     ($name:ident | $pkg:ident {
-        $(
-            $enum:ident -> $str:expr
-        ),* $(,)*
+        $( $enum:ident -> $str:expr ),* $(,)*
+    } internal {
+        $( $ienum:ident -> $istr:expr ),* $(,)*
     }) => {
+        native_obj!(@impl $name | $pkg
+            [$( $enum -> $str, )* $( $ienum -> $istr, )*]
+            [$( $enum -> $str, )*]);
+    };
+    (@impl $name:ident | $pkg:ident
+        [$( $enum:ident -> $str:expr, )*]
+        [$( $penum:ident -> $pstr:expr, )*]
+    ) => {
         #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
         pub enum $name {
             $( $enum ( $enum ), )*
@@ -58,6 +94,23 @@ macro_rules! native_obj {
 
         impl $pkg {
             const MEMBERS: &'static [&'static str] = &[$( $str ),*];
+            /// The packages that can be imported by name
+            pub const ALL: &'static [$pkg] = &[$( $pkg::$penum ),*];
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $( Self::$enum => $str, )*
+                }
+            }
+
+            pub fn manifest(&self) -> PackageManifest {
+                PackageManifest {
+                    name: self.name(),
+                    functions: match self {
+                        $( Self::$enum => $enum::SIGNATURES, )*
+                    },
+                }
+            }
 
             pub fn raw_names(&self) -> &'static [&'static str] {
                 match self {
@@ -87,7 +140,7 @@ macro_rules! native_obj {
 
             pub fn from_str(s: &str) -> Option<Self> {
                 match s {
-                    $($str => Some(Self::$enum),)*
+                    $($pstr => Some(Self::$penum),)*
                     _ => None,
                 }
             }
@@ -100,6 +153,11 @@ macro_rules! native_obj {
 }
 
 macro_rules! native_package {
+    (@params READ_ARGS, $a:ident) => { &[stringify!($a)] };
+    (@params $($param:ident),*) => { &[$( stringify!($param) ),*] };
+    (@variadic READ_ARGS, $a:ident) => { true };
+    (@variadic $($param:ident),*) => { false };
+
     (@branch $args:ident $rt:ident $program:ident [READ_ARGS, $a:ident] $b:block) => {
         {
             fn closure( $rt: &mut Runtime, $program: &Program, $a: Vec<GribValue> ) -> GribValue $b
@@ -120,7 +178,7 @@ macro_rules! native_package {
 
     ($name:ident [$program:ident $rt:ident] {
         $(
-            $fn_name:ident [$str:expr] ($($param:ident),*) $b:block
+            $fn_name:ident [$str:expr $(, $alias:expr)*] ($($param:ident),*) $b:block
         )*
 
     }) => {
@@ -130,8 +188,16 @@ macro_rules! native_package {
         }
 
         impl $name {
-            const MEMBERS: &'static [&'static str] = &[$( $str ),*];
+            const MEMBERS: &'static [&'static str] = &[$( $str $(, $alias)* ),*];
             const FUNCTIONS: &'static [$name] = &[$( Self::$fn_name ),*];
+            const SIGNATURES: &'static [NativeSignature] = &[$(
+                NativeSignature {
+                    name: $str,
+                    aliases: &[$( $alias ),*],
+                    params: native_package!(@params $( $param ),*),
+                    variadic: native_package!(@variadic $( $param ),*),
+                }
+            ),*];
 
             pub fn fn_name(&self) -> &'static str {
                 use self::$name::*;
@@ -143,7 +209,7 @@ macro_rules! native_package {
             pub fn from_str(s: &str) -> Option<Self> {
                 use self::$name::*;
                 match s {
-                    $( $str => Some($fn_name), )*
+                    $( $str $(| $alias)* => Some($fn_name), )*
                     _ => None,
                 }
             }
@@ -159,50 +225,60 @@ macro_rules! native_package {
     };
 }
 
-fn print_values(values: Vec<GribValue>, program: &Program, runtime: &Runtime) {
+fn print_values(values: Vec<GribValue>, end: &str, program: &Program, runtime: &mut Runtime) {
+    let mut text = String::new();
     for val in values {
-        if let GribValue::Error(err) = val {
-            print!("[ERR: {}]", err.as_str(program, runtime));
-        } else {
-            print!("{}", val.as_str(program, runtime));
-        }
+        text.push_str(&val.display(program, runtime));
     }
+    text.push_str(end);
+    debug::print(runtime, &text, Output::Stdout);
 }
 
 const NO_ARRAY: &'static str = "Functon provided non-array value";
 const NO_CALLBACK: &'static str = "Function provided non-callable value";
 
+/// A line from stdin without its line ending, or None at the end of the input
+fn read_line() -> Option<String> {
+    let mut buf = String::new();
+    match io::stdin().read_line(&mut buf) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(buf.trim_end_matches(&['\n', '\r'][..]).to_string()),
+    }
+}
+
 native_package!(NativeConsolePackage[program runtime] {
     Print["print"](READ_ARGS, args) {
-        print_values(args, program, runtime);
-        if io::stdout().flush().is_err() {
+        print_values(args, "", program, runtime);
+        if runtime.console.flush().is_err() {
             GribValue::err("Error flushing STDOUT")
         } else {
             GribValue::Nil
         }
     }
     Println["println"](READ_ARGS, args) {
-        print_values(args, program, runtime);
-        println!();
+        print_values(args, "\n", program, runtime);
         GribValue::Nil
     }
     PrintError["printError"](s) {
-        if let GribValue::Error(err) = s {
-            eprintln!("[ERR: {}]", err.as_str(program, runtime));
-        } else {
-            eprintln!("{}", s.as_str(program, runtime));
-        }
+        let text = format!("{}\n", s.display(program, runtime));
+        debug::print(runtime, &text, Output::Stderr);
         GribValue::Nil
     }
-    Readline["readlineSync"]() {
-        let mut buf = String::new();
-        let mut stdin = io::stdin();
+    Readline["readLineSync", "readlineSync"]() {
+        let line = match runtime.remaining_time() {
+            None => read_line(),
+            // Reading on another thread lets the time limit stop the program while it waits
+            Some(remaining) => {
+                let (send, receive) = mpsc::channel();
+                thread::spawn(move || send.send(read_line()));
+                receive.recv_timeout(remaining).unwrap_or_else(|_| runtime.time_exceeded())
+            }
+        };
 
-        if stdin.read_to_string(&mut buf).is_err() {
-            return GribValue::Nil
+        match line {
+            Some(line) => GribValue::String(runtime.alloc_str(line)),
+            None => GribValue::Nil,
         }
-
-        GribValue::String(runtime.alloc_str(buf))
     }
 });
 
@@ -294,6 +370,44 @@ native_package!(NativeErrPackage[_program _runtime] {
     }
 });
 
+/// Whether a copied value holds something that's only equal to itself
+fn is_opaque(value: &Value) -> bool {
+    match value {
+        Value::Opaque(_) => true,
+        Value::Array(items) => items.iter().any(is_opaque),
+        Value::Hash(entries) => entries.values().any(is_opaque),
+        Value::Error(inner) => is_opaque(inner),
+        _ => false,
+    }
+}
+
+native_package!(NativeTestPackage[program runtime] {
+    Assert["assert"](condition, message) {
+        if !condition.truthy(program, &runtime.gc) {
+            let message = match message {
+                GribValue::Nil => "condition is falsy".to_string(),
+                message => message.display(program, runtime).into_owned(),
+            };
+            fatal(RuntimeErrorKind::AssertionFailed(message));
+        }
+        GribValue::Nil
+    }
+    AssertEq["assertEq"](actual, expected) {
+        if actual.exact_equals(&expected, program, &runtime.gc) {
+            return GribValue::Nil;
+        }
+
+        // Arrays and hashes are compared by their contents rather than by reference
+        let actual = Value::from_grib(&actual, runtime, program);
+        let expected = Value::from_grib(&expected, runtime, program);
+        if actual != expected || is_opaque(&actual) {
+            let message = format!("expected {}, got {}", expected, actual);
+            fatal(RuntimeErrorKind::AssertionFailed(message));
+        }
+        GribValue::Nil
+    }
+});
+
 native_package!(NativeMetaPackage[program runtime] {
     TypeOf["typeOf"](val) {
         use self::GribValue::*;
@@ -316,8 +430,50 @@ native_package!(NativeMetaPackage[program runtime] {
         runtime.clean();
         GribValue::Nil
     }
+    GcStats["gcStats"]() {
+        let census = runtime.census();
+        let stats = &runtime.gc_stats;
+        let fields = [
+            ("collections", stats.collections() as f64),
+            ("minorCollections", stats.minor_collections as f64),
+            ("fullCollections", stats.full_collections as f64),
+            ("freed", stats.freed as f64),
+            ("totalPauseMs", stats.total_pause.as_secs_f64() * 1000.),
+            ("maxPauseMs", stats.max_pause.as_secs_f64() * 1000.),
+            ("lastPauseMs", stats.last_pause.as_secs_f64() * 1000.),
+            ("liveSlots", census.live() as f64),
+            ("garbageSlots", census.garbage as f64),
+            ("heapSlots", census.capacity as f64),
+            ("bytes", census.bytes as f64),
+            ("arrays", census.arrays as f64),
+            ("hashes", census.hashes as f64),
+            ("strings", census.strings as f64),
+            ("capturedStacks", census.captured_stacks as f64),
+            ("sockets", census.sockets as f64),
+            ("generators", census.generators as f64),
+            ("captured", census.captured as f64),
+        ];
+
+        let mut hash = HashValue::new(false);
+        for (name, value) in fields.iter() {
+            let key = hash.key(GribString::Static(name), program, &runtime.gc);
+            hash.init_value(key, GribValue::Number(*value));
+        }
+        GribValue::HeapValue(runtime.alloc_heap(HeapValue::Hash(hash)))
+    }
+    HeapSnapshot["heapSnapshot"](path_val) {
+        let path_str = path_val.as_str(program, runtime).into_owned();
+        let snapshot = serde_json::to_string(&runtime.heap_snapshot())
+            .expect("Heap snapshots are always serializable");
+
+        match fs::write(&path_str, snapshot) {
+            Ok(()) => GribValue::Nil,
+            Err(e) => io_err(runtime, e),
+        }
+    }
     Exit["exit"](has_err) {
-        std::process::exit(if has_err.truthy(program, &runtime.gc) { 1 } else { 0 })
+        // Only the command line turns this into the process exiting
+        fatal(RuntimeErrorKind::Exit(if has_err.truthy(program, &runtime.gc) { 1 } else { 0 }))
     }
     BindFn["bindFn"](fnc_val, target) {
         let mut fnc = fnc_val;
@@ -339,6 +495,51 @@ native_package!(NativeMetaPackage[program runtime] {
     }
 });
 
+native_package!(NativeProcessPackage[program runtime] {
+    Args["args"]() {
+        let args = runtime.args.clone();
+        runtime.alloc_str_array(args)
+    }
+    Env["env"](name) {
+        let name = name.as_str(program, runtime).into_owned();
+        match env::var(name) {
+            Ok(val) => GribValue::String(runtime.alloc_str(val)),
+            Err(env::VarError::NotPresent) => GribValue::Nil,
+            Err(env::VarError::NotUnicode(_)) => GribValue::err("Environment variable is not valid unicode"),
+        }
+    }
+    SetEnv["setEnv"](name, value) {
+        let name = name.as_str(program, runtime).into_owned();
+        if name.is_empty() || name.contains(&['=', '\0'][..]) {
+            return GribValue::err("Invalid environment variable name");
+        }
+
+        if value.is_nil() {
+            env::remove_var(name);
+        } else {
+            let value = value.as_str(program, runtime).into_owned();
+            if value.contains('\0') {
+                return GribValue::err("Invalid environment variable value");
+            }
+            env::set_var(name, value);
+        }
+        GribValue::Nil
+    }
+    Cwd["cwd"]() {
+        env::current_dir()
+            .map(|dir| runtime.alloc_str(dir.to_string_lossy().into_owned()))
+            .map(GribValue::String)
+            .unwrap_or(GribValue::err("Could not read the current directory"))
+    }
+    ScriptPath["scriptPath"]() {
+        // Programs read from stdin have no path on disk
+        fs::canonicalize(&program.path)
+            .map(|path| runtime.alloc_str(path.to_string_lossy().into_owned()))
+            .map(GribValue::String)
+            .unwrap_or_default()
+    }
+});
+
 native_package!(NativeStrPackage[program runtime] {
     Split["split"](content, delim) {
         let delim = delim.as_str(program, runtime);
@@ -346,12 +547,7 @@ native_package!(NativeStrPackage[program runtime] {
             .split(delim.as_ref())
             .map(|s| s.to_string())
             .collect::<Vec<String>>();
-        let grib_arr = str_arr.into_iter()
-            .map(|s| runtime.alloc_str(s))
-            .map(GribValue::String)
-            .collect::<Vec<_>>();
-        let ptr = runtime.alloc_heap(HeapValue::Array(grib_arr));
-        GribValue::HeapValue(ptr)
+        runtime.alloc_str_array(str_arr)
     }
     Strlen["strlen"](obj) {
         let string = obj.as_str(program, runtime);
@@ -424,8 +620,138 @@ fn write_bytes(path: &Path, contents: &[u8], append: bool) -> GribValue {
         .unwrap_or(GribValue::err("Failed to write to file"))
 }
 
+fn io_err(runtime: &mut Runtime, e: io::Error) -> GribValue {
+    let message = runtime.alloc_str(format!("IO: {}", e));
+    GribValue::Error(GribValue::String(message).into())
+}
+
+/// Wraps a heap value in an immutable hash whose methods are bound to it
+fn method_hash<F: Clone>(
+    value: HeapValue,
+    methods: &[(&'static str, F)],
+    program: &Program,
+    runtime: &mut Runtime,
+) -> GribValue
+where
+    NativeFunction: From<F>,
+{
+    let ptr = runtime.alloc_heap(value);
+    // Keep the value reachable in case allocating the hash triggers a collection
+    runtime.stack.add(GribValue::HeapValue(ptr));
+
+    let mut hash = HashValue::new(false);
+    for (name, method) in methods {
+        let key = hash.key(GribString::Static(name), program, &runtime.gc);
+        let function = NativeFunction::from(method.clone());
+        hash.init_value(
+            key,
+            GribValue::Callable(Callable::Method {
+                function,
+                receiver: ptr,
+            }),
+        );
+    }
+
+    let hash_ptr = runtime.alloc_heap(HeapValue::Hash(hash));
+    runtime.stack.pop();
+    GribValue::HeapValue(hash_ptr)
+}
+
+fn stream_hash(socket: Socket, program: &Program, runtime: &mut Runtime) -> GribValue {
+    use self::NativeSocketPackage::*;
+    let methods = [
+        ("read", Read),
+        ("write", Write),
+        ("close", Close),
+        ("address", Address),
+    ];
+    method_hash(HeapValue::Socket(socket), &methods, program, runtime)
+}
+
+fn listener_hash(socket: Socket, program: &Program, runtime: &mut Runtime) -> GribValue {
+    use self::NativeSocketPackage::*;
+    let methods = [("accept", Accept), ("close", Close), ("address", Address)];
+    method_hash(HeapValue::Socket(socket), &methods, program, runtime)
+}
+
+macro_rules! socket_guard {
+    ($runtime:ident, $val:expr) => {
+        match $runtime.gc.try_get_socket($val) {
+            Some(socket) => socket.clone(),
+            None => return GribValue::err("IO: value is not a socket"),
+        }
+    };
+}
+
+native_package!(NativeSocketPackage[program runtime] {
+    Read["read"](socket, max_val) {
+        let socket = socket_guard!(runtime, socket);
+        let max = if max_val.is_nil() {
+            READ_SIZE
+        } else {
+            match max_val.cast_ind(program, &runtime.gc) {
+                Some(max) if max > 0 => max,
+                _ => return GribValue::err("IO: the number of bytes to read must be positive"),
+            }
+        };
+
+        match socket.read(max, runtime.remaining_time()) {
+            Ok(bytes) => {
+                let arr = bytes.into_iter().map(|b| GribValue::Number(b as f64)).collect();
+                runtime.alloc_heap(HeapValue::Array(arr)).into()
+            }
+            Err(ref e) if timed_out(e) => runtime.time_exceeded(),
+            Err(e) => io_err(runtime, e),
+        }
+    }
+    Write["write"](socket, contents) {
+        let socket = socket_guard!(runtime, socket);
+        let bytes = match runtime.gc.try_get_array(contents.clone()) {
+            Some(arr) => arr.iter()
+                .map(|val| val.cast_ind(program, &runtime.gc).unwrap_or(0).min(255) as u8)
+                .collect::<Vec<u8>>(),
+            None => contents.as_str(program, runtime).as_bytes().to_vec(),
+        };
+
+        socket.write(&bytes).map(|_| GribValue::Nil).unwrap_or_else(|e| io_err(runtime, e))
+    }
+    Accept["accept"](listener) {
+        let listener = socket_guard!(runtime, listener);
+        match listener.accept(runtime.remaining_time()) {
+            Ok(stream) => stream_hash(stream, program, runtime),
+            Err(ref e) if timed_out(e) => runtime.time_exceeded(),
+            Err(e) => io_err(runtime, e),
+        }
+    }
+    Address["address"](socket) {
+        let socket = socket_guard!(runtime, socket);
+        match socket.address() {
+            Ok(addr) => GribValue::String(runtime.alloc_str(addr)),
+            Err(e) => io_err(runtime, e),
+        }
+    }
+    Close["close"](socket) {
+        let socket = socket_guard!(runtime, socket);
+        socket.close().map(|_| GribValue::Nil).unwrap_or_else(|e| io_err(runtime, e))
+    }
+});
+
 native_package!(NativeSyncIoPackage[program runtime] {
-    ReadText["readText"](obj) {
+    NewSocket["newSocket"](addr) {
+        let addr = addr.as_str(program, runtime).into_owned();
+        match Socket::connect(&addr) {
+            Ok(socket) => stream_hash(socket, program, runtime),
+            Err(e) => io_err(runtime, e),
+        }
+    }
+    Listen["listen"](addr) {
+        let addr = addr.as_str(program, runtime).into_owned();
+        match Socket::listen(&addr) {
+            Ok(socket) => listener_hash(socket, program, runtime),
+            Err(e) => io_err(runtime, e),
+        }
+    }
+    ReadText["readFile", "readText"](obj) {
         let path_str = obj.as_str(program, runtime).into_owned();
         let path = Path::new(&path_str);
 
@@ -434,7 +760,7 @@ native_package!(NativeSyncIoPackage[program runtime] {
             .map(GribValue::String)
             .unwrap_or(GribValue::err("Error reading provided file provided to readText"))
     }
-    WriteText["writeText"](path_val, contents_val, append) {
+    WriteText["writeFile", "writeText"](path_val, contents_val, append) {
         let path_str = path_val.as_str(program, runtime).into_owned();
         let path = Path::new(&path_str);
 
@@ -474,15 +800,184 @@ native_package!(NativeSyncIoPackage[program runtime] {
         let path = Path::new(&path_str);
         GribValue::Bool(path.is_file())
     }
-    IsDir["isDir"](path_val) {
+    IsDir["isDirectory", "isDir"](path_val) {
         let path_str = path_val.as_str(program, runtime).into_owned();
         let path = Path::new(&path_str);
         GribValue::Bool(path.is_dir())
     }
     PathContents["pathContents"](path_val) {
         let path_str = path_val.as_str(program, runtime).into_owned();
-        let path = Path::new(&path_str);
-        GribValue::Bool(path.is_dir())
+        let entries = match fs::read_dir(&path_str) {
+            Ok(entries) => entries,
+            Err(e) => return io_err(runtime, e),
+        };
+
+        let mut names = Vec::new();
+        for entry in entries {
+            match entry {
+                Ok(entry) => names.push(entry.file_name().to_string_lossy().into_owned()),
+                Err(e) => return io_err(runtime, e),
+            }
+        }
+        names.sort();
+
+        runtime.alloc_str_array(names)
+    }
+});
+
+const NO_GENERATOR: &str = "Function provided non-generator value";
+
+/// Runs `f` with the generator made by `generator` in the math
+/// package, or the runtime's shared one if there isn't one
+fn with_rng<T>(
+    runtime: &mut Runtime,
+    generator: Option<GribValue>,
+    f: impl FnOnce(&mut StdRng) -> T,
+) -> Result<T, GribValue> {
+    let rng = match generator {
+        None => &mut runtime.rng,
+        Some(generator) => match runtime.gc.try_get_generator_mut(generator) {
+            Some(rng) => rng,
+            None => return Err(GribValue::err(NO_GENERATOR)),
+        },
+    };
+    Ok(f(rng))
+}
+
+/// Seeds are truncated to integers, so `seed(7)` matches `--seed 7`
+fn seed_value(program: &Program, runtime: &Runtime, n: GribValue) -> u64 {
+    n.cast_num(program, &runtime.gc) as i64 as u64
+}
+
+fn random(runtime: &mut Runtime, generator: Option<GribValue>) -> GribValue {
+    with_rng(runtime, generator, |rng| GribValue::Number(rng.gen())).unwrap_or_else(|e| e)
+}
+
+fn reseed(
+    program: &Program,
+    runtime: &mut Runtime,
+    generator: Option<GribValue>,
+    n: GribValue,
+) -> GribValue {
+    let seed = seed_value(program, runtime, n);
+    with_rng(runtime, generator, |rng| {
+        *rng = StdRng::seed_from_u64(seed);
+        GribValue::Nil
+    })
+    .unwrap_or_else(|e| e)
+}
+
+/// A whole number between `lo` and `hi`, including both
+fn rand_int(
+    program: &Program,
+    runtime: &mut Runtime,
+    generator: Option<GribValue>,
+    lo: GribValue,
+    hi: GribValue,
+) -> GribValue {
+    let lo = lo.cast_num(program, &runtime.gc).ceil();
+    let hi = hi.cast_num(program, &runtime.gc).floor();
+    if !lo.is_finite() || !hi.is_finite() || lo > hi {
+        return GribValue::err("randInt provided an empty range");
+    }
+
+    with_rng(runtime, generator, |rng| {
+        GribValue::Number(rng.gen_range(lo as i64..=hi as i64) as f64)
+    })
+    .unwrap_or_else(|e| e)
+}
+
+fn choice(runtime: &mut Runtime, generator: Option<GribValue>, arr: GribValue) -> GribValue {
+    let len = match runtime.gc.try_get_array(arr.clone()) {
+        Some(arr) => arr.len(),
+        None => return GribValue::err(NO_ARRAY),
+    };
+    if len == 0 {
+        return GribValue::Nil;
+    }
+
+    match with_rng(runtime, generator, |rng| rng.gen_range(0..len)) {
+        Ok(index) => runtime
+            .gc
+            .try_get_array(arr)
+            .map(|arr| arr[index].clone())
+            .unwrap_or_default(),
+        Err(e) => e,
+    }
+}
+
+/// Shuffles the array in place and returns it
+fn shuffle(runtime: &mut Runtime, generator: Option<GribValue>, arr: GribValue) -> GribValue {
+    let mut order = match runtime.gc.try_get_array(arr.clone()) {
+        Some(arr) => (0..arr.len()).collect::<Vec<_>>(),
+        None => return GribValue::err(NO_ARRAY),
+    };
+    if let Err(e) = with_rng(runtime, generator, |rng| order.shuffle(rng)) {
+        return e;
+    }
+
+    if let Some(values) = runtime.gc.try_get_array_mut(arr.clone()) {
+        *values = order.into_iter().map(|i| values[i].clone()).collect();
+    }
+    arr
+}
+
+/// A normally distributed number from the Box-Muller transform.  The
+/// mean defaults to 0 and the standard deviation to 1 when they're nil
+fn gaussian(
+    program: &Program,
+    runtime: &mut Runtime,
+    generator: Option<GribValue>,
+    mean: GribValue,
+    sd: GribValue,
+) -> GribValue {
+    let mean = if mean.is_nil() {
+        0.
+    } else {
+        mean.cast_num(program, &runtime.gc)
+    };
+    let sd = if sd.is_nil() {
+        1.
+    } else {
+        sd.cast_num(program, &runtime.gc)
+    };
+
+    with_rng(runtime, generator, |rng| {
+        // 1 - x keeps the logarithm's argument above 0
+        let u1 = 1. - rng.gen::<f64>();
+        let u2 = rng.gen::<f64>();
+        let z = (-2. * u1.ln()).sqrt() * (2. * std::f64::consts::PI * u2).cos();
+        GribValue::Number(mean + sd * z)
+    })
+    .unwrap_or_else(|e| e)
+}
+
+fn generator_hash(rng: StdRng, program: &Program, runtime: &mut Runtime) -> GribValue {
+    use self::NativeGeneratorPackage::*;
+    let methods = [
+        ("random", Random),
+        ("seed", Seed),
+        ("randInt", RandInt),
+        ("choice", Choice),
+        ("shuffle", Shuffle),
+        ("gaussian", Gaussian),
+    ];
+    method_hash(
+        HeapValue::Generator(Box::new(rng)),
+        &methods,
+        program,
+        runtime,
+    )
+}
+
+native_package!(NativeGeneratorPackage[program runtime] {
+    Random["random"](generator) { random(runtime, Some(generator)) }
+    Seed["seed"](generator, n) { reseed(program, runtime, Some(generator), n) }
+    RandInt["randInt"](generator, lo, hi) { rand_int(program, runtime, Some(generator), lo, hi) }
+    Choice["choice"](generator, arr) { choice(runtime, Some(generator), arr) }
+    Shuffle["shuffle"](generator, arr) { shuffle(runtime, Some(generator), arr) }
+    Gaussian["gaussian"](generator, mean, sd) {
+        gaussian(program, runtime, Some(generator), mean, sd)
     }
 });
 
@@ -529,8 +1024,20 @@ native_package!(NativeMathPackage[program runtime] {
         GribValue::Number(largest)
     }
 
-    Random["random"]() {
-        GribValue::Number(rand::random())
+    Random["random"]() { random(runtime, None) }
+    Seed["seed"](n) { reseed(program, runtime, None, n) }
+    RandInt["randInt"](lo, hi) { rand_int(program, runtime, None, lo, hi) }
+    Choice["choice"](arr) { choice(runtime, None, arr) }
+    Shuffle["shuffle"](arr) { shuffle(runtime, None, arr) }
+    Gaussian["gaussian"](mean, sd) { gaussian(program, runtime, None, mean, sd) }
+    Generator["generator"](n) {
+        let rng = if n.is_nil() {
+            // Drawing the seed from the shared generator keeps `--seed` runs repeatable
+            StdRng::seed_from_u64(runtime.rng.gen())
+        } else {
+            StdRng::seed_from_u64(seed_value(program, runtime, n))
+        };
+        generator_hash(rng, program, runtime)
     }
 
     MathConst["mathConst"](s) {
@@ -687,4 +1194,33 @@ native_obj!(NativeFunction | NativePackage {
     NativeStrPackage -> "str",
     NativeHashPackage -> "hash",
     NativeMetaPackage -> "meta",
+    NativeProcessPackage -> "process",
+    NativeTestPackage -> "test",
+} internal {
+    // Only reachable through the methods of sockets and generators
+    NativeSocketPackage -> "socket",
+    NativeGeneratorPackage -> "generator",
 });
+
+impl NativeFunction {
+    /// Whether the function reaches outside of the interpreter to files,
+    /// sockets, or its process, or reads the process's arguments and environment
+    pub fn escapes_sandbox(&self) -> bool {
+        match self {
+            NativeFunction::NativeSyncIoPackage(_) => true,
+            NativeFunction::NativeMetaPackage(f) => {
+                matches!(f, NativeMetaPackage::Exit | NativeMetaPackage::HeapSnapshot)
+            }
+            NativeFunction::NativeProcessPackage(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The signatures of every native function, grouped by package
+pub fn std_manifest() -> Vec<PackageManifest> {
+    NativePackage::ALL
+        .iter()
+        .map(NativePackage::manifest)
+        .collect()
+}
@@ -1,13 +1,16 @@
 use super::GribValue;
-use ast::node::{NativeFunction, Program};
+use ast::node::{HostFunction, NativeFunction, Program};
+use location::Location;
 use runtime::{
-    exec::{evaluate_lambda, run_block},
+    exec::{run_code, CallFrame, CodeId, FrameKind},
     memory::Runtime,
 };
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Callable {
     Native(NativeFunction),
+    /// Function registered by the program embedding the interpreter
+    Host(HostFunction),
     Procedure {
         module: Option<usize>,
         index: usize,
@@ -17,6 +20,11 @@ pub enum Callable {
         stack: Option<usize>,
         index: usize,
     },
+    /// Native function that receives a heap value as its first argument
+    Method {
+        function: NativeFunction,
+        receiver: usize,
+    },
 }
 
 impl Callable {
@@ -26,8 +34,81 @@ impl Callable {
         runtime: &mut Runtime,
         args: Vec<GribValue>,
     ) -> GribValue {
+        self.call_at(program, runtime, args, None)
+    }
+
+    /// Calls the function, recording `call_site` in the runtime's call frames
+    pub fn call_at(
+        &self,
+        program: &Program,
+        runtime: &mut Runtime,
+        args: Vec<GribValue>,
+        call_site: Option<&Location>,
+    ) -> GribValue {
+        runtime.push_frame(CallFrame::new(self.frame_kind(), call_site));
+        let ret = self.invoke(program, runtime, args);
+        runtime.frames.pop();
+        ret
+    }
+
+    /// Whether the function is implemented in Rust rather than Grib
+    pub fn is_native(&self) -> bool {
+        matches!(
+            self,
+            Callable::Native(_) | Callable::Host(_) | Callable::Method { .. }
+        )
+    }
+
+    pub fn frame_kind(&self) -> FrameKind {
+        match self {
+            Callable::Native(n) | Callable::Method { function: n, .. } => {
+                FrameKind::Native(n.clone())
+            }
+            Callable::Host(f) => FrameKind::Host(*f),
+            Callable::Procedure { module, index } => FrameKind::Procedure {
+                module: *module,
+                index: *index,
+            },
+            Callable::Lambda { index, .. } => FrameKind::Lambda(*index),
+        }
+    }
+
+    fn invoke(&self, program: &Program, runtime: &mut Runtime, args: Vec<GribValue>) -> GribValue {
+        match self {
+            Callable::Native(n) => {
+                runtime.check_sandbox(n);
+                n.call(program, runtime, args)
+            }
+            Callable::Host(f) => {
+                runtime.check_host_sandbox(&program.host, *f);
+                program.host.call(*f, program, runtime, args)
+            }
+            Callable::Method { function, receiver } => {
+                runtime.check_sandbox(function);
+                let mut args = args;
+                args.insert(0, GribValue::HeapValue(*receiver));
+                function.call(program, runtime, args)
+            }
+            Callable::Procedure { .. } | Callable::Lambda { .. } => {
+                let (id, this, alloced) = self.enter(program, runtime, args);
+                let ret = run_code(id, &this, runtime, program);
+
+                runtime.stack.pop_stack(alloced);
+                ret
+            }
+        }
+    }
+
+    /// Pushes the captured variables and parameters of a Grib function
+    /// onto the stack.  Returns the code to run, the value of `this`,
+    /// and the number of stack slots that were added
+    pub fn enter(
+        &self,
+        program: &Program,
+        runtime: &mut Runtime,
+        args: Vec<GribValue>,
+    ) -> (CodeId, GribValue, usize) {
         match self {
-            Callable::Native(n) => n.call(program, runtime, args),
             Callable::Procedure { module, index } => {
                 let fnc = if let Some(i) = module {
                     &program.modules[*i].functions[*index]
@@ -36,12 +117,11 @@ impl Callable {
                 };
 
                 let alloced = runtime.add_params(&fnc.param_list, args);
-                let ret = run_block(&fnc.body, &GribValue::Nil, runtime, program)
-                    .map(GribValue::from)
-                    .unwrap_or_default();
-
-                runtime.stack.pop_stack(alloced);
-                ret
+                let id = CodeId::Procedure {
+                    module: *module,
+                    index: *index,
+                };
+                (id, GribValue::Nil, alloced)
             }
             Callable::Lambda {
                 binding,
@@ -56,10 +136,10 @@ impl Callable {
                     .clone()
                     .map(GribValue::HeapValue)
                     .unwrap_or_default();
-                let res = evaluate_lambda(&lambda.body, &this, runtime, program);
-
-                runtime.stack.pop_stack(captured + params);
-                res
+                (CodeId::Lambda(*index), this, captured + params)
+            }
+            Callable::Native(_) | Callable::Host(_) | Callable::Method { .. } => {
+                panic!("Native functions don't run on the Grib stack")
             }
         }
     }
@@ -1,9 +1,10 @@
 use super::{AccessFunc, Callable, GribString, GribValue};
 use ast::node::Program;
-use runtime::exec::evaluate_lambda;
+use runtime::exec::{run_code, CallFrame, CodeId, FrameKind};
 use runtime::memory::{Gc, Runtime};
 use std::collections::HashMap;
 use std::hash::{BuildHasher, Hash, Hasher};
+use std::mem;
 
 #[derive(Clone, Debug)]
 pub enum HashPropertyValue {
@@ -36,13 +37,17 @@ impl HashPropertyValue {
                     AccessFunc::Static(val) => val.clone().into(),
                     AccessFunc::Captured(ptr) => runtime.gc.get_captured(*ptr).cloned(),
                     AccessFunc::Callable { index, stack } => {
-                        program.getters.get(*index).and_then(|getter| {
+                        program.getters.get(*index).map(|_| {
                             let alloced = runtime.add_stack(stack.clone());
                             let this = GribValue::HeapValue(self_ptr);
 
-                            let val = evaluate_lambda(&getter.block, &this, runtime, program);
+                            let frame = CallFrame::new(FrameKind::Getter(*index), None);
+                            runtime.push_frame(frame);
+                            let val = run_code(CodeId::Getter(*index), &this, runtime, program);
+                            runtime.frames.pop();
+
                             runtime.stack.pop_stack(alloced);
-                            Some(val)
+                            val
                         })
                     }
                 })
@@ -107,6 +112,11 @@ impl HashValue {
         self.values.insert(key, value.into());
     }
 
+    /// Roughly how many bytes the hash's entries take up
+    pub fn size(&self) -> usize {
+        self.values.capacity() * mem::size_of::<(GribKey, HashPropertyValue)>()
+    }
+
     fn get_hasher(&self) -> impl Hasher {
         self.values.hasher().build_hasher()
     }
@@ -226,7 +236,10 @@ pub fn eval_setter(
             }
 
             let this = GribValue::HeapValue(self_ptr);
-            let res = evaluate_lambda(&setter.block, &this, runtime, program);
+            let frame = CallFrame::new(FrameKind::Setter(*index), None);
+            runtime.push_frame(frame);
+            let res = run_code(CodeId::Setter(*index), &this, runtime, program);
+            runtime.frames.pop();
 
             runtime.stack.pop_stack(stack_alloced + 1);
             res
@@ -1,5 +1,7 @@
-use super::{GribValue, HashValue};
+use super::{GribValue, HashValue, Socket};
+use rand::rngs::StdRng;
 use runtime::memory::{Gc, StackSlot};
+use std::mem;
 
 /*macro_rules! try_method {
     ($n:ident, $e:expr) => {
@@ -17,6 +19,23 @@ pub enum HeapValue {
     Hash(HashValue),
     String(String),
     CapturedStack(Vec<StackSlot>),
+    Socket(Socket),
+    /// A random number generator made by `generator` in the math package
+    Generator(Box<StdRng>),
+}
+
+impl HeapValue {
+    /// Roughly how many bytes the value holds outside of its heap slot
+    pub fn size(&self) -> usize {
+        match self {
+            HeapValue::Array(arr) => arr.capacity() * mem::size_of::<GribValue>(),
+            HeapValue::Hash(hash) => hash.size(),
+            HeapValue::String(s) => s.capacity(),
+            HeapValue::CapturedStack(stack) => stack.capacity() * mem::size_of::<StackSlot>(),
+            HeapValue::Socket(_) => 0,
+            HeapValue::Generator(_) => mem::size_of::<StdRng>(),
+        }
+    }
 }
 
 macro_rules! type_ref {
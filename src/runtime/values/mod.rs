@@ -1,6 +1,8 @@
 mod callable;
 mod hash;
 mod heap;
+mod owned;
+mod socket;
 mod string;
 
 use ast::node::*;
@@ -11,6 +13,8 @@ use std::cmp::Ordering;
 pub use self::callable::*;
 pub use self::hash::*;
 pub use self::heap::*;
+pub use self::owned::Value;
+pub use self::socket::*;
 pub use self::string::*;
 
 /*pub fn float_to_ind(f: f64) -> Option<usize> {
@@ -141,7 +145,7 @@ impl GribValue {
         match self {
             Self::Nil => GribString::Static("nil"),
             Self::Callable(fnc) => GribString::Static(match fnc {
-                Callable::Native(_) => "[native]",
+                Callable::Native(_) | Callable::Host(_) | Callable::Method { .. } => "[native]",
                 Callable::Procedure { .. } => "[proc]",
                 Callable::Lambda { .. } => "[lambda]",
             }),
@@ -162,7 +166,7 @@ impl GribValue {
         match self {
             Self::Nil => "nil".into(),
             Self::Callable(fnc) => match fnc {
-                Callable::Native(_) => "[native]",
+                Callable::Native(_) | Callable::Host(_) | Callable::Method { .. } => "[native]",
                 Callable::Procedure { .. } => "[proc]",
                 Callable::Lambda { .. } => "[lambda]",
             }
@@ -181,6 +185,15 @@ impl GribValue {
         }
     }
 
+    /// Same as `as_str`, but includes the message of error values
+    pub fn display<'a>(&'a self, program: &'a Program, runtime: &'a Runtime) -> Cow<'a, str> {
+        if let Self::Error(err) = self {
+            format!("[ERR: {}]", err.as_str(program, runtime)).into()
+        } else {
+            self.as_str(program, runtime)
+        }
+    }
+
     pub fn cast_ind(&self, program: &Program, gc: &Gc) -> Option<usize> {
         Some(self.cast_num(program, gc).trunc())
             .filter(|&i| i.is_finite() && i >= 0. && i <= (usize::MAX as f64))
@@ -0,0 +1,219 @@
+use super::{GribString, GribValue, HashValue, HeapValue};
+use ast::node::Program;
+use runtime::memory::Runtime;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+/// A Grib value that has been copied out of the interpreter, or
+/// one that can be copied in.  Unlike `GribValue`, it doesn't
+/// refer to anything on the heap, so it outlives the runtime
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Hash(BTreeMap<String, Value>),
+    Error(Box<Value>),
+    /// Something that can't be copied, such as a function, module, or
+    /// socket, or an array or hash inside of itself.  Holds the kind of
+    /// value it was.  It's copied back into the interpreter as nil
+    Opaque(&'static str),
+}
+
+impl Value {
+    /// Copies a value out of the runtime.  The getters of hashes are called
+    /// to find their values, so the value has to be reachable by the collector
+    pub fn from_grib(value: &GribValue, runtime: &mut Runtime, program: &Program) -> Self {
+        copy_out(value, runtime, program, &mut Vec::new())
+    }
+
+    /// Allocates the value in the runtime.  Hashes are mutable
+    pub fn to_grib(&self, runtime: &mut Runtime, program: &Program) -> GribValue {
+        match self {
+            Value::Nil | Value::Opaque(_) => GribValue::Nil,
+            Value::Bool(b) => GribValue::Bool(*b),
+            Value::Number(n) => GribValue::Number(*n),
+            Value::String(s) => GribValue::String(runtime.alloc_str(s.clone())),
+            Value::Error(inner) => GribValue::Error(inner.to_grib(runtime, program).into()),
+            Value::Array(items) => {
+                // Items wait on the operand stack so they stay
+                // rooted while the rest are allocated
+                let base = runtime.operands.len();
+                for item in items {
+                    let item = item.to_grib(runtime, program);
+                    runtime.operands.push(item);
+                }
+
+                let items = runtime.operands.split_off(base);
+                GribValue::HeapValue(runtime.alloc_heap(HeapValue::Array(items)))
+            }
+            Value::Hash(entries) => {
+                let base = runtime.operands.len();
+                for (key, value) in entries {
+                    let key = GribValue::String(runtime.alloc_str(key.clone()));
+                    runtime.operands.push(key);
+                    let value = value.to_grib(runtime, program);
+                    runtime.operands.push(value);
+                }
+
+                let mut hash = HashValue::new(true);
+                for pair in runtime.operands[base..].chunks(2) {
+                    if let GribValue::String(s) = &pair[0] {
+                        let key = hash.key(s.clone(), program, &runtime.gc);
+                        hash.init_value(key, pair[1].clone());
+                    }
+                }
+
+                let ptr = runtime.alloc_heap(HeapValue::Hash(hash));
+                runtime.operands.truncate(base);
+                GribValue::HeapValue(ptr)
+            }
+        }
+    }
+}
+
+fn copy_str(s: &GribString, runtime: &Runtime, program: &Program) -> String {
+    s.as_ref(program, &runtime.gc)
+        .map(|s| s.to_string())
+        .unwrap_or_default()
+}
+
+/// `parents` holds the arrays and hashes that the value is inside of
+fn copy_out(
+    value: &GribValue,
+    runtime: &mut Runtime,
+    program: &Program,
+    parents: &mut Vec<usize>,
+) -> Value {
+    let ptr = match value {
+        GribValue::Nil => return Value::Nil,
+        GribValue::Bool(b) => return Value::Bool(*b),
+        GribValue::Number(n) => return Value::Number(*n),
+        GribValue::String(s) => return Value::String(copy_str(s, runtime, program)),
+        GribValue::Error(inner) => {
+            return Value::Error(copy_out(inner, runtime, program, parents).into())
+        }
+        GribValue::Callable(_) => return Value::Opaque("callable"),
+        GribValue::ModuleObject(_) => return Value::Opaque("module object"),
+        GribValue::HeapValue(ptr) => *ptr,
+    };
+
+    if parents.contains(&ptr) {
+        return Value::Opaque(match runtime.gc.heap_val(ptr) {
+            Some(HeapValue::Hash(_)) => "hash",
+            _ => "array",
+        });
+    }
+
+    match runtime.gc.heap_val(ptr).cloned() {
+        Some(HeapValue::Array(items)) => {
+            parents.push(ptr);
+            let items = items
+                .iter()
+                .map(|item| copy_out(item, runtime, program, parents))
+                .collect();
+            parents.pop();
+            Value::Array(items)
+        }
+        Some(HeapValue::Hash(hash)) => {
+            parents.push(ptr);
+            let mut entries = BTreeMap::new();
+            for (key, prop) in hash.iter() {
+                let key = copy_str(key, runtime, program);
+                let value = prop.get(runtime, program, ptr);
+
+                // Values returned by getters aren't reachable from anything else
+                runtime.operands.push(value.clone());
+                entries.insert(key, copy_out(&value, runtime, program, parents));
+                runtime.operands.pop();
+            }
+            parents.pop();
+            Value::Hash(entries)
+        }
+        Some(HeapValue::String(s)) => Value::String(s),
+        Some(HeapValue::Socket(_)) => Value::Opaque("socket"),
+        Some(HeapValue::Generator(_)) => Value::Opaque("generator"),
+        Some(HeapValue::CapturedStack(_)) | None => Value::Nil,
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(n: i32) -> Self {
+        Value::Number(n.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Self {
+        Value::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Value::Nil)
+    }
+}
+
+/// Shows the value the way it would be written in Grib, with strings quoted
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{:?}", s),
+            Value::Error(inner) => write!(f, "[ERR: {}]", inner),
+            Value::Opaque(kind) => write!(f, "[{}]", kind),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Hash(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {:?}: {}", key, value)?;
+                }
+                match entries.is_empty() {
+                    true => write!(f, "}}"),
+                    false => write!(f, " }}"),
+                }
+            }
+        }
+    }
+}
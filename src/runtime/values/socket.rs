@@ -0,0 +1,132 @@
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The number of bytes read when no limit is given
+pub const READ_SIZE: usize = 4096;
+/// The most bytes a single read returns, whatever limit is given
+pub const MAX_READ_SIZE: usize = 1 << 16;
+/// How often a listener with a timeout checks for a connection
+const ACCEPT_POLL: Duration = Duration::from_millis(10);
+
+#[derive(Debug)]
+enum SocketKind {
+    Stream(TcpStream),
+    Listener(TcpListener),
+}
+
+/// A TCP connection or listener stored on the heap.  Closing a socket
+/// releases the underlying handle, so clones share their state
+#[derive(Clone, Debug)]
+pub struct Socket(Rc<RefCell<Option<SocketKind>>>);
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "socket is closed")
+}
+
+fn wrong_kind() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "operation not supported on this kind of socket",
+    )
+}
+
+/// Whether a read or accept gave up because its timeout passed
+pub fn timed_out(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// Listeners can't be given a timeout, so this polls for a connection instead
+fn accept_before(listener: &TcpListener, deadline: Instant) -> io::Result<(TcpStream, SocketAddr)> {
+    listener.set_nonblocking(true)?;
+    let accepted = loop {
+        match listener.accept() {
+            Err(ref e) if timed_out(e) && Instant::now() < deadline => thread::sleep(ACCEPT_POLL),
+            result => break result,
+        }
+    };
+    listener.set_nonblocking(false)?;
+
+    let (stream, addr) = accepted?;
+    stream.set_nonblocking(false)?;
+    Ok((stream, addr))
+}
+
+impl Socket {
+    fn new(kind: SocketKind) -> Self {
+        Socket(Rc::new(RefCell::new(Some(kind))))
+    }
+
+    pub fn connect(addr: &str) -> io::Result<Self> {
+        TcpStream::connect(addr).map(|s| Self::new(SocketKind::Stream(s)))
+    }
+
+    pub fn listen(addr: &str) -> io::Result<Self> {
+        TcpListener::bind(addr).map(|l| Self::new(SocketKind::Listener(l)))
+    }
+
+    fn with_stream<T>(&self, f: impl FnOnce(&mut TcpStream) -> io::Result<T>) -> io::Result<T> {
+        match &mut *self.0.borrow_mut() {
+            Some(SocketKind::Stream(stream)) => f(stream),
+            Some(SocketKind::Listener(_)) => Err(wrong_kind()),
+            None => Err(closed()),
+        }
+    }
+
+    /// Blocks until at least one byte is available or the timeout passes.
+    /// An empty vec is returned once the connection is closed
+    pub fn read(&self, max: usize, timeout: Option<Duration>) -> io::Result<Vec<u8>> {
+        if timeout == Some(Duration::ZERO) {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.with_stream(|stream| {
+            stream.set_read_timeout(timeout)?;
+            let mut buf = vec![0; max.min(MAX_READ_SIZE)];
+            let count = stream.read(&mut buf)?;
+            buf.truncate(count);
+            Ok(buf)
+        })
+    }
+
+    pub fn write(&self, bytes: &[u8]) -> io::Result<()> {
+        self.with_stream(|stream| stream.write_all(bytes).and_then(|_| stream.flush()))
+    }
+
+    /// Waits for a connection on a listening socket, until the timeout passes
+    pub fn accept(&self, timeout: Option<Duration>) -> io::Result<Self> {
+        match &*self.0.borrow() {
+            Some(SocketKind::Listener(listener)) => match timeout {
+                Some(timeout) => accept_before(listener, Instant::now() + timeout),
+                None => listener.accept(),
+            }
+            .map(|(stream, _)| Self::new(SocketKind::Stream(stream))),
+            Some(SocketKind::Stream(_)) => Err(wrong_kind()),
+            None => Err(closed()),
+        }
+    }
+
+    /// The address a listener is bound to or the address
+    /// of the other end of a connection
+    pub fn address(&self) -> io::Result<String> {
+        match &*self.0.borrow() {
+            Some(SocketKind::Listener(listener)) => listener.local_addr(),
+            Some(SocketKind::Stream(stream)) => stream.peer_addr(),
+            None => Err(closed()),
+        }
+        .map(|addr| addr.to_string())
+    }
+
+    pub fn close(&self) -> io::Result<()> {
+        match self.0.borrow_mut().take() {
+            Some(SocketKind::Stream(stream)) => stream.shutdown(Shutdown::Both),
+            Some(SocketKind::Listener(_)) => Ok(()),
+            None => Err(closed()),
+        }
+    }
+}
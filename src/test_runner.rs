@@ -0,0 +1,357 @@
+///
+/// Finds and runs the tests in Grib modules for `gribc test`
+///
+use ast::{self, node::Program};
+use cli::{self, Options};
+use diagnostic::Diagnostic;
+use lex;
+use runtime::values::{Callable, Value};
+use runtime::{RuntimeConfig, Session};
+use serde_json::Value as Json;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Public procedures with this prefix are tests
+const PREFIX: &str = "test_";
+
+pub struct TestResult {
+    pub name: String,
+    /// Why the test failed, if it did
+    pub failure: Option<String>,
+    pub duration: Duration,
+}
+
+/// The tests in one module
+pub struct Suite {
+    pub path: PathBuf,
+    pub tests: Vec<TestResult>,
+    /// Why the module couldn't be loaded, in which case it has no tests
+    pub error: Option<String>,
+}
+
+impl Suite {
+    fn failures(&self) -> usize {
+        self.tests.iter().filter(|t| t.failure.is_some()).count()
+    }
+
+    fn duration(&self) -> Duration {
+        self.tests.iter().map(|t| t.duration).sum()
+    }
+}
+
+/// The Grib files under a directory in a stable order, or the path itself if it's a file
+pub fn discover(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension() == Some(OsStr::new("grib")) {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Lexes, parses, and analyzes a module the same way `gribc check` does
+fn load(path: &Path, sandbox: bool) -> Result<Program, String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    let tokens =
+        lex::lex(&source).map_err(|e| Diagnostic::from_lex(&e, path, &source).to_string())?;
+    let parsed = match sandbox {
+        true => ast::ast_sandboxed(tokens, path),
+        false => ast::ast(tokens, path),
+    };
+    let mut program = parsed.map_err(|e| Diagnostic::from_parse(&e, path, &source).to_string())?;
+    ast::ref_check(&mut program)
+        .map_err(|e| Diagnostic::from_walk(&e, &program, path, &source).to_string())?;
+    Ok(program)
+}
+
+/// Calls every test in the module with a runtime of its own.  The module's
+/// top level statements aren't run, since procedures can't see its variables
+pub fn run_suite(path: &Path, config: impl Fn() -> RuntimeConfig) -> Suite {
+    let program = match load(path, config().sandbox) {
+        Ok(program) => program,
+        Err(error) => {
+            return Suite {
+                path: path.to_path_buf(),
+                tests: Vec::new(),
+                error: Some(error),
+            }
+        }
+    };
+
+    let tests = program
+        .functions
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| f.public && program.strings[f.identifier.data].starts_with(PREFIX))
+        .map(|(index, f)| {
+            let function = Callable::Procedure {
+                module: None,
+                index,
+            };
+
+            let start = Instant::now();
+            let result = Session::new(config()).call(&program, &function, &[]);
+            let failure = match result {
+                Ok(Value::Error(inner)) => Some(format!("returned an error: {}", inner)),
+                Ok(_) => None,
+                Err(e) => Some(e.to_string()),
+            };
+
+            TestResult {
+                name: program.strings[f.identifier.data].clone(),
+                failure,
+                duration: start.elapsed(),
+            }
+        })
+        .collect();
+
+    Suite {
+        path: path.to_path_buf(),
+        tests,
+        error: None,
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.)
+}
+
+/// Escapes text for an XML attribute or element
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The first line of a failure, used where the full text doesn't fit
+fn summary(failure: &str) -> &str {
+    failure.lines().next().unwrap_or_default()
+}
+
+/// Describes the results in the JUnit XML format read by CI servers.
+/// Modules that couldn't be loaded have one test case with an error
+pub fn junit(suites: &[Suite]) -> String {
+    let tests = suites.iter().map(|s| s.tests.len()).sum::<usize>();
+    let failures = suites.iter().map(Suite::failures).sum::<usize>();
+    let errors = suites.iter().filter(|s| s.error.is_some()).count();
+    let time = suites.iter().map(Suite::duration).sum::<Duration>();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml += &format!(
+        "<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.6}\">\n",
+        tests,
+        failures,
+        errors,
+        time.as_secs_f64()
+    );
+
+    for suite in suites {
+        let name = escape(&suite.path.display().to_string());
+        xml += &format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.6}\">\n",
+            name,
+            suite.tests.len(),
+            suite.failures(),
+            suite.error.is_some() as usize,
+            suite.duration().as_secs_f64()
+        );
+
+        if let Some(error) = &suite.error {
+            xml += &format!(
+                "    <testcase name=\"(module)\" classname=\"{}\" time=\"0\">\n      <error message=\"{}\">{}</error>\n    </testcase>\n",
+                name,
+                escape(summary(error)),
+                escape(error)
+            );
+        }
+
+        for test in &suite.tests {
+            let case = format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.6}\"",
+                escape(&test.name),
+                name,
+                test.duration.as_secs_f64()
+            );
+            xml += &match &test.failure {
+                Some(failure) => format!(
+                    "{}>\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                    case,
+                    escape(summary(failure)),
+                    escape(failure)
+                ),
+                None => format!("{}/>\n", case),
+            };
+        }
+
+        xml += "  </testsuite>\n";
+    }
+
+    xml + "</testsuites>\n"
+}
+
+/// Describes the results as JSON, with durations in milliseconds
+pub fn json(suites: &[Suite]) -> Json {
+    let passed = suites
+        .iter()
+        .all(|s| s.error.is_none() && s.failures() == 0);
+    let suites = suites
+        .iter()
+        .map(|suite| {
+            let tests = suite
+                .tests
+                .iter()
+                .map(|test| {
+                    json!({
+                        "name": test.name,
+                        "passed": test.failure.is_none(),
+                        "failure": test.failure,
+                        "durationMs": test.duration.as_secs_f64() * 1000.,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            json!({
+                "path": suite.path.display().to_string(),
+                "error": suite.error,
+                "tests": tests,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "passed": passed,
+        "suites": suites,
+    })
+}
+
+fn write_report(path: &str, report: &str) -> bool {
+    match fs::write(path, report) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("error: could not write {}: {}", path, e);
+            false
+        }
+    }
+}
+
+/// Runs the tests under the input path, printing each result as it
+/// finishes followed by the failures and a summary
+pub fn run(path: &Path, options: &Options) -> i32 {
+    let files = match discover(path) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("error: could not read {}: {}", path.display(), e);
+            return cli::EXIT_NO_INPUT;
+        }
+    };
+
+    // Every test has a runtime of its own, so there's no one report to print
+    let config = || RuntimeConfig {
+        gc_stats: false,
+        ..options.runtime_config(false)
+    };
+
+    let start = Instant::now();
+    let mut suites = Vec::new();
+    for file in files {
+        let suite = run_suite(&file, config);
+        if let Some(error) = &suite.error {
+            println!("error {}: {}", file.display(), summary(error));
+        }
+        for test in &suite.tests {
+            let status = if test.failure.is_some() {
+                "FAILED"
+            } else {
+                "ok"
+            };
+            println!(
+                "test {}::{} ... {} ({})",
+                file.display(),
+                test.name,
+                status,
+                millis(test.duration)
+            );
+        }
+
+        if suite.error.is_some() || !suite.tests.is_empty() {
+            suites.push(suite);
+        }
+    }
+
+    let mut failed = 0;
+    let mut passed = 0;
+    for suite in &suites {
+        if let Some(error) = &suite.error {
+            failed += 1;
+            print!("\n---- {} ----\n{}", suite.path.display(), error);
+        }
+        for test in &suite.tests {
+            match &test.failure {
+                Some(failure) => {
+                    failed += 1;
+                    print!(
+                        "\n---- {}::{} ----\n{}",
+                        suite.path.display(),
+                        test.name,
+                        failure
+                    );
+                    if !failure.ends_with('\n') {
+                        println!();
+                    }
+                }
+                None => passed += 1,
+            }
+        }
+    }
+
+    let status = if failed == 0 { "ok" } else { "FAILED" };
+    println!(
+        "\ntest result: {}. {} passed; {} failed; finished in {}",
+        status,
+        passed,
+        failed,
+        millis(start.elapsed())
+    );
+
+    let mut written = true;
+    if let Some(report) = &options.json_report {
+        let json = serde_json::to_string_pretty(&json(&suites))
+            .expect("Test reports are always serializable");
+        written &= write_report(report, &json);
+    }
+    if let Some(report) = &options.junit_report {
+        written &= write_report(report, &junit(&suites));
+    }
+
+    match (failed, written) {
+        (_, false) => cli::EXIT_CANT_CREATE,
+        (0, true) => 0,
+        _ => cli::EXIT_RUNTIME,
+    }
+}
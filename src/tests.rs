@@ -1,18 +1,45 @@
 use crate::serde::de::DeserializeOwned;
 
 use ast::{
-    ast,
+    ast, ast_with_host,
+    format::format,
+    lint,
     node::{Module, Program},
+    parse_input, read_compiled, ref_check, write_compiled, LintConfig, LintKind, LoadError,
+    ReplScope, SaveError,
 };
-use lex::lex;
+use cli::{parse_args, Cli, CliError, Command, Input};
+use dap;
+use diagnostic::Diagnostic;
+use lex::{lex, lex_with_comments};
+use lsp;
+use runtime::{
+    console::{Capture, Console},
+    debug,
+    debug::{Breakpoints, Debugger, Paused, Resume, StopReason},
+    error::{Limit, RuntimeError, RuntimeErrorKind},
+    execute,
+    host::HostRegistry,
+    native_fn::std_manifest,
+    values::GribValue,
+    GcMode, Limits, RuntimeConfig, Session, DEFAULT_STACK_LIMIT,
+};
+use test_runner;
 
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::ffi::OsStr;
 use std::fmt::Debug;
 use std::fs;
+use std::io::Write;
 use std::path::Path;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
 
-use util::remove_file;
+use gribc::util::remove_file;
+use gribc::{Error as GribError, Interpreter, Value};
 
 #[derive(Clone, Copy, Debug)]
 struct GenericErr;
@@ -112,3 +139,1851 @@ fn ast_test() -> Result<(), Box<dyn std::error::Error>> {
         canonicalize_ast,
     )
 }
+
+#[test]
+fn walk_error_diagnostic() -> Result<(), Box<dyn std::error::Error>> {
+    let source = "decl a = 1;\na = b;\n";
+    let path = Path::new("test.grib");
+
+    let mut program = ast(lex(source)?, path)?;
+    let err = ref_check(&mut program).invert().map_err(|_| GenericErr)?;
+    let report = Diagnostic::from_walk(&err, &program, path, source).to_string();
+
+    assert_eq!(
+        report,
+        "error: cannot find `b` in this scope\n  --> test.grib:2:5\n  |\n2 | a = b;\n  |     ^\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn runtime_error_trace() -> Result<(), Box<dyn std::error::Error>> {
+    let source = "proc f |n| {\n  return f(n + 1);\n}\nf(0);\n";
+    let path = Path::new("test.grib");
+
+    let mut program = ast(lex(source)?, path)?;
+    ref_check(&mut program).map_err(|_| GenericErr)?;
+
+    // The Grib stack has to fill up before the native one does
+    let err = thread::Builder::new()
+        .stack_size(1 << 30)
+        .spawn(move || {
+            let config = RuntimeConfig {
+                cleanup_after: 1000,
+                gc_mode: GcMode::Generational,
+                gc_stats: false,
+                stack_limit: DEFAULT_STACK_LIMIT,
+                report_errors: false,
+                args: Vec::new(),
+                limits: Limits::default(),
+                sandbox: false,
+                console: Console::Std,
+                seed: None,
+            };
+            execute(&program, config)
+        })?
+        .join()
+        .map_err(|_| GenericErr)?
+        .invert()
+        .map_err(|_| GenericErr)?;
+
+    assert_eq!(err.kind, RuntimeErrorKind::StackOverflow);
+    assert_eq!(err.trace[0].name, "f");
+
+    let report = err.to_string();
+    assert!(report.starts_with("runtime error: Grib stack overflow\n    at f (test.grib)\n"));
+    assert!(report.ends_with("more times]\n    at <main> (test.grib:4:1)\n"));
+    Ok(())
+}
+
+#[test]
+fn cli_args() {
+    let args = |s: &str| parse_args(s.split_whitespace().map(String::from));
+
+    match args("--gc-threshold 50 --gc-stats --stack-limit 64 script.grib -x --y") {
+        Ok(Cli::Exec(options)) => {
+            assert_eq!(options.command, Command::Run);
+            assert_eq!(options.input, Input::File("script.grib".to_string()));
+            assert_eq!(options.gc_threshold, 50);
+            assert!(options.gc_stats);
+            assert_eq!(options.stack_limit, 64);
+            assert_eq!(options.args, vec!["-x", "--y"]);
+        }
+        other => panic!("unexpected parse {:?}", other),
+    }
+
+    match args("dump-ast --analyzed -") {
+        Ok(Cli::Exec(options)) => {
+            assert_eq!(options.command, Command::DumpAst);
+            assert_eq!(options.input, Input::Stdin);
+            assert!(options.analyzed);
+        }
+        other => panic!("unexpected parse {:?}", other),
+    }
+
+    match (args(""), args("repl --gc-threshold 5")) {
+        (Ok(Cli::Exec(bare)), Ok(Cli::Exec(repl))) => {
+            assert_eq!(bare.command, Command::Repl);
+            assert_eq!(repl.command, Command::Repl);
+            assert_eq!(repl.gc_threshold, 5);
+        }
+        other => panic!("unexpected parse {:?}", other),
+    }
+
+    assert_eq!(args("tokens --help"), Ok(Cli::Help));
+    assert_eq!(args("std-manifest"), Ok(Cli::Manifest));
+    assert_eq!(
+        args("std-manifest -"),
+        Err(CliError::UnexpectedArgument("-".to_string()))
+    );
+    assert_eq!(args("check"), Err(CliError::MissingInput));
+    assert_eq!(
+        args("check a.grib b.grib"),
+        Err(CliError::UnexpectedArgument("b.grib".to_string()))
+    );
+    match args("compile -o out.gribc a.grib") {
+        Ok(Cli::Exec(options)) => {
+            assert_eq!(options.command, Command::Compile);
+            assert_eq!(options.output, Some("out.gribc".to_string()));
+        }
+        other => panic!("unexpected parse {:?}", other),
+    }
+    assert_eq!(
+        args("run -o out.gribc a.grib"),
+        Err(CliError::UnknownOption("-o".to_string()))
+    );
+    match args("--gc-mode full a.grib") {
+        Ok(Cli::Exec(options)) => assert_eq!(options.gc_mode, GcMode::Full),
+        other => panic!("unexpected parse {:?}", other),
+    }
+    assert_eq!(
+        args("--gc-mode eager a.grib"),
+        Err(CliError::InvalidValue(
+            "--gc-mode".to_string(),
+            "eager".to_string()
+        ))
+    );
+    assert_eq!(
+        args("--stack-size 0 a.grib"),
+        Err(CliError::InvalidValue(
+            "--stack-size".to_string(),
+            "0".to_string()
+        ))
+    );
+    match args("--sandbox --max-steps 500 --time-limit 20 --max-heap 64 --max-depth 8 a.grib") {
+        Ok(Cli::Exec(options)) => {
+            assert!(options.sandbox);
+            assert_eq!(
+                options.limits,
+                Limits {
+                    steps: Some(500),
+                    time: Some(Duration::from_millis(20)),
+                    heap_slots: Some(64),
+                    call_depth: Some(8),
+                }
+            );
+        }
+        other => panic!("unexpected parse {:?}", other),
+    }
+    match args("debug a.grib x") {
+        Ok(Cli::Exec(options)) => {
+            assert_eq!(options.command, Command::Debug);
+            assert_eq!(options.args, vec!["x"]);
+        }
+        other => panic!("unexpected parse {:?}", other),
+    }
+    match args("--gc-stats --seed 3 a.grib x") {
+        Ok(Cli::Exec(options)) => {
+            let config = options.runtime_config(true);
+            assert!(config.gc_stats && config.report_errors);
+            assert_eq!((config.args, config.seed), (vec!["x".to_string()], Some(3)));
+        }
+        other => panic!("unexpected parse {:?}", other),
+    }
+    assert_eq!(
+        args("debug -"),
+        Err(CliError::UnexpectedArgument("-".to_string()))
+    );
+    match args("dap --sandbox") {
+        Ok(Cli::Exec(options)) => {
+            assert_eq!(options.command, Command::Dap);
+            assert!(options.sandbox);
+        }
+        other => panic!("unexpected parse {:?}", other),
+    }
+    assert_eq!(
+        args("dap a.grib"),
+        Err(CliError::UnexpectedArgument("a.grib".to_string()))
+    );
+    match args("lsp") {
+        Ok(Cli::Exec(options)) => assert_eq!(options.command, Command::Lsp),
+        other => panic!("unexpected parse {:?}", other),
+    }
+    match args("fmt --check a.grib") {
+        Ok(Cli::Exec(options)) => {
+            assert_eq!(options.command, Command::Fmt);
+            assert!(options.check && !options.write);
+        }
+        other => panic!("unexpected parse {:?}", other),
+    }
+    assert_eq!(
+        args("fmt --write -"),
+        Err(CliError::UnexpectedArgument("-".to_string()))
+    );
+    assert_eq!(
+        args("fmt --check -w a.grib"),
+        Err(CliError::ConflictingOptions(
+            "-w".to_string(),
+            "--check".to_string()
+        ))
+    );
+    assert_eq!(
+        args("check -w a.grib"),
+        Err(CliError::UnknownOption("-w".to_string()))
+    );
+    match args("lint -A unread-im --warn unused-parameter a.grib") {
+        Ok(Cli::Exec(options)) => {
+            assert_eq!(options.command, Command::Lint);
+            assert!(!options.lints.is_enabled(&LintKind::UnreadIm));
+            assert!(options.lints.is_enabled(&LintKind::UnusedParameter));
+            assert!(options.lints.is_enabled(&LintKind::UnusedVariable));
+        }
+        other => panic!("unexpected parse {:?}", other),
+    }
+    match args("--seed 7 a.grib") {
+        Ok(Cli::Exec(options)) => assert_eq!(options.seed, Some(7)),
+        other => panic!("unexpected parse {:?}", other),
+    }
+    assert_eq!(
+        args("--seed -1 a.grib"),
+        Err(CliError::InvalidValue(
+            "--seed".to_string(),
+            "-1".to_string()
+        ))
+    );
+    match args("test --junit out.xml tests") {
+        Ok(Cli::Exec(options)) => {
+            assert_eq!(options.command, Command::Test);
+            assert_eq!(options.input, Input::File("tests".to_string()));
+            assert_eq!(options.junit_report, Some("out.xml".to_string()));
+            assert_eq!(options.json_report, None);
+        }
+        other => panic!("unexpected parse {:?}", other),
+    }
+    assert_eq!(
+        args("test -"),
+        Err(CliError::UnexpectedArgument("-".to_string()))
+    );
+    assert_eq!(
+        args("lint -A unused a.grib"),
+        Err(CliError::InvalidValue(
+            "-A".to_string(),
+            "unused".to_string()
+        ))
+    );
+}
+
+#[test]
+fn process_package() -> Result<(), Box<dyn std::error::Error>> {
+    let run = |source: &str, path: &Path| -> Result<String, Box<dyn std::error::Error>> {
+        let mut program = ast(lex(source)?, path)?;
+        ref_check(&mut program).map_err(|_| GenericErr)?;
+
+        let capture = Capture::new();
+        let config = RuntimeConfig {
+            args: vec!["one".to_string(), "two words".to_string()],
+            console: capture.console(),
+            ..RuntimeConfig::default()
+        };
+        execute(&program, config).map_err(|_| GenericErr)?;
+        Ok(capture.text())
+    };
+
+    let name = format!("GRIBC_PROCESS_TEST_{}", std::process::id());
+    let source = format!(
+        r#"
+        import * from "process"; import |println| from "console"; import |arrlen| from "array";
+        decl passed = args();
+        println(arrlen(passed)); println(passed[0]); println(passed[1]);
+        println(env("{name}"));
+        setEnv("{name}", "a value");
+        println(env("{name}"));
+        setEnv("{name}", nil);
+        println(env("{name}"));
+        println(scriptPath());
+        "#,
+        name = name
+    );
+
+    let script = std::env::temp_dir().join(format!("gribc_process_{}.grib", std::process::id()));
+    fs::write(&script, &source)?;
+    let canonical = fs::canonicalize(&script)?;
+    let output = run(&source, &script);
+    fs::remove_file(&script)?;
+    assert_eq!(
+        output?,
+        format!(
+            "2\none\ntwo words\nnil\na value\nnil\n{}\n",
+            canonical.display()
+        )
+    );
+    assert_eq!(std::env::var_os(&name), None);
+
+    // Programs read from stdin have no path
+    let output = run(&source, Path::new("<stdin>"))?;
+    assert!(output.ends_with("\nnil\nnil\n"), "{}", output);
+    Ok(())
+}
+
+/// Runs code like the REPL does, keeping declarations between inputs
+struct TestSession {
+    program: Program,
+    scope: ReplScope,
+    session: Session,
+}
+
+impl TestSession {
+    fn new(config: RuntimeConfig) -> Self {
+        Self {
+            program: Program::new(),
+            scope: ReplScope::new(),
+            session: Session::new(config),
+        }
+    }
+
+    /// Runs an input, returning the value of its last expression or the runtime error it raised
+    fn run(
+        &mut self,
+        source: &str,
+    ) -> Result<Result<Option<String>, RuntimeError>, Box<dyn Error>> {
+        let program = &mut self.program;
+        program.body = parse_input(lex(source)?, program, Path::new("."))?;
+        self.scope.check(program).map_err(|_| GenericErr)?;
+        Ok(self.session.run(program))
+    }
+
+    fn eval(&mut self, source: &str) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(self.run(source)?.map_err(|_| GenericErr)?)
+    }
+}
+
+/// The config the runtime tests start from
+fn test_config() -> RuntimeConfig {
+    RuntimeConfig {
+        cleanup_after: 1000,
+        gc_mode: GcMode::Generational,
+        gc_stats: false,
+        stack_limit: DEFAULT_STACK_LIMIT,
+        report_errors: false,
+        args: Vec::new(),
+        limits: Limits::default(),
+        sandbox: false,
+        console: Console::Std,
+        seed: None,
+    }
+}
+
+/// Runs source code in a new session with the given config
+fn eval_source(source: &str, config: RuntimeConfig) -> Result<Option<String>, Box<dyn Error>> {
+    TestSession::new(config).eval(source)
+}
+
+#[test]
+fn repl_state_persists() -> Result<(), Box<dyn std::error::Error>> {
+    let mut repl = TestSession::new(test_config());
+    let mut eval = |source: &str| repl.eval(source);
+
+    assert_eq!(eval("decl x = 1;")?, None);
+    assert_eq!(eval("proc double |n| { return n * 2; }")?, None);
+    assert_eq!(eval("decl inc = lam || { x += 1; return x; };")?, None);
+    assert_eq!(eval("inc();")?, Some("2".to_string()));
+    assert_eq!(eval("double(x) + 1;")?, Some("5".to_string()));
+    assert!(eval("y;").is_err());
+    assert_eq!(eval("[x, x][1];")?, Some("2".to_string()));
+    Ok(())
+}
+
+#[test]
+fn socket_loopback() -> Result<(), Box<dyn std::error::Error>> {
+    let mut repl = TestSession::new(test_config());
+    let mut eval = |source: &str| repl.eval(source);
+
+    eval(r#"import |newSocket listen| from "syncio"; import |arrlen| from "array";"#)?;
+    eval(r#"im server = listen("127.0.0.1:0");"#)?;
+    eval("im client = newSocket(server.address()); im conn = server.accept();")?;
+    assert_eq!(eval("client.write([1, 2, 300]);")?, None);
+
+    eval("im received = conn.read();")?;
+    assert_eq!(eval("arrlen(received);")?, Some("3".to_string()));
+    assert_eq!(eval("received[2];")?, Some("255".to_string()));
+
+    eval(r#"conn.write("hi"); im reply = client.read(1);"#)?;
+    assert_eq!(eval("arrlen(reply) + reply[0];")?, Some("105".to_string()));
+    for size in &["0", "-1"] {
+        assert_eq!(
+            eval(&format!("client.read({});", size))?,
+            Some("[ERR: IO: the number of bytes to read must be positive]".to_string())
+        );
+    }
+
+    eval("client.close();")?;
+    assert_eq!(eval("arrlen(conn.read());")?, Some("0".to_string()));
+    eval("server.close();")?;
+    assert_eq!(
+        eval("server.accept();")?,
+        Some("[ERR: IO: socket is closed]".to_string())
+    );
+
+    // Waiting on a socket still stops at the time limit
+    let time = Duration::from_millis(50);
+    let mut limited = TestSession::new(RuntimeConfig {
+        limits: Limits {
+            time: Some(time),
+            ..Limits::default()
+        },
+        ..test_config()
+    });
+    limited.eval(r#"import |newSocket listen| from "syncio";"#)?;
+    limited.eval(r#"im server = listen("127.0.0.1:0");"#)?;
+    let timed_out = Some(RuntimeErrorKind::LimitExceeded(Limit::Time(time)));
+    assert_eq!(
+        limited.run("server.accept();")?.err().map(|e| e.kind),
+        timed_out
+    );
+    limited.eval("im client = newSocket(server.address()); im conn = server.accept();")?;
+    assert_eq!(
+        limited.run("client.read();")?.err().map(|e| e.kind),
+        timed_out
+    );
+    assert_eq!(
+        limited.eval("conn.write([7]); client.read()[0];")?,
+        Some("7".to_string())
+    );
+    Ok(())
+}
+
+/// Reads the packages in the standard library reference as a
+/// map from package names to each function's parameter count
+fn spec_functions(spec: &str) -> HashMap<String, HashMap<String, usize>> {
+    let mut packages = HashMap::new();
+    let mut current = None;
+
+    for line in spec.lines() {
+        if let Some(heading) = line.strip_prefix("### ") {
+            let name = heading.trim_matches(|c| c == '`' || c == '"');
+            current = Some(name.to_string());
+            packages.insert(name.to_string(), HashMap::new());
+        } else if let (Some(row), Some(pkg)) = (line.strip_prefix("| `"), &current) {
+            let open = row
+                .find('(')
+                .expect("Documented functions have parameter lists");
+            let close = row
+                .find(')')
+                .expect("Documented functions have parameter lists");
+            let params = row[open + 1..close]
+                .split(',')
+                .filter(|p| !p.trim().is_empty());
+
+            packages
+                .get_mut(pkg)
+                .unwrap()
+                .insert(row[..open].to_string(), params.count());
+        }
+    }
+
+    packages
+}
+
+#[test]
+fn std_matches_spec() {
+    let spec = spec_functions(&fs::read_to_string("spec/std.md").unwrap());
+    let manifest = std_manifest();
+
+    let mut documented = spec.keys().cloned().collect::<Vec<_>>();
+    let mut registered = manifest
+        .iter()
+        .map(|p| p.name.to_string())
+        .collect::<Vec<_>>();
+    documented.sort();
+    registered.sort();
+    assert_eq!(documented, registered, "packages differ");
+
+    for package in manifest {
+        let spec_fns = &spec[package.name];
+        let mut names = Vec::new();
+
+        for function in package.functions {
+            for name in std::iter::once(&function.name).chain(function.aliases) {
+                names.push(name.to_string());
+                let params = spec_fns.get(*name);
+                assert!(
+                    params.is_some(),
+                    "{}.{} is undocumented",
+                    package.name,
+                    name
+                );
+                if !function.variadic {
+                    assert_eq!(
+                        params,
+                        Some(&function.params.len()),
+                        "{}.{} has the wrong arity",
+                        package.name,
+                        name
+                    );
+                }
+            }
+        }
+
+        let mut documented = spec_fns.keys().cloned().collect::<Vec<_>>();
+        documented.sort();
+        names.sort();
+        assert_eq!(documented, names, "functions in {} differ", package.name);
+    }
+}
+
+#[test]
+fn vm_control_flow() -> Result<(), Box<dyn std::error::Error>> {
+    let source = r#"
+        proc classify |n| {
+            if n < 0 { return "neg"; } else if n == 0 { return "zero"; }
+            return "pos";
+        }
+
+        decl sum = 0;
+        for decl i = 0; i < 10; i += 1 {
+            decl sq = i * i;
+            if i == 2 { continue; }
+            if sq > 40 { break; }
+            decl j = 0;
+            while true {
+                j += 1;
+                decl k = j;
+                if k > i { break; }
+                if k % 2 == 0 { continue; }
+                sum += k;
+            }
+        }
+
+        classify(0) + classify(-1) + (sum + (false || 1 && "x"));
+    "#;
+
+    let config = RuntimeConfig {
+        cleanup_after: 10,
+        ..test_config()
+    };
+    assert_eq!(eval_source(source, config)?, Some("zeroneg28".to_string()));
+    Ok(())
+}
+
+#[test]
+fn compiled_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let source = r#"
+        import |println| from "console";
+        proc far |x| { return x < Infinity; }
+        decl point = #{ x -> 1, double { get { this.x * 2 } } };
+        decl add = lam |a b| { a + b };
+        while true { println(add(point.double, 1)); break; }
+    "#;
+    let mut tree = ast(lex(source)?, Path::new("compiled.grib"))?;
+    ref_check(&mut tree).map_err(|_| GenericErr)?;
+
+    let compiled = write_compiled(&tree)?;
+    assert_eq!(read_compiled(&compiled)?, tree);
+
+    let (_, body) = compiled.split_once('\n').unwrap_or_default();
+    assert_eq!(read_compiled(body), Err(LoadError::MissingHeader));
+    assert_eq!(
+        read_compiled(&format!("gribc-program 0\n{}", body)),
+        Err(LoadError::Version {
+            found: "0".to_string()
+        })
+    );
+    assert!(matches!(
+        read_compiled(&compiled.replace("\"Lambda\":0", "\"Lambda\":7")),
+        Err(LoadError::InvalidReference(_))
+    ));
+    assert!(compiled.contains("\"StackOffset\":1"));
+    assert_eq!(
+        read_compiled(&compiled.replace("\"StackOffset\":", "\"StackOffset\":9")),
+        Err(LoadError::InvalidReference("stack offset 91".to_string()))
+    );
+    assert!(matches!(
+        read_compiled(&compiled[..compiled.len() / 2]),
+        Err(LoadError::Malformed(_))
+    ));
+
+    // Host packages aren't saved, so programs importing them are refused
+    let mut host = HostRegistry::new();
+    host.package("host")?
+        .function("f", |_, _, _| GribValue::Nil);
+    let source = r#"import |f| from "host"; f();"#;
+    let mut tree = ast_with_host(lex(source)?, Path::new("host.grib"), host)?;
+    ref_check(&mut tree).map_err(|_| GenericErr)?;
+    assert_eq!(
+        write_compiled(&tree),
+        Err(SaveError::HostImport("host".to_string()))
+    );
+    Ok(())
+}
+
+#[test]
+fn try_catch_unwinds() -> Result<(), Box<dyn std::error::Error>> {
+    let source = r#"
+        proc check |n| {
+            decl local = n;
+            if n > 1 { throw n * 10; }
+            return local;
+        }
+
+        decl out = "";
+        for decl i = 0; i < 5; i += 1 {
+            try {
+                decl scratch = i;
+                out += check(i);
+                if i == 0 { continue; }
+            } catch |e| {
+                decl later = lam { e };
+                out += "(" + later() + ")";
+                if e == 30 { break; }
+            }
+        }
+        try { throw nil; } catch { out += "!"; }
+        out;
+    "#;
+
+    let mut repl = TestSession::new(RuntimeConfig {
+        cleanup_after: 10,
+        ..test_config()
+    });
+    assert_eq!(repl.eval(source)?, Some("01(20)(30)!".to_string()));
+
+    let err = repl
+        .run("throw \"oops\";")?
+        .invert()
+        .map_err(|_| GenericErr)?;
+    assert_eq!(err.kind, RuntimeErrorKind::Uncaught("oops".to_string()));
+    Ok(())
+}
+
+#[test]
+fn throw_after_try_is_uncaught() -> Result<(), Box<dyn std::error::Error>> {
+    let source = r#"try { decl a = 1; } catch |e| {} throw "boom";"#;
+    let err = TestSession::new(test_config())
+        .run(source)?
+        .invert()
+        .map_err(|_| GenericErr)?;
+    assert_eq!(err.kind, RuntimeErrorKind::Uncaught("boom".to_string()));
+    Ok(())
+}
+
+#[test]
+fn stack_overflow_is_catchable() -> Result<(), Box<dyn std::error::Error>> {
+    let source = r#"
+        import |errVal| from "err";
+        proc count |n| { if n == 0 { return 0; } return 1 + count(n - 1); }
+        proc forever |n| { decl next = n + 1; return forever(next); }
+
+        decl out = "";
+        try { forever(0); } catch |e| { out += errVal(e); }
+        out + " " + count(20000);
+    "#;
+
+    // Grib calls don't use the native stack, so this
+    // runs on a test thread's default stack size
+    let config = RuntimeConfig {
+        stack_limit: 50_000,
+        ..test_config()
+    };
+    assert_eq!(
+        eval_source(source, config)?,
+        Some("Grib stack overflow 20000".to_string())
+    );
+    Ok(())
+}
+
+#[test]
+fn overflow_after_try_catch() -> Result<(), Box<dyn std::error::Error>> {
+    let source = r#"
+        proc forever |n| { return forever(n + 1); }
+        try { decl a = 1; } catch |e| {}
+        forever(0);
+    "#;
+    let config = RuntimeConfig {
+        stack_limit: 1000,
+        ..test_config()
+    };
+    let err = TestSession::new(config)
+        .run(source)?
+        .invert()
+        .map_err(|_| GenericErr)?;
+    assert_eq!(err.kind, RuntimeErrorKind::StackOverflow);
+    Ok(())
+}
+
+#[test]
+fn gc_keeps_reachable_values() -> Result<(), Box<dyn std::error::Error>> {
+    let source = r#"
+        import |clearGc| from "meta";
+        import |push| from "array";
+
+        decl list = nil, kept = [];
+        for decl i = 0; i < 100000; i += 1 {
+            list = [i, list];
+            if i % 1000 == 0 { push(kept, "n" + i); }
+        }
+        clearGc();
+
+        decl sum = 0;
+        while list != nil { sum += list[0]; list = list[1]; }
+        sum + " " + kept[99];
+    "#;
+
+    for &gc_mode in &[GcMode::Full, GcMode::Generational] {
+        // Marking a long chain of arrays doesn't recurse on the native stack
+        let config = RuntimeConfig {
+            cleanup_after: 10,
+            gc_mode,
+            ..test_config()
+        };
+        assert_eq!(
+            eval_source(source, config)?,
+            Some("4999950000 n99000".to_string())
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn gc_stats_count_live_values() -> Result<(), Box<dyn std::error::Error>> {
+    let mut repl = TestSession::new(test_config());
+    let mut eval = |source: &str| repl.eval(source);
+
+    eval(r#"import |clearGc gcStats| from "meta";"#)?;
+    eval(r#"decl kept = [#{ a -> "some text" }, [1, 2]]; [3]; "garbage" + 1;"#)?;
+    eval("clearGc(); decl stats = gcStats();")?;
+    assert_eq!(eval("stats.fullCollections;")?, Some("1".to_string()));
+    assert_eq!(eval("stats.freed;")?, Some("3".to_string()));
+    // Variables declared in the REPL are captured so they outlive each input
+    assert_eq!(
+        eval(
+            r#"stats.arrays + " " + stats.hashes + " " + stats.strings + " " + stats.liveSlots;"#
+        )?,
+        Some("2 1 1 5".to_string())
+    );
+    assert_eq!(eval("stats.bytes > 0;")?, Some("true".to_string()));
+
+    // Garbage that hasn't been collected yet isn't live
+    eval("decl before = gcStats(); [4, [5]]; decl after = gcStats();")?;
+    assert_eq!(
+        eval("after.garbageSlots - before.garbageSlots;")?,
+        Some("2".to_string())
+    );
+    assert_eq!(
+        eval("after.arrays - before.arrays;")?,
+        Some("0".to_string())
+    );
+    Ok(())
+}
+
+#[test]
+fn heap_snapshot_follows_gc_edges() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("gribc-snapshot-{}.json", std::process::id()));
+    let source = format!(
+        r#"
+        import |heapSnapshot| from "meta";
+        decl counter = 0;
+        decl obj = #{{ items -> [1, 2], count {{ get {{ counter + 1 }} }} }};
+        [3, [4]];
+        heapSnapshot({:?});
+        "#,
+        path.to_string_lossy()
+    );
+
+    assert_eq!(eval_source(&source, test_config())?, None);
+
+    let snapshot: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+    fs::remove_file(&path)?;
+
+    let slots = snapshot["slots"].as_array().ok_or(GenericErr)?;
+    let slot = |index: &serde_json::Value| {
+        slots
+            .iter()
+            .find(|slot| &slot["index"] == index)
+            .expect("References point to live slots")
+    };
+    let slot_kind = |index: &serde_json::Value| slot(index)["kind"].as_str().unwrap().to_string();
+    let kinds = |slot: &serde_json::Value| {
+        let mut kinds = slot["references"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(slot_kind)
+            .collect::<Vec<_>>();
+        kinds.sort();
+        kinds
+    };
+
+    // Garbage that hasn't been collected yet is left out
+    assert_eq!(slots.iter().filter(|s| s["kind"] == "array").count(), 1);
+
+    // The getter's closure keeps `counter` alive through the hash
+    let hash = slots
+        .iter()
+        .find(|slot| slot["kind"] == "hash")
+        .ok_or(GenericErr)?;
+    assert_eq!(kinds(hash), vec!["array", "capturedStack"]);
+
+    let roots = snapshot["roots"].as_array().ok_or(GenericErr)?;
+    let counter = &roots[0]["references"][0];
+    assert_eq!(roots[0]["kind"], "Stack");
+    assert_eq!(slot_kind(counter), "captured");
+    assert!(slots
+        .iter()
+        .any(|s| s["kind"] == "capturedStack" && s["references"][0] == *counter));
+    Ok(())
+}
+
+#[test]
+fn embedded_interpreter() -> Result<(), Box<dyn std::error::Error>> {
+    let mut grib = Interpreter::default();
+
+    assert_eq!(
+        grib.eval(
+            r#"
+            decl loaded = 1;
+            proc describe |name scores| {
+                decl total = 0;
+                for decl i = 0; i < arrlen(scores); i += 1 { total += scores[i]; }
+                return #{ name -> name, total -> total, double { get { total * 2 } }, self -> nil };
+            }
+            import |arrlen| from "array";
+            loaded;
+            "#
+        )?,
+        Value::Number(1.)
+    );
+
+    let mut expected = BTreeMap::new();
+    expected.insert("double".to_string(), Value::Number(13.));
+    expected.insert("name".to_string(), Value::from("ada"));
+    expected.insert("self".to_string(), Value::Nil);
+    expected.insert("total".to_string(), Value::Number(6.5));
+    assert_eq!(
+        grib.call("describe", &["ada".into(), vec![1., 2., 3.5].into()])?,
+        Value::Hash(expected)
+    );
+
+    assert_eq!(
+        grib.eval("decl loop = [1]; loop[0] = loop; [loop, lam {}];")?,
+        Value::Array(vec![
+            Value::Array(vec![Value::Opaque("array")]),
+            Value::Opaque("callable")
+        ])
+    );
+
+    assert!(matches!(
+        grib.call("missing", &[]),
+        Err(GribError::UnknownProcedure(ref name)) if name == "missing"
+    ));
+    match grib.eval("decl x = ;") {
+        Err(GribError::Syntax(diagnostic)) => {
+            assert_eq!(diagnostic.location.map(|l| l.get_col()), Some(9))
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+    match grib.eval("proc boom || { throw \"bad\"; } boom();") {
+        Err(GribError::Runtime(error)) => {
+            assert_eq!(error.kind, RuntimeErrorKind::Uncaught("bad".to_string()));
+            assert_eq!(error.trace[0].name, "boom");
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // Failed code doesn't disturb what was loaded before it
+    assert_eq!(grib.eval("loaded;")?, Value::Number(1.));
+    assert!(matches!(
+        grib.load_file("samples/missing.grib"),
+        Err(GribError::Io { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn host_packages() -> Result<(), Box<dyn std::error::Error>> {
+    use gribc::runtime::host::ReservedName;
+    use gribc::runtime::values::GribValue;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counted = calls.clone();
+
+    let mut grib = Interpreter::default();
+    grib.package("host")?
+        .function("sum", |_, _, args| {
+            let total = args.iter().fold(0., |total, arg| match arg {
+                GribValue::Number(n) => total + n,
+                _ => total,
+            });
+            GribValue::Number(total)
+        })
+        .function("tick", move |_, _, _| {
+            GribValue::Number((counted.fetch_add(1, Ordering::SeqCst) + 1) as f64)
+        });
+
+    assert_eq!(
+        grib.eval("import |sum| from \"host\"; sum(1, 2, 3.5);")?,
+        Value::Number(6.5)
+    );
+    assert_eq!(
+        grib.eval("import * from \"host\"; tick(); tick();")?,
+        Value::Number(2.)
+    );
+    assert_eq!(
+        grib.eval("import h from \"host\"; [h.tick, h.sum(4)];")?,
+        Value::Array(vec![Value::Opaque("callable"), Value::Number(4.)])
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    // String arguments can be literals or built at runtime
+    grib.package("host")?
+        .function("greet", |program, runtime, args| {
+            let names = args
+                .iter()
+                .map(|arg| arg.as_str(program, runtime).into_owned())
+                .collect::<Vec<_>>();
+            GribValue::String(runtime.alloc_str(format!("hello {}", names.join(" and "))))
+        });
+    assert_eq!(
+        grib.eval(
+            "import |greet| from \"host\"; im other = \"gr\" + \"ib\"; greet(\"you\", other);"
+        )?,
+        Value::from("hello you and grib")
+    );
+
+    // Functions registered later can be imported by later code
+    grib.package("host")?
+        .function("twice", |_, _, args| match args.first() {
+            Some(GribValue::Number(n)) => GribValue::Number(n * 2.),
+            _ => GribValue::Nil,
+        });
+    assert_eq!(
+        grib.eval("import |twice| from \"host\"; twice(sum(1, 2));")?,
+        Value::Number(6.)
+    );
+
+    assert!(matches!(
+        grib.eval("import |missing| from \"host\";"),
+        Err(GribError::Syntax(_))
+    ));
+    // Packages in the standard library can't be replaced
+    assert_eq!(
+        grib.package("math").err(),
+        Some(ReservedName("math".to_string()))
+    );
+
+    // Sandboxed code can only call the functions that stay inside the interpreter
+    let mut sandboxed = Interpreter::new(RuntimeConfig {
+        sandbox: true,
+        ..RuntimeConfig::default()
+    });
+    sandboxed
+        .package("host")?
+        .function("pure", |_, _, _| GribValue::Number(1.))
+        .escaping_function("launch", |_, _, _| GribValue::Nil);
+    assert_eq!(
+        sandboxed.eval("import |pure| from \"host\"; pure();")?,
+        Value::Number(1.)
+    );
+    match sandboxed.eval("import |launch| from \"host\"; launch();") {
+        Err(GribError::Runtime(error)) => assert_eq!(
+            error.kind,
+            RuntimeErrorKind::Sandboxed("host.launch".to_string())
+        ),
+        other => panic!("unexpected result {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn resource_limits() -> Result<(), Box<dyn std::error::Error>> {
+    let limited = |limits: Limits, source: &str| {
+        let mut grib = Interpreter::new(RuntimeConfig {
+            limits,
+            sandbox: true,
+            ..RuntimeConfig::default()
+        });
+        match grib.eval(source) {
+            Err(GribError::Runtime(error)) => Some(error.kind),
+            _ => None,
+        }
+    };
+
+    // Limits can't be caught by try blocks
+    let spin = "try { while true {} } catch |e| {}";
+    assert_eq!(
+        limited(
+            Limits {
+                steps: Some(10_000),
+                ..Limits::default()
+            },
+            spin
+        ),
+        Some(RuntimeErrorKind::LimitExceeded(Limit::Steps(10_000)))
+    );
+    let time = Duration::from_millis(50);
+    assert_eq!(
+        limited(
+            Limits {
+                time: Some(time),
+                ..Limits::default()
+            },
+            spin
+        ),
+        Some(RuntimeErrorKind::LimitExceeded(Limit::Time(time)))
+    );
+
+    let heap = Limits {
+        heap_slots: Some(100),
+        ..Limits::default()
+    };
+    assert_eq!(
+        limited(heap.clone(), "decl a = []; while true { a = [a]; }"),
+        Some(RuntimeErrorKind::LimitExceeded(Limit::HeapSlots(100)))
+    );
+    // Garbage is collected before the limit is enforced
+    assert_eq!(
+        limited(heap, "for decl i = 0; i < 1000; i += 1 { [i]; }"),
+        None
+    );
+    assert_eq!(
+        limited(
+            Limits {
+                call_depth: Some(20),
+                ..Limits::default()
+            },
+            "proc f |n| { return f(n + 1); } f(0);"
+        ),
+        Some(RuntimeErrorKind::LimitExceeded(Limit::CallDepth(20)))
+    );
+
+    let unlimited = Limits::default();
+    assert_eq!(
+        limited(
+            unlimited.clone(),
+            "import |readFile| from \"syncio\"; readFile(\"a\");"
+        ),
+        Some(RuntimeErrorKind::Sandboxed("syncio.readFile".to_string()))
+    );
+    assert_eq!(
+        limited(
+            unlimited.clone(),
+            "import |exit| from \"meta\"; exit(false);"
+        ),
+        Some(RuntimeErrorKind::Sandboxed("meta.exit".to_string()))
+    );
+    assert_eq!(
+        limited(unlimited, "import |env| from \"process\"; env(\"HOME\");"),
+        Some(RuntimeErrorKind::Sandboxed("process.env".to_string()))
+    );
+
+    // Modules outside the current directory aren't read at all
+    let secret = std::env::temp_dir().join(format!("gribc_secret_{}.txt", std::process::id()));
+    std::fs::write(&secret, "secret line one\n")?;
+    let mut sandboxed = Interpreter::new(RuntimeConfig {
+        sandbox: true,
+        ..RuntimeConfig::default()
+    });
+    let import = format!("import * from {:?};", secret.display().to_string());
+    let result = sandboxed.eval(&import);
+    std::fs::remove_file(&secret)?;
+    match result {
+        Err(GribError::Syntax(diagnostic)) => {
+            assert!(diagnostic.message.contains("in the sandbox"));
+            assert!(!diagnostic.to_string().contains("secret line one"));
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    // A session can keep going after one of its inputs runs out
+    let mut grib = Interpreter::new(RuntimeConfig {
+        limits: Limits {
+            steps: Some(1000),
+            ..Limits::default()
+        },
+        ..RuntimeConfig::default()
+    });
+    grib.eval("decl n = 0;")?;
+    assert!(grib.eval("while true { n += 1; }").is_err());
+    assert_eq!(grib.eval("n > 0;")?, Value::Bool(true));
+    Ok(())
+}
+
+/// A pause seen by the scripted debugger: the line, the call depth, and the locals
+type Stop = (StopReason, usize, usize, Vec<(String, Value)>);
+
+/// Resumes with each of its commands in turn, recording where the program paused
+struct Scripted {
+    commands: Vec<Resume>,
+    stops: Rc<RefCell<Vec<Stop>>>,
+}
+
+impl Debugger for Scripted {
+    fn paused(&mut self, paused: &mut Paused, reason: StopReason) -> Resume {
+        let locals = paused
+            .locals()
+            .into_iter()
+            .map(|(name, value)| (name, paused.copy(&value)))
+            .collect();
+        let line = paused.location().get_row();
+        self.stops
+            .borrow_mut()
+            .push((reason, line, paused.depth(), locals));
+        match self.commands.is_empty() {
+            true => Resume::Continue,
+            false => self.commands.remove(0),
+        }
+    }
+}
+
+#[test]
+fn debugger_steps() -> Result<(), Box<dyn std::error::Error>> {
+    let source = "\
+proc add |a b| {
+    decl sum = a + b;
+    return sum;
+}
+decl x = 1;
+decl y = add(x, 2);
+decl h = #{ n -> y, value -> lam || { return this.n; } };
+decl z = h.value();
+";
+    let path = Path::new("debugged.grib");
+    let mut program = ast(lex(source)?, path)?;
+    ref_check(&mut program).map_err(|_| GenericErr)?;
+
+    let run = |breakpoints: Breakpoints, start: Resume, commands: Vec<Resume>| {
+        let stops = Rc::new(RefCell::new(Vec::new()));
+        let debugger = Scripted {
+            commands,
+            stops: stops.clone(),
+        };
+        let config = RuntimeConfig {
+            report_errors: false,
+            ..RuntimeConfig::default()
+        };
+        let result = debug(&program, config, Box::new(debugger), breakpoints, start);
+        let stops = stops.borrow().clone();
+        (result, stops)
+    };
+    let lines = |stops: &[Stop]| stops.iter().map(|stop| stop.1).collect::<Vec<_>>();
+    let n = |n: i32| Value::from(n);
+
+    // Without breakpoints, continuing never pauses
+    let (result, stops) = run(Breakpoints::new(), Resume::Continue, vec![]);
+    assert!(result.is_ok());
+    assert!(stops.is_empty());
+
+    // Breakpoints show the locals of the function they're in
+    let mut breakpoints = Breakpoints::new();
+    breakpoints.add(path, 3);
+    let (_, stops) = run(breakpoints.clone(), Resume::Continue, vec![]);
+    assert_eq!(
+        stops,
+        vec![(
+            StopReason::Breakpoint,
+            3,
+            1,
+            vec![
+                ("a".to_string(), n(1)),
+                ("b".to_string(), n(2)),
+                ("sum".to_string(), n(3))
+            ]
+        )]
+    );
+
+    // Stepping out returns to the caller, then stepping over skips the call to the lambda
+    let (_, stops) = run(
+        breakpoints,
+        Resume::Continue,
+        vec![Resume::StepOut, Resume::StepOver, Resume::StepOver],
+    );
+    assert_eq!(lines(&stops), vec![3, 7, 8]);
+    assert_eq!(
+        stops[2].3,
+        vec![
+            ("x".to_string(), n(1)),
+            ("y".to_string(), n(3)),
+            ("h".to_string(), stops[2].3[2].1.clone())
+        ]
+    );
+
+    // Stepping in enters every function that's called
+    let (_, stops) = run(Breakpoints::new(), Resume::StepIn, vec![Resume::StepIn; 8]);
+    assert_eq!(lines(&stops), vec![5, 6, 2, 3, 7, 8, 7]);
+    assert_eq!(stops[6].2, 1);
+
+    // Stopping ends the program with an error
+    let (result, stops) = run(Breakpoints::new(), Resume::StepIn, vec![Resume::Stop]);
+    assert_eq!(result.map_err(|e| e.kind), Err(RuntimeErrorKind::Stopped));
+    assert_eq!(stops.len(), 1);
+    Ok(())
+}
+
+/// Collects what the debug adapter writes to its client
+#[derive(Clone, Default)]
+struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn debug_adapter() -> Result<(), Box<dyn std::error::Error>> {
+    let source = "\
+import |println| from \"console\";
+proc add |a b| {
+    decl sum = [a, b];
+    return sum;
+}
+decl h = #{ n -> 1 };
+decl pair = add(h.n, 2);
+println(pair[1]);
+";
+    let path = std::env::temp_dir().join("gribc_debug_adapter.grib");
+    fs::write(&path, source)?;
+    let path = fs::canonicalize(&path)?;
+
+    let requests = [
+        json!({ "command": "initialize", "arguments": { "adapterID": "grib" } }),
+        json!({ "command": "launch", "arguments": { "program": path } }),
+        json!({ "command": "setBreakpoints", "arguments": {
+            "source": { "path": path },
+            "breakpoints": [{ "line": 4 }],
+        } }),
+        json!({ "command": "configurationDone" }),
+        json!({ "command": "stackTrace", "arguments": { "threadId": 1 } }),
+        json!({ "command": "scopes", "arguments": { "frameId": 0 } }),
+        json!({ "command": "variables", "arguments": { "variablesReference": 1 } }),
+        json!({ "command": "variables", "arguments": { "variablesReference": 2 } }),
+        json!({ "command": "evaluate", "arguments": { "expression": "sum[1]" } }),
+        json!({ "command": "next", "arguments": { "threadId": 1 } }),
+        json!({ "command": "continue", "arguments": { "threadId": 1 } }),
+        json!({ "command": "disconnect" }),
+    ];
+    let mut input = Vec::new();
+    for (seq, mut request) in requests.iter().cloned().enumerate() {
+        request["seq"] = (seq + 1).into();
+        request["type"] = "request".into();
+        let body = request.to_string();
+        write!(input, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    }
+
+    let output = SharedOutput::default();
+    let config = RuntimeConfig {
+        report_errors: false,
+        ..RuntimeConfig::default()
+    };
+    let code = dap::serve(
+        Box::new(std::io::Cursor::new(input)),
+        Box::new(output.clone()),
+        config,
+    );
+    let _ = fs::remove_file(&path);
+    assert_eq!(code, 0);
+
+    let output = String::from_utf8(output.0.borrow().clone())?;
+    let messages = output
+        .split("Content-Length: ")
+        .filter(|m| !m.is_empty())
+        .map(|m| serde_json::from_str(m.split_once("\r\n\r\n").unwrap().1))
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+
+    let response = |command: &str| {
+        messages
+            .iter()
+            .find(|m| m["type"] == "response" && m["command"] == command)
+            .map(|m| (m["success"].clone(), m["body"].clone()))
+            .unwrap_or_else(|| panic!("no response to {}", command))
+    };
+    let events = messages
+        .iter()
+        .filter(|m| m["type"] == "event")
+        .map(|m| m["event"].as_str().unwrap_or_default())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![
+            "initialized",
+            "stopped",
+            "stopped",
+            "output",
+            "exited",
+            "terminated"
+        ]
+    );
+    let stopped = messages
+        .iter()
+        .filter(|m| m["event"] == "stopped")
+        .map(|m| m["body"]["reason"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(stopped, vec![json!("breakpoint"), json!("step")]);
+
+    let (success, body) = response("setBreakpoints");
+    assert_eq!(success, json!(true));
+    assert_eq!(body["breakpoints"][0]["line"], json!(4));
+
+    let (_, body) = response("stackTrace");
+    let frames = body["stackFrames"].as_array().unwrap();
+    let names = frames.iter().map(|f| f["name"].clone()).collect::<Vec<_>>();
+    let lines = frames.iter().map(|f| f["line"].clone()).collect::<Vec<_>>();
+    assert_eq!(names, vec![json!("add"), json!("<main>")]);
+    assert_eq!(lines, vec![json!(4), json!(7)]);
+    assert_eq!(frames[0]["source"]["path"], json!(path));
+
+    let variables = messages
+        .iter()
+        .filter(|m| m["command"] == "variables")
+        .map(|m| {
+            m["body"]["variables"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| (v["name"].clone(), v["value"].clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        variables,
+        vec![
+            vec![
+                (json!("a"), json!("1")),
+                (json!("b"), json!("2")),
+                (json!("sum"), json!("array(2)"))
+            ],
+            vec![(json!("0"), json!("1")), (json!("1"), json!("2"))],
+        ]
+    );
+    assert_eq!(response("evaluate").1["result"], json!("2"));
+
+    let output = messages.iter().find(|m| m["event"] == "output").unwrap();
+    assert_eq!(output["body"]["output"], json!("2\n"));
+    let exited = messages.iter().find(|m| m["event"] == "exited").unwrap();
+    assert_eq!(exited["body"]["exitCode"], json!(0));
+    Ok(())
+}
+
+#[test]
+fn language_server() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = fs::canonicalize(std::env::temp_dir())?.join("gribc_language_server");
+    fs::create_dir_all(&dir)?;
+    fs::write(
+        dir.join("shapes.grib"),
+        "proc area |w h| {\n    return w * h;\n}\n",
+    )?;
+    let main = dir.join("main.grib");
+    let uri = format!("file://{}", main.display());
+    let source = "\
+import |println| from \"console\";
+import shapes from \"./shapes.grib\";
+import math from \"math\";
+proc double |n| { return n * 2; }
+println(shapes.area(2, 3), double(1), math.max(1));
+";
+    let broken = "import |println| from \"console\";\nprintln(missing);\n";
+
+    let at = |line: usize, character: usize| json!({ "textDocument": { "uri": uri }, "position": { "line": line, "character": character } });
+    let messages = [
+        json!({ "id": 1, "method": "initialize", "params": {} }),
+        json!({ "method": "initialized", "params": {} }),
+        json!({ "method": "textDocument/didOpen", "params": {
+            "textDocument": { "uri": uri, "languageId": "grib", "version": 1, "text": source },
+        } }),
+        json!({ "id": 2, "method": "textDocument/definition", "params": at(4, 16) }),
+        json!({ "id": 3, "method": "textDocument/definition", "params": at(4, 28) }),
+        json!({ "id": 4, "method": "textDocument/hover", "params": at(4, 2) }),
+        json!({ "id": 5, "method": "textDocument/completion", "params": at(4, 43) }),
+        json!({ "id": 6, "method": "textDocument/completion", "params": at(4, 0) }),
+        json!({ "method": "textDocument/didChange", "params": {
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "text": broken }],
+        } }),
+        json!({ "id": 7, "method": "textDocument/rename", "params": at(0, 0) }),
+        json!({ "id": 8, "method": "shutdown" }),
+        json!({ "method": "exit" }),
+    ];
+    let mut input = Vec::new();
+    for mut message in messages.iter().cloned() {
+        message["jsonrpc"] = "2.0".into();
+        let body = message.to_string();
+        write!(input, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    }
+
+    let output = SharedOutput::default();
+    let code = lsp::serve(
+        Box::new(std::io::Cursor::new(input)),
+        Box::new(output.clone()),
+    );
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(code, 0);
+
+    let output = String::from_utf8(output.0.borrow().clone())?;
+    let messages = output
+        .split("Content-Length: ")
+        .filter(|m| !m.is_empty())
+        .map(|m| serde_json::from_str(m.split_once("\r\n\r\n").unwrap().1))
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+    let response = |id: i64| {
+        messages
+            .iter()
+            .find(|m| m["id"] == id)
+            .unwrap_or_else(|| panic!("no response to {}", id))
+    };
+
+    let capabilities = &response(1)["result"]["capabilities"];
+    assert_eq!(capabilities["definitionProvider"], json!(true));
+
+    // Definitions are found in imported modules and the file itself
+    let area = &response(2)["result"];
+    assert!(area["uri"].as_str().unwrap().ends_with("shapes.grib"));
+    assert_eq!(area["range"]["start"], json!({ "line": 0, "character": 5 }));
+    let double = &response(3)["result"];
+    assert_eq!(double["uri"], json!(uri));
+    assert_eq!(
+        double["range"]["start"],
+        json!({ "line": 3, "character": 5 })
+    );
+
+    let hover = response(4)["result"]["contents"]["value"].as_str().unwrap();
+    assert!(hover.contains("console.println(...args)"), "{}", hover);
+
+    let labels = |id: i64| {
+        response(id)["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    let math = labels(5);
+    assert!(math.contains(&"max".to_string()) && math.contains(&"random".to_string()));
+    assert_eq!(labels(6), vec!["println", "shapes", "math", "double"]);
+
+    let diagnostics = messages
+        .iter()
+        .filter(|m| m["method"] == "textDocument/publishDiagnostics")
+        .map(|m| m["params"]["diagnostics"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(diagnostics[0], json!([]));
+    assert_eq!(
+        diagnostics[1][0]["message"],
+        json!("cannot find `missing` in this scope")
+    );
+    assert_eq!(
+        diagnostics[1][0]["range"]["start"],
+        json!({ "line": 1, "character": 8 })
+    );
+
+    assert_eq!(response(7)["error"]["code"], json!(-32601));
+    assert_eq!(response(8)["result"], json!(null));
+    Ok(())
+}
+
+#[test]
+fn lint_warnings() -> Result<(), Box<dyn std::error::Error>> {
+    let source = "import |cos sin| from \"math\";
+import |println| from \"console\";
+import * from \"array\";
+decl cos = 2;
+im k = 3;
+decl _ignored = 4;
+proc f |a b| {
+    return a;
+    println(sin(1));
+}
+decl g = lam |x| { x = 1; };
+g(f(1, 2));
+";
+    let path = Path::new("lint.grib");
+    let warnings = |config: &LintConfig| -> Result<Vec<(String, usize)>, Box<dyn Error>> {
+        let mut program = ast(lex(source)?, path)?;
+        let lints = lint(&mut program, config).map_err(|_| GenericErr)?;
+        Ok(lints
+            .iter()
+            .map(|lint| (lint.kind.str().to_string(), lint.start.get_row()))
+            .collect())
+    };
+    let expected = |pairs: &[(&str, usize)]| -> Vec<(String, usize)> {
+        pairs.iter().map(|(k, row)| (k.to_string(), *row)).collect()
+    };
+
+    assert_eq!(
+        warnings(&LintConfig::new())?,
+        expected(&[
+            ("unused-import", 1),
+            ("unused-import", 3),
+            ("shadowed-import", 4),
+            ("unused-variable", 4),
+            ("unread-im", 5),
+            ("unreachable-code", 9),
+        ])
+    );
+
+    // Assigning to `x` doesn't read it
+
+    let mut config = LintConfig::new();
+    config.disable(LintKind::UnusedImport);
+    config.disable(LintKind::UnusedVariable);
+    config.enable(LintKind::UnusedParameter);
+    assert_eq!(
+        warnings(&config)?,
+        expected(&[
+            ("shadowed-import", 4),
+            ("unread-im", 5),
+            ("unused-parameter", 7),
+            ("unreachable-code", 9),
+            ("unused-parameter", 11),
+        ])
+    );
+
+    let mut program = ast(lex(source)?, path)?;
+    let lints = lint(&mut program, &LintConfig::new()).map_err(|_| GenericErr)?;
+    assert_eq!(
+        Diagnostic::from_lint(&lints[2], &program, path, source).to_string(),
+        "warning: `cos` shadows an import\n  --> lint.grib:4:6\n  |\n4 | decl cos = 2;\n  |      ^^^\nnote: reported by the `shadowed-import` lint\n"
+    );
+    Ok(())
+}
+
+/// Seeds `random` so the programs that use it print the same thing every run
+const GOLDEN_SEED: u64 = 7;
+
+/// Runs a program with its console captured, followed by the error that
+/// ended it if there was one. The program gets `scratch` as its first
+/// argument to write files to
+fn run_captured(path: &Path, scratch: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let source = fs::read_to_string(path)?;
+    let mut program = ast(lex(&source)?, path)?;
+    ref_check(&mut program).map_err(|_| GenericErr)?;
+
+    let capture = Capture::new();
+    let config = RuntimeConfig {
+        console: capture.console(),
+        args: vec![scratch.to_string_lossy().into_owned()],
+        seed: Some(GOLDEN_SEED),
+        ..RuntimeConfig::default()
+    };
+    let result = execute(&program, config);
+
+    let mut output = capture.text();
+    if let Err(e) = result {
+        output += &e.to_string();
+    }
+    Ok(output)
+}
+
+/// Runs every program in tests/run and the samples, comparing what they print
+/// to the `.out` files in tests/run.  Set GRIB_BLESS to rewrite the `.out` files
+#[test]
+fn golden_output() -> Result<(), Box<dyn std::error::Error>> {
+    let run = Path::new("tests/run");
+    let mut programs = vec![];
+    for dir in &["tests/run", "samples"] {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension() == Some(OsStr::new("grib")) {
+                let out = match *dir {
+                    "samples" => run
+                        .join("samples")
+                        .join(path.with_extension("out").file_name().unwrap()),
+                    _ => path.with_extension("out"),
+                };
+                programs.push((path, out));
+            }
+        }
+    }
+    programs.sort();
+    assert!(programs.len() > 5);
+
+    let scratch = std::env::temp_dir().join(format!("gribc-golden-{}", std::process::id()));
+    let bless = std::env::var_os("GRIB_BLESS").is_some();
+    let mut failed = vec![];
+    for (path, out) in programs {
+        let output = run_captured(&path, &scratch)?;
+        if bless {
+            fs::write(&out, &output)?;
+            continue;
+        }
+
+        let expected = fs::read_to_string(&out)
+            .map_err(|e| format!("could not read {}: {}", out.display(), e))?;
+        if output != expected {
+            eprintln!("---- {} ----\n{}", path.display(), output);
+            failed.push(path);
+        }
+    }
+
+    let _ = fs::remove_dir_all(&scratch);
+    assert!(failed.is_empty(), "output differs for {:?}", failed);
+    Ok(())
+}
+
+#[test]
+fn test_runner_reports() -> Result<(), Box<dyn std::error::Error>> {
+    /// Removes the directory when the test ends, even if an assertion fails
+    struct RemoveDir(std::path::PathBuf);
+    impl Drop for RemoveDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    let name = format!("gribc_test_runner_{}", std::process::id());
+    let dir = fs::canonicalize(std::env::temp_dir())?.join(name);
+    let _ = fs::remove_dir_all(&dir);
+    let _cleanup = RemoveDir(dir.clone());
+    fs::create_dir_all(dir.join("lib"))?;
+    fs::write(
+        dir.join("lib/pair.grib"),
+        "public proc pair |a b| { return [a, b]; }\n",
+    )?;
+    fs::write(
+        dir.join("pair_test.grib"),
+        "\
+import |pair| from \"./lib/pair.grib\";
+import |assert assertEq| from \"test\";
+import |err| from \"err\";
+import |exit| from \"meta\";
+public proc test_pair { assertEq(pair(1, #{ a -> 2 }), [1, #{ a -> 2 }]); }
+public proc test_mismatch { assertEq(pair(1, 2), [2, 1]); }
+public proc test_assert { assert(1 > 2, \"1 <= 2\"); }
+public proc test_error { return err(\"bad\"); }
+public proc test_exit { exit(false); }
+proc test_private { assert(false, nil); }
+public proc helper { assert(false, nil); }
+",
+    )?;
+    fs::write(dir.join("broken.grib"), "public proc test_broken { x; }\n")?;
+
+    let files = test_runner::discover(&dir)?;
+    assert_eq!(
+        files,
+        vec![
+            dir.join("broken.grib"),
+            dir.join("lib/pair.grib"),
+            dir.join("pair_test.grib")
+        ]
+    );
+    let suites = files
+        .iter()
+        .map(|file| test_runner::run_suite(file, RuntimeConfig::default))
+        .collect::<Vec<_>>();
+
+    assert!(suites[0].error.is_some());
+    assert!(suites[1].error.is_none() && suites[1].tests.is_empty());
+    let results = suites[2]
+        .tests
+        .iter()
+        .map(|t| {
+            (
+                t.name.as_str(),
+                t.failure.as_ref().map(|f| f.lines().next()),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        results,
+        vec![
+            ("test_pair", None),
+            (
+                "test_mismatch",
+                Some(Some(
+                    "runtime error: assertion failed: expected [2, 1], got [1, 2]"
+                ))
+            ),
+            (
+                "test_assert",
+                Some(Some("runtime error: assertion failed: 1 <= 2"))
+            ),
+            ("test_error", Some(Some("returned an error: \"bad\""))),
+            (
+                "test_exit",
+                Some(Some("runtime error: exited with status 0"))
+            ),
+        ]
+    );
+
+    let report = test_runner::json(&suites);
+    assert_eq!(report["passed"], json!(false));
+    assert_eq!(report["suites"][2]["tests"][0]["passed"], json!(true));
+
+    let xml = test_runner::junit(&suites);
+    assert!(xml.contains("<testsuites tests=\"5\" failures=\"4\" errors=\"1\""));
+    assert!(xml.contains("<failure message=\"returned an error: &quot;bad&quot;\">"));
+    Ok(())
+}
+
+fn format_source(source: &str, path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let (tokens, comments) = lex_with_comments(source)?;
+    let program = ast(tokens.iter().cloned(), path)?;
+    Ok(format(&program, &tokens, comments, source))
+}
+
+fn grib_files(dir: &Path, files: &mut Vec<std::path::PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            grib_files(&path, files)?;
+        } else if path.extension() == Some(OsStr::new("grib")) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn formatter_is_idempotent() -> Result<(), Box<dyn std::error::Error>> {
+    let mut files = vec![];
+    grib_files(Path::new("samples"), &mut files)?;
+    grib_files(Path::new("tests/ast_tests/grib"), &mut files)?;
+    assert!(!files.is_empty());
+
+    for path in files {
+        let source = fs::read_to_string(&path)?;
+        let once = format_source(&source, &path)?;
+        let twice = format_source(&once, &path)?;
+        assert_eq!(
+            once,
+            twice,
+            "formatting {} twice changed it",
+            path.display()
+        );
+
+        let count = |text: &str| lex_with_comments(text).map(|(_, comments)| comments.len());
+        assert_eq!(
+            count(&source)?,
+            count(&once)?,
+            "{} lost comments",
+            path.display()
+        );
+    }
+
+    let source = "import |println| from \"console\";
+@ header
+proc add|a b|{return (a+b)*(a-b);} @ difference of squares
+decl x=lam||{x=(1);},  y   =  #{b->1,a->\"q\\\"\"};
+
+
+if (x) { @{ empty }@ }
+";
+    assert_eq!(
+        format_source(source, Path::new("fmt.grib"))?,
+        "import |println| from \"console\";
+
+@ header
+proc add |a b| {
+    return (a + b) * (a - b);
+} @ difference of squares
+decl x = lam {
+        x = 1;
+    },
+    y = #{ a -> \"q\\\"\", b -> 1 };
+
+if x {
+    @{ empty }@
+}
+"
+    );
+    Ok(())
+}
+
+#[test]
+fn seeded_random() -> Result<(), Box<dyn std::error::Error>> {
+    let seeded = || {
+        Interpreter::new(RuntimeConfig {
+            seed: Some(7),
+            ..RuntimeConfig::default()
+        })
+    };
+    let draws = r#"
+        import |random randInt choice shuffle gaussian| from "math";
+        [random(), randInt(1, 6), choice([1, 2, 3]), shuffle([1, 2, 3, 4]), gaussian(0, 1)];
+    "#;
+    let first = seeded().eval(draws)?;
+    assert_eq!(first, seeded().eval(draws)?);
+
+    let mut grib = seeded();
+    grib.eval(
+        r#"
+        import |random seed randInt choice shuffle gaussian generator| from "math";
+        import |arrlen| from "array";
+        import |typeOf| from "meta";
+        "#,
+    )?;
+    assert_eq!(
+        grib.eval("seed(3); decl a = random(); seed(3); a == random();")?,
+        Value::Bool(true)
+    );
+    assert_eq!(
+        grib.eval(
+            r#"
+            decl inRange = true;
+            for decl i = 0; i < 200; i += 1 {
+                decl n = randInt(-2, 2.5);
+                inRange = inRange && n >= -2 && n <= 2 && n == n - n % 1;
+            }
+            inRange;
+            "#
+        )?,
+        Value::Bool(true)
+    );
+    assert_eq!(grib.eval("typeOf(randInt(3, 2));")?, Value::from("error"));
+    assert_eq!(
+        grib.eval("[choice([]), choice([5]), gaussian(4, 0)];")?,
+        Value::Array(vec![Value::Nil, Value::Number(5.), Value::Number(4.)])
+    );
+
+    // Shuffling rearranges the array it's given
+    assert_eq!(
+        grib.eval(
+            r#"
+            decl arr = [1, 2, 3, 4, 5, 6];
+            decl same = shuffle(arr) == arr;
+            decl total = 0;
+            for decl i = 0; i < arrlen(arr); i += 1 { total += arr[i]; }
+            [same, arrlen(arr), total];
+            "#
+        )?,
+        Value::Array(vec![
+            Value::Bool(true),
+            Value::Number(6.),
+            Value::Number(21.)
+        ])
+    );
+
+    // Generators with the same seed agree no matter what else draws numbers
+    assert_eq!(
+        grib.eval(
+            r#"
+            decl g = generator(5);
+            decl h = generator(5);
+            decl fromG = [g.random(), g.randInt(1, 100), g.choice([1, 2, 3]), g.gaussian(0, 1)];
+            random(); shuffle([1, 2, 3]);
+            decl fromH = [h.random(), h.randInt(1, 100), h.choice([1, 2, 3]), h.gaussian(0, 1)];
+            g.seed(5);
+            decl again = g.random();
+            [fromG[0] == fromH[0], fromG[1] == fromH[1], fromG[2] == fromH[2], fromG[3] == fromH[3], again == fromG[0]];
+            "#
+        )?,
+        Value::Array(vec![Value::Bool(true); 5])
+    );
+    assert!(grib.eval(r#"import methods from "generator";"#).is_err());
+    Ok(())
+}